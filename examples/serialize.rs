@@ -6,6 +6,7 @@ fn main() {
         event_type: EventType::KeyPress(Key::KeyS),
         time: SystemTime::now(),
         name: Some(String::from("S")),
+        device_id: None,
     };
 
     let serialized = serde_json::to_string(&event).unwrap();
@@ -15,4 +16,10 @@ fn main() {
     println!("Serialized event {:?}", serialized);
     println!("Deserialized event {:?}", deserialized);
     assert_eq!(event, deserialized);
+
+    // Events saved before `device_id` existed still load
+    let saved = serialized.replace(",\"device_id\":null", "");
+    assert!(!saved.contains("device_id"));
+    let deserialized: Event = serde_json::from_str(&saved).unwrap();
+    assert_eq!(event, deserialized);
 }
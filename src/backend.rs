@@ -0,0 +1,427 @@
+#[cfg(feature = "unstable_grab")]
+use crate::gamepad::GamepadMapping;
+#[cfg(feature = "unstable_grab")]
+use crate::hotkey::KeyCombo;
+#[cfg(feature = "unstable_grab")]
+use crate::processor::EventProcessor;
+use crate::rdev::{Event, EventType, ListenError, SimulateError};
+#[cfg(feature = "unstable_grab")]
+use crate::rdev::{GrabError, Key};
+#[cfg(feature = "unstable_grab")]
+use std::fmt;
+#[cfg(feature = "unstable_grab")]
+use std::time::Duration;
+
+#[cfg(target_os = "linux")]
+pub use crate::linux::available_backends;
+#[cfg(all(target_os = "linux", feature = "unstable_grab"))]
+use crate::linux::grab_with;
+#[cfg(target_os = "linux")]
+use crate::linux::{listen_with, NativeSimulator};
+
+/// The OS facility used to listen to, simulate or grab events.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
+#[non_exhaustive]
+pub enum Backend {
+    /// Windows hooks and MacOS event taps, the only backend on those platforms.
+    Native,
+    /// Linux: XInput2 to listen and XTest to simulate, through the X display
+    /// (which only sees XWayland clients on Wayland). Grabbing only covers
+    /// keyboards, but needs no access to `/dev/input`.
+    X11,
+    /// Linux: reads `/dev/input` to listen and grab, and creates `/dev/uinput`
+    /// devices to simulate. Works without any display server. (Requires the `evdev` feature)
+    Evdev,
+}
+
+/// What a backend can do in the current session, as reported by `available_backends`.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
+pub struct BackendInfo {
+    pub backend: Backend,
+    pub listen: bool,
+    pub simulate: bool,
+    pub grab: bool,
+}
+
+/// Lists the backends that work in the current session, along with what they can do.
+/// On Linux the X display is reachable or not, `/dev/input` nodes readable or not and
+/// `/dev/uinput` writable or not, so the same binary can pick what works on both X11 and
+/// Wayland desktops.
+///
+/// ```no_run
+/// use rdev::available_backends;
+///
+/// for info in available_backends() {
+///     println!("{:?}", info);
+/// }
+/// ```
+#[cfg(not(target_os = "linux"))]
+pub fn available_backends() -> Vec<BackendInfo> {
+    vec![BackendInfo {
+        backend: Backend::Native,
+        listen: true,
+        simulate: true,
+        grab: cfg!(feature = "unstable_grab"),
+    }]
+}
+
+/// The listen settings besides the backend, see `ListenerBuilder`.
+#[derive(Debug, Copy, Clone, Default)]
+pub(crate) struct ListenOptions {
+    pub gestures: bool,
+}
+
+#[cfg(not(target_os = "linux"))]
+fn listen_with<T>(
+    backend: Option<Backend>,
+    _options: ListenOptions,
+    callback: T,
+) -> Result<(), ListenError>
+where
+    T: FnMut(Event) + 'static,
+{
+    match backend {
+        None | Some(Backend::Native) => crate::listen(callback),
+        Some(_) => Err(ListenError::UnsupportedBackend),
+    }
+}
+
+/// The virtual devices the evdev grab writes the events it lets through to.
+#[cfg(feature = "unstable_grab")]
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Default)]
+#[non_exhaustive]
+pub enum GrabOutput {
+    /// A clone of each grabbed device, with its name and ids.
+    #[default]
+    Clones,
+    /// A single device with the keys, buttons and relative axes of every grabbed
+    /// device, which other programs can be set up to trust once and for all.
+    /// Devices with absolute axes (touchpads, tablets, gamepads) still get a clone,
+    /// as libinput does not handle them on a device that also moves relatively.
+    Merged {
+        name: String,
+        vendor: u16,
+        product: u16,
+    },
+}
+
+#[cfg(feature = "unstable_grab")]
+impl GrabOutput {
+    /// A merged device named "rdev grab output", with vendor and product ids of 0.
+    pub fn merged() -> GrabOutput {
+        GrabOutput::Merged {
+            name: "rdev grab output".to_string(),
+            vendor: 0,
+            product: 0,
+        }
+    }
+}
+
+/// The grab settings besides the backend and processors, see `GrabberBuilder`.
+#[cfg(feature = "unstable_grab")]
+#[derive(Debug, Clone)]
+pub(crate) struct GrabOptions {
+    pub escape_combo: KeyCombo,
+    pub watchdog: Option<Duration>,
+    pub output: GrabOutput,
+    pub gamepad: Option<GamepadMapping>,
+}
+
+#[cfg(feature = "unstable_grab")]
+impl Default for GrabOptions {
+    fn default() -> GrabOptions {
+        GrabOptions {
+            escape_combo: KeyCombo::new(Key::Pause).ctrl().alt(),
+            watchdog: None,
+            output: GrabOutput::default(),
+            gamepad: None,
+        }
+    }
+}
+
+#[cfg(all(not(target_os = "linux"), feature = "unstable_grab"))]
+fn grab_with<T>(
+    backend: Option<Backend>,
+    processors: Vec<Box<dyn EventProcessor>>,
+    options: GrabOptions,
+    callback: T,
+) -> Result<(), GrabError>
+where
+    T: Fn(Event) -> Option<Event> + 'static,
+{
+    match backend {
+        None | Some(Backend::Native) if processors.is_empty() && options.gamepad.is_none() => {
+            crate::grab(callback)
+        }
+        _ => Err(GrabError::UnsupportedBackend),
+    }
+}
+
+#[cfg(not(target_os = "linux"))]
+struct NativeSimulator;
+
+#[cfg(not(target_os = "linux"))]
+impl NativeSimulator {
+    fn new(backend: Option<Backend>) -> Result<NativeSimulator, SimulateError> {
+        match backend {
+            None | Some(Backend::Native) => Ok(NativeSimulator),
+            Some(_) => Err(SimulateError),
+        }
+    }
+
+    fn simulate(&self, event_type: &EventType) -> Result<(), SimulateError> {
+        crate::simulate(event_type)
+    }
+}
+
+/// Builds a `Listener`, see `Listener::builder`.
+#[derive(Debug, Default, Clone)]
+pub struct ListenerBuilder {
+    backend: Option<Backend>,
+    options: ListenOptions,
+}
+
+impl ListenerBuilder {
+    /// Forces a backend instead of picking the best available one.
+    pub fn backend(mut self, backend: Backend) -> ListenerBuilder {
+        self.backend = Some(backend);
+        self
+    }
+
+    /// Also reports the two finger scrolls, the pinches and the swipes made on
+    /// touchscreens and touchpads as `EventType::Gesture`, after the touches making
+    /// them. Off by default. (Linux evdev backend only)
+    pub fn gestures(mut self, gestures: bool) -> ListenerBuilder {
+        self.options.gestures = gestures;
+        self
+    }
+
+    pub fn build(self) -> Listener {
+        Listener {
+            backend: self.backend,
+            options: self.options,
+        }
+    }
+}
+
+/// Same as `listen`, with a choice of backend.
+///
+/// ```no_run
+/// use rdev::{Backend, Listener};
+///
+/// let listener = Listener::builder().backend(Backend::Evdev).build();
+/// // This will block.
+/// if let Err(error) = listener.listen(|event| println!("{:?}", event)) {
+///     println!("Error: {:?}", error)
+/// }
+/// ```
+#[derive(Debug, Clone)]
+pub struct Listener {
+    backend: Option<Backend>,
+    options: ListenOptions,
+}
+
+impl Listener {
+    pub fn builder() -> ListenerBuilder {
+        ListenerBuilder::default()
+    }
+
+    /// The backend that was asked for, `None` means the best available one is
+    /// picked when listening.
+    pub fn backend(&self) -> Option<Backend> {
+        self.backend
+    }
+
+    pub fn listen<T>(&self, callback: T) -> Result<(), ListenError>
+    where
+        T: FnMut(Event) + 'static,
+    {
+        listen_with(self.backend, self.options, callback)
+    }
+}
+
+/// Builds a `Simulator`, see `Simulator::builder`.
+#[derive(Debug, Default, Clone)]
+pub struct SimulatorBuilder {
+    backend: Option<Backend>,
+}
+
+impl SimulatorBuilder {
+    /// Forces a backend instead of picking the best available one.
+    pub fn backend(mut self, backend: Backend) -> SimulatorBuilder {
+        self.backend = Some(backend);
+        self
+    }
+
+    /// Fails when the backend is not supported, or when the uinput devices
+    /// cannot be created for `Backend::Evdev`.
+    pub fn build(self) -> Result<Simulator, SimulateError> {
+        Ok(Simulator {
+            inner: NativeSimulator::new(self.backend)?,
+        })
+    }
+}
+
+/// Same as `simulate`, with a choice of backend.
+///
+/// ```no_run
+/// use rdev::{Backend, EventType, Key, Simulator};
+///
+/// let simulator = Simulator::builder().backend(Backend::Evdev).build().unwrap();
+/// simulator.simulate(&EventType::KeyPress(Key::KeyS)).unwrap();
+/// simulator.simulate(&EventType::KeyRelease(Key::KeyS)).unwrap();
+/// ```
+pub struct Simulator {
+    inner: NativeSimulator,
+}
+
+impl Simulator {
+    pub fn builder() -> SimulatorBuilder {
+        SimulatorBuilder::default()
+    }
+
+    pub fn simulate(&self, event_type: &EventType) -> Result<(), SimulateError> {
+        self.inner.simulate(event_type)
+    }
+}
+
+/// Builds a `Grabber`, see `Grabber::builder`.
+#[cfg(feature = "unstable_grab")]
+#[derive(Default)]
+pub struct GrabberBuilder {
+    backend: Option<Backend>,
+    processors: Vec<Box<dyn EventProcessor>>,
+    options: GrabOptions,
+}
+
+#[cfg(feature = "unstable_grab")]
+impl GrabberBuilder {
+    /// Forces a backend instead of picking the best available one.
+    pub fn backend(mut self, backend: Backend) -> GrabberBuilder {
+        self.backend = Some(backend);
+        self
+    }
+
+    /// Adds a stage rewriting key and button events before the callback sees
+    /// them, like `TapHold`. Processors run in the order they are added, and
+    /// need the evdev backend, the only one picked when there are some.
+    pub fn processor<P>(mut self, processor: P) -> GrabberBuilder
+    where
+        P: EventProcessor + 'static,
+    {
+        self.processors.push(Box::new(processor));
+        self
+    }
+
+    /// Pressing this combo ends the grab right away, releasing every device,
+    /// whatever the processors and the callback do with it. It is checked on
+    /// physical keys, with modifiers on either side. `Ctrl+Alt+Pause` by default,
+    /// `grab` then returns `GrabError::EscapeCombo`. (Linux only)
+    pub fn escape_combo(mut self, combo: KeyCombo) -> GrabberBuilder {
+        self.options.escape_combo = combo;
+        self
+    }
+
+    /// Releases every device from another thread when handling an event takes
+    /// longer than `timeout`, processors and callback included, so a callback stuck
+    /// in a deadlock or an endless loop does not leave the machine without input.
+    /// `grab` then returns `GrabError::WatchdogTimeout` if the callback ever
+    /// returns. Off by default. (Linux evdev grab only)
+    pub fn watchdog(mut self, timeout: Duration) -> GrabberBuilder {
+        self.options.watchdog = Some(timeout);
+        self
+    }
+
+    /// Where the events let through go, a clone of each grabbed device by default.
+    /// (Linux evdev grab only)
+    pub fn output(mut self, output: GrabOutput) -> GrabberBuilder {
+        self.options.output = output;
+        self
+    }
+
+    /// Drives a virtual gamepad with the keyboard and the mouse, for games that
+    /// only take controllers. The keys, buttons and mouse movements the mapping
+    /// uses go to the gamepad instead of the processors and the callback.
+    /// (Linux evdev grab only)
+    pub fn gamepad(mut self, mapping: GamepadMapping) -> GrabberBuilder {
+        self.options.gamepad = Some(mapping);
+        self
+    }
+
+    pub fn build(self) -> Grabber {
+        Grabber {
+            backend: self.backend,
+            processors: self.processors,
+            options: self.options,
+        }
+    }
+}
+
+#[cfg(feature = "unstable_grab")]
+impl fmt::Debug for GrabberBuilder {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("GrabberBuilder")
+            .field("backend", &self.backend)
+            .field("processors", &self.processors.len())
+            .field("options", &self.options)
+            .finish()
+    }
+}
+
+/// Same as `grab`, with a choice of backend and processors.
+///
+/// ```no_run
+/// use rdev::{Backend, Event, EventType, Grabber, Key};
+/// use std::time::Duration;
+///
+/// let grabber = Grabber::builder()
+///     .backend(Backend::Evdev)
+///     .watchdog(Duration::from_secs(2))
+///     .build();
+/// // This will block.
+/// let result = grabber.grab(|event: Event| match event.event_type {
+///     EventType::KeyPress(Key::Tab) => None,
+///     _ => Some(event),
+/// });
+/// if let Err(error) = result {
+///     println!("Error: {:?}", error)
+/// }
+/// ```
+#[cfg(feature = "unstable_grab")]
+pub struct Grabber {
+    backend: Option<Backend>,
+    processors: Vec<Box<dyn EventProcessor>>,
+    options: GrabOptions,
+}
+
+#[cfg(feature = "unstable_grab")]
+impl Grabber {
+    pub fn builder() -> GrabberBuilder {
+        GrabberBuilder::default()
+    }
+
+    /// The backend that was asked for, `None` means the best available one is
+    /// picked when grabbing.
+    pub fn backend(&self) -> Option<Backend> {
+        self.backend
+    }
+
+    /// Processors keep their state while grabbing, so grabbing consumes the grabber.
+    pub fn grab<T>(self, callback: T) -> Result<(), GrabError>
+    where
+        T: Fn(Event) -> Option<Event> + 'static,
+    {
+        grab_with(self.backend, self.processors, self.options, callback)
+    }
+}
+
+#[cfg(feature = "unstable_grab")]
+impl fmt::Debug for Grabber {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Grabber")
+            .field("backend", &self.backend)
+            .field("processors", &self.processors.len())
+            .field("options", &self.options)
+            .finish()
+    }
+}
@@ -0,0 +1,442 @@
+// Only the evdev grab drives a gamepad
+#![cfg_attr(not(target_os = "linux"), allow(dead_code))]
+use crate::rdev::{Button, EventType, GamepadAxis, GamepadButton, Key};
+use std::collections::{HashMap, HashSet};
+use std::f64::consts::FRAC_1_SQRT_2;
+use std::time::{Duration, Instant};
+
+/// The stick a mouse stops moving for this long goes back to the center.
+const MOUSE_RECENTER: Duration = Duration::from_millis(50);
+/// Mouse movement in one event deflecting a stick fully, by default.
+const MOUSE_COUNTS: f64 = 10.0;
+
+/// A stick of the virtual gamepad, see `GamepadMapping`.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
+pub enum GamepadStick {
+    Left,
+    Right,
+}
+
+impl GamepadStick {
+    fn axes(self) -> (GamepadAxis, GamepadAxis) {
+        match self {
+            GamepadStick::Left => (GamepadAxis::LeftStickX, GamepadAxis::LeftStickY),
+            GamepadStick::Right => (GamepadAxis::RightStickX, GamepadAxis::RightStickY),
+        }
+    }
+}
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
+enum Direction {
+    Up,
+    Left,
+    Down,
+    Right,
+}
+
+/// What a key or mouse button drives.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
+enum Target {
+    Button(GamepadButton),
+    Stick(GamepadStick, Direction),
+    /// All the way, for triggers.
+    Axis(GamepadAxis),
+}
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
+enum Input {
+    Key(Key),
+    Button(Button),
+}
+
+/// How the evdev grab drives a virtual gamepad from the keyboard and the mouse,
+/// see `GrabberBuilder::gamepad`. Keys, mouse buttons and mouse movements that
+/// are mapped only reach the gamepad.
+///
+/// The default mapping is made for shooters: WASD move the left stick and the
+/// mouse the right one, the mouse buttons pull the triggers, Space, R, F and C
+/// are the South, West, North and East buttons, Q and E the bumpers, Shift and V
+/// press the sticks, Tab and Escape are Select and Start and the arrows the d-pad.
+///
+/// ```no_run
+/// use rdev::{Event, GamepadButton, GamepadMapping, GamepadStick, Grabber, Key};
+///
+/// let mapping = GamepadMapping::new()
+///     .stick_keys(GamepadStick::Left, Key::KeyW, Key::KeyA, Key::KeyS, Key::KeyD)
+///     .mouse_stick(GamepadStick::Right, 20.0)
+///     .key(Key::Space, GamepadButton::South);
+/// let grabber = Grabber::builder().gamepad(mapping).build();
+/// // This will block.
+/// if let Err(error) = grabber.grab(|event: Event| Some(event)) {
+///     println!("Error: {:?}", error)
+/// }
+/// ```
+#[derive(Debug, Clone, PartialEq)]
+pub struct GamepadMapping {
+    inputs: HashMap<Input, Target>,
+    analog: HashMap<u16, GamepadAxis>,
+    mouse: Option<(GamepadStick, f64)>,
+}
+
+impl Default for GamepadMapping {
+    fn default() -> GamepadMapping {
+        GamepadMapping::new()
+            .stick_keys(
+                GamepadStick::Left,
+                Key::KeyW,
+                Key::KeyA,
+                Key::KeyS,
+                Key::KeyD,
+            )
+            .mouse_stick(GamepadStick::Right, MOUSE_COUNTS)
+            .axis_button(Button::Right, GamepadAxis::LeftTrigger)
+            .axis_button(Button::Left, GamepadAxis::RightTrigger)
+            .key(Key::Space, GamepadButton::South)
+            .key(Key::KeyC, GamepadButton::East)
+            .key(Key::KeyF, GamepadButton::North)
+            .key(Key::KeyR, GamepadButton::West)
+            .key(Key::KeyQ, GamepadButton::LeftBumper)
+            .key(Key::KeyE, GamepadButton::RightBumper)
+            .key(Key::ShiftLeft, GamepadButton::LeftThumb)
+            .key(Key::KeyV, GamepadButton::RightThumb)
+            .key(Key::Tab, GamepadButton::Select)
+            .key(Key::Escape, GamepadButton::Start)
+            .key(Key::UpArrow, GamepadButton::DpadUp)
+            .key(Key::DownArrow, GamepadButton::DpadDown)
+            .key(Key::LeftArrow, GamepadButton::DpadLeft)
+            .key(Key::RightArrow, GamepadButton::DpadRight)
+    }
+}
+
+impl GamepadMapping {
+    /// A mapping without anything, unlike the default one.
+    pub fn new() -> GamepadMapping {
+        GamepadMapping {
+            inputs: HashMap::new(),
+            analog: HashMap::new(),
+            mouse: None,
+        }
+    }
+
+    pub fn key(mut self, key: Key, button: GamepadButton) -> GamepadMapping {
+        self.inputs.insert(Input::Key(key), Target::Button(button));
+        self
+    }
+
+    pub fn mouse_button(mut self, mouse_button: Button, button: GamepadButton) -> GamepadMapping {
+        self.inputs
+            .insert(Input::Button(mouse_button), Target::Button(button));
+        self
+    }
+
+    /// Pushes the stick all the way while the keys are held, diagonals included.
+    pub fn stick_keys(
+        mut self,
+        stick: GamepadStick,
+        up: Key,
+        left: Key,
+        down: Key,
+        right: Key,
+    ) -> GamepadMapping {
+        for (key, direction) in &[
+            (up, Direction::Up),
+            (left, Direction::Left),
+            (down, Direction::Down),
+            (right, Direction::Right),
+        ] {
+            self.inputs
+                .insert(Input::Key(*key), Target::Stick(stick, *direction));
+        }
+        self
+    }
+
+    /// Moves the stick along with the mouse, `counts` of movement in a single
+    /// mouse event deflecting it fully. It goes back to the center once the mouse
+    /// stops. The default mapping uses 10 counts.
+    pub fn mouse_stick(mut self, stick: GamepadStick, counts: f64) -> GamepadMapping {
+        self.mouse = Some((stick, counts));
+        self
+    }
+
+    /// Pulls a trigger, or pushes a stick axis to its maximum, while the key is held.
+    pub fn axis_key(mut self, key: Key, axis: GamepadAxis) -> GamepadMapping {
+        self.inputs.insert(Input::Key(key), Target::Axis(axis));
+        self
+    }
+
+    pub fn axis_button(mut self, mouse_button: Button, axis: GamepadAxis) -> GamepadMapping {
+        self.inputs
+            .insert(Input::Button(mouse_button), Target::Axis(axis));
+        self
+    }
+
+    /// Drives the axis with the `EventType::Axis` events of this `ABS_*` code, like
+    /// the travel of analog keys, across the whole range of the axis.
+    pub fn analog(mut self, code: u16, axis: GamepadAxis) -> GamepadMapping {
+        self.analog.insert(code, axis);
+        self
+    }
+}
+
+/// A change of the virtual gamepad, axes in the ranges of `GamepadAxis::min`.
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub(crate) enum GamepadChange {
+    Button(GamepadButton, bool),
+    Axis(GamepadAxis, f64),
+}
+
+/// Turns keyboard and mouse events into gamepad changes following a mapping.
+#[derive(Debug)]
+pub(crate) struct GamepadEmulator {
+    mapping: GamepadMapping,
+    pressed: HashSet<Input>,
+    /// From the mouse and analog inputs, keys are added on top.
+    positions: HashMap<GamepadAxis, f64>,
+    /// What the gamepad was last told, to only send changes.
+    buttons: HashSet<GamepadButton>,
+    axes: HashMap<GamepadAxis, f64>,
+    mouse_deadline: Option<Instant>,
+}
+
+impl GamepadEmulator {
+    pub(crate) fn new(mapping: GamepadMapping) -> GamepadEmulator {
+        GamepadEmulator {
+            mapping,
+            pressed: HashSet::new(),
+            positions: HashMap::new(),
+            buttons: HashSet::new(),
+            axes: HashMap::new(),
+            mouse_deadline: None,
+        }
+    }
+
+    /// The changes caused by an event, `None` when the event is not mapped and
+    /// should go on as usual. Mouse movements are `MouseMoveRelative` events.
+    pub(crate) fn handle(
+        &mut self,
+        event_type: &EventType,
+        now: Instant,
+    ) -> Option<Vec<GamepadChange>> {
+        let (input, pressed) = match *event_type {
+            EventType::KeyPress(key) => (Input::Key(key), true),
+            EventType::KeyRelease(key) => (Input::Key(key), false),
+            EventType::ButtonPress(button) => (Input::Button(button), true),
+            EventType::ButtonRelease(button) => (Input::Button(button), false),
+            EventType::MouseMoveRelative { delta_x, delta_y } => {
+                let (stick, counts) = self.mapping.mouse?;
+                let (axis_x, axis_y) = stick.axes();
+                for (axis, delta) in &[(axis_x, delta_x), (axis_y, delta_y)] {
+                    if *delta != 0.0 {
+                        self.positions
+                            .insert(*axis, (delta / counts).clamp(-1.0, 1.0));
+                    }
+                }
+                self.mouse_deadline = Some(now + MOUSE_RECENTER);
+                return Some(self.changes());
+            }
+            EventType::Axis {
+                code,
+                value,
+                min,
+                max,
+            } => {
+                let axis = *self.mapping.analog.get(&code)?;
+                let travel = if min < max {
+                    f64::from(value - min) / f64::from(max - min)
+                } else {
+                    0.0
+                };
+                let position = axis.min() + travel.clamp(0.0, 1.0) * (1.0 - axis.min());
+                self.positions.insert(axis, position);
+                return Some(self.changes());
+            }
+            _ => return None,
+        };
+        self.mapping.inputs.get(&input)?;
+        if pressed {
+            self.pressed.insert(input);
+        } else {
+            self.pressed.remove(&input);
+        }
+        Some(self.changes())
+    }
+
+    pub(crate) fn deadline(&self) -> Option<Instant> {
+        self.mouse_deadline
+    }
+
+    /// Recenters the stick the mouse moved.
+    pub(crate) fn timeout(&mut self, _now: Instant) -> Vec<GamepadChange> {
+        self.mouse_deadline = None;
+        if let Some((stick, _)) = self.mapping.mouse {
+            let (axis_x, axis_y) = stick.axes();
+            self.positions.remove(&axis_x);
+            self.positions.remove(&axis_y);
+        }
+        self.changes()
+    }
+
+    fn targets(&self) -> impl Iterator<Item = Target> + '_ {
+        self.pressed
+            .iter()
+            .filter_map(move |input| self.mapping.inputs.get(input).copied())
+    }
+
+    /// Where the keys push the stick, a unit vector or the center.
+    fn stick_keys(&self, stick: GamepadStick) -> (f64, f64) {
+        let (mut x, mut y) = (0.0, 0.0);
+        for target in self.targets() {
+            match target {
+                Target::Stick(pushed, Direction::Up) if pushed == stick => y -= 1.0,
+                Target::Stick(pushed, Direction::Left) if pushed == stick => x -= 1.0,
+                Target::Stick(pushed, Direction::Down) if pushed == stick => y += 1.0,
+                Target::Stick(pushed, Direction::Right) if pushed == stick => x += 1.0,
+                _ => {}
+            }
+        }
+        if x != 0.0 && y != 0.0 {
+            (x * FRAC_1_SQRT_2, y * FRAC_1_SQRT_2)
+        } else {
+            (x, y)
+        }
+    }
+
+    /// Works out the whole gamepad and returns what differs from last time.
+    fn changes(&mut self) -> Vec<GamepadChange> {
+        let buttons: HashSet<GamepadButton> = self
+            .targets()
+            .filter_map(|target| match target {
+                Target::Button(button) => Some(button),
+                _ => None,
+            })
+            .collect();
+        let mut axes = self.positions.clone();
+        for stick in &[GamepadStick::Left, GamepadStick::Right] {
+            let (axis_x, axis_y) = stick.axes();
+            let (x, y) = self.stick_keys(*stick);
+            for (axis, pushed) in &[(axis_x, x), (axis_y, y)] {
+                let position = axes.entry(*axis).or_insert(0.0);
+                *position = (*position + pushed).clamp(-1.0, 1.0);
+            }
+        }
+        let held: Vec<GamepadAxis> = self
+            .targets()
+            .filter_map(|target| match target {
+                Target::Axis(axis) => Some(axis),
+                _ => None,
+            })
+            .collect();
+        for axis in held {
+            axes.insert(axis, 1.0);
+        }
+
+        let mut changes = vec![];
+        for button in self.buttons.difference(&buttons) {
+            changes.push(GamepadChange::Button(*button, false));
+        }
+        for button in buttons.difference(&self.buttons) {
+            changes.push(GamepadChange::Button(*button, true));
+        }
+        // Axes no input holds anymore go back to rest, at 0.0 for sticks and triggers
+        for axis in self.axes.keys() {
+            axes.entry(*axis).or_insert(0.0);
+        }
+        for (axis, position) in &axes {
+            if self.axes.get(axis).copied().unwrap_or(0.0) != *position {
+                changes.push(GamepadChange::Axis(*axis, *position));
+            }
+        }
+        self.buttons = buttons;
+        self.axes = axes;
+        changes
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use EventType::{KeyPress as Press, KeyRelease as Release};
+
+    fn axis(changes: &[GamepadChange], axis: GamepadAxis) -> Option<f64> {
+        changes.iter().find_map(|change| match change {
+            GamepadChange::Axis(changed, position) if *changed == axis => Some(*position),
+            _ => None,
+        })
+    }
+
+    #[test]
+    fn test_buttons() {
+        let mut emulator = GamepadEmulator::new(GamepadMapping::default());
+        let now = Instant::now();
+        assert_eq!(
+            emulator.handle(&Press(Key::Space), now),
+            Some(vec![GamepadChange::Button(GamepadButton::South, true)])
+        );
+        // Key repeat
+        assert_eq!(emulator.handle(&Press(Key::Space), now), Some(vec![]));
+        assert_eq!(
+            emulator.handle(&Release(Key::Space), now),
+            Some(vec![GamepadChange::Button(GamepadButton::South, false)])
+        );
+        assert_eq!(emulator.handle(&Press(Key::KeyZ), now), None);
+    }
+
+    #[test]
+    fn test_stick_keys() {
+        let mut emulator = GamepadEmulator::new(GamepadMapping::default());
+        let now = Instant::now();
+        let changes = emulator.handle(&Press(Key::KeyW), now).unwrap();
+        assert_eq!(axis(&changes, GamepadAxis::LeftStickY), Some(-1.0));
+        assert_eq!(axis(&changes, GamepadAxis::LeftStickX), None);
+        let changes = emulator.handle(&Press(Key::KeyD), now).unwrap();
+        assert_eq!(axis(&changes, GamepadAxis::LeftStickX), Some(FRAC_1_SQRT_2));
+        assert_eq!(
+            axis(&changes, GamepadAxis::LeftStickY),
+            Some(-FRAC_1_SQRT_2)
+        );
+        emulator.handle(&Release(Key::KeyW), now);
+        let changes = emulator.handle(&Release(Key::KeyD), now).unwrap();
+        assert_eq!(axis(&changes, GamepadAxis::LeftStickX), Some(0.0));
+    }
+
+    #[test]
+    fn test_mouse_recenters() {
+        let mut emulator = GamepadEmulator::new(GamepadMapping::default());
+        let now = Instant::now();
+        let moved = EventType::MouseMoveRelative {
+            delta_x: 5.0,
+            delta_y: -20.0,
+        };
+        let changes = emulator.handle(&moved, now).unwrap();
+        assert_eq!(axis(&changes, GamepadAxis::RightStickX), Some(0.5));
+        assert_eq!(axis(&changes, GamepadAxis::RightStickY), Some(-1.0));
+        assert_eq!(emulator.deadline(), Some(now + MOUSE_RECENTER));
+        let changes = emulator.timeout(now + MOUSE_RECENTER);
+        assert_eq!(axis(&changes, GamepadAxis::RightStickX), Some(0.0));
+        assert_eq!(axis(&changes, GamepadAxis::RightStickY), Some(0.0));
+        assert_eq!(emulator.deadline(), None);
+    }
+
+    #[test]
+    fn test_triggers() {
+        let mapping = GamepadMapping::default().analog(0x2d, GamepadAxis::LeftTrigger);
+        let mut emulator = GamepadEmulator::new(mapping);
+        let now = Instant::now();
+        let changes = emulator
+            .handle(&EventType::ButtonPress(Button::Left), now)
+            .unwrap();
+        assert_eq!(axis(&changes, GamepadAxis::RightTrigger), Some(1.0));
+        let changes = emulator
+            .handle(&EventType::ButtonRelease(Button::Left), now)
+            .unwrap();
+        assert_eq!(axis(&changes, GamepadAxis::RightTrigger), Some(0.0));
+        let travel = EventType::Axis {
+            code: 0x2d,
+            value: 64,
+            min: 0,
+            max: 256,
+        };
+        let changes = emulator.handle(&travel, now).unwrap();
+        assert_eq!(axis(&changes, GamepadAxis::LeftTrigger), Some(0.25));
+    }
+}
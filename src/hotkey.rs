@@ -0,0 +1,397 @@
+#[cfg(feature = "serialize")]
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeSet, HashMap, HashSet};
+use std::sync::{Arc, Mutex};
+
+#[cfg(feature = "unstable_grab")]
+use crate::rdev::GrabError;
+use crate::rdev::{Event, EventType, Key, ListenError};
+
+/// A key pressed while some other keys (usually modifiers) are held down,
+/// like `Ctrl+Shift+K`. Modifiers are stored with their side, the default
+/// `Hotkey` matching just ignores it.
+///
+/// ```
+/// use rdev::{Key, KeyCombo};
+///
+/// let combo = KeyCombo::new(Key::KeyK).ctrl().shift();
+/// let strict = KeyCombo::new(Key::KeyK).with(Key::ControlRight);
+/// assert_ne!(combo, strict);
+/// ```
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serialize", derive(Serialize, Deserialize))]
+pub struct KeyCombo {
+    pub modifiers: BTreeSet<Key>,
+    pub key: Key,
+}
+
+impl KeyCombo {
+    pub fn new(key: Key) -> KeyCombo {
+        KeyCombo {
+            modifiers: BTreeSet::new(),
+            key,
+        }
+    }
+
+    /// Adds a key that must be held, any key can be used, not only modifiers.
+    pub fn with(mut self, key: Key) -> KeyCombo {
+        self.modifiers.insert(key);
+        self
+    }
+
+    pub fn ctrl(self) -> KeyCombo {
+        self.with(Key::ControlLeft)
+    }
+
+    pub fn shift(self) -> KeyCombo {
+        self.with(Key::ShiftLeft)
+    }
+
+    pub fn alt(self) -> KeyCombo {
+        self.with(Key::Alt)
+    }
+
+    pub fn meta(self) -> KeyCombo {
+        self.with(Key::MetaLeft)
+    }
+
+    /// The combo with every modifier on its left side.
+    pub(crate) fn sideless(&self) -> KeyCombo {
+        KeyCombo {
+            modifiers: self.modifiers.iter().map(|key| left_side(*key)).collect(),
+            key: left_side(self.key),
+        }
+    }
+}
+
+impl From<Key> for KeyCombo {
+    fn from(key: Key) -> KeyCombo {
+        KeyCombo::new(key)
+    }
+}
+
+/// Ctrl, Shift, Alt (including AltGr) and Meta, on either side.
+pub(crate) fn is_modifier(key: Key) -> bool {
+    matches!(
+        left_side(key),
+        Key::ControlLeft | Key::ShiftLeft | Key::Alt | Key::MetaLeft
+    )
+}
+
+pub(crate) fn left_side(key: Key) -> Key {
+    match key {
+        Key::ControlRight => Key::ControlLeft,
+        Key::ShiftRight => Key::ShiftLeft,
+        Key::AltGr => Key::Alt,
+        Key::MetaRight => Key::MetaLeft,
+        key => key,
+    }
+}
+
+/// A `KeyCombo` along with how `HotkeyManager` should treat it.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct Hotkey {
+    combo: KeyCombo,
+    strict: bool,
+    consume: bool,
+}
+
+impl Hotkey {
+    /// Matches no matter which side the modifiers are pressed on, see `strict`.
+    pub fn new(combo: KeyCombo) -> Hotkey {
+        Hotkey {
+            combo,
+            strict: false,
+            consume: false,
+        }
+    }
+
+    /// Only matches when the modifiers are pressed on the side given in the combo,
+    /// `Ctrl+K` is then different from `RightCtrl+K`.
+    pub fn strict(mut self) -> Hotkey {
+        self.strict = true;
+        self
+    }
+
+    /// When grabbing, the key press and release of the hotkey don't reach
+    /// the focused application. The modifiers do, as they are pressed before
+    /// we know a hotkey is coming.
+    pub fn consume(mut self) -> Hotkey {
+        self.consume = true;
+        self
+    }
+
+    pub fn combo(&self) -> &KeyCombo {
+        &self.combo
+    }
+
+    fn matches(&self, key: Key, held: &BTreeSet<Key>) -> bool {
+        if self.strict {
+            self.combo.key == key && &self.combo.modifiers == held
+        } else {
+            let held = held.iter().map(|key| left_side(*key)).collect();
+            let combo = self.combo.sideless();
+            combo.key == left_side(key) && combo.modifiers == held
+        }
+    }
+}
+
+impl From<KeyCombo> for Hotkey {
+    fn from(combo: KeyCombo) -> Hotkey {
+        Hotkey::new(combo)
+    }
+}
+
+/// Returned by `HotkeyManager::register`, to unregister the hotkey later on.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
+pub struct HotkeyId(u64);
+
+type HotkeyCallback = Arc<Mutex<dyn FnMut() + Send>>;
+
+#[derive(Default)]
+struct State {
+    next_id: u64,
+    hotkeys: HashMap<HotkeyId, (Hotkey, HotkeyCallback)>,
+    pressed: HashSet<Key>,
+    /// Keys whose press was consumed, so their repeats and release are too.
+    consumed: HashSet<Key>,
+}
+
+/// Runs callbacks when key combinations are pressed. The manager is cheap to
+/// clone and every clone shares the same hotkeys, so they can be registered
+/// and unregistered from any thread (or from a hotkey callback) while
+/// `listen` or `grab` is running.
+///
+/// ```no_run
+/// use rdev::{Hotkey, HotkeyManager, Key, KeyCombo};
+///
+/// let manager = HotkeyManager::new();
+/// manager.register(KeyCombo::new(Key::KeyK).ctrl().shift(), || println!("Ctrl+Shift+K"));
+/// let id = manager.register(
+///     Hotkey::new(KeyCombo::new(Key::KeyQ).with(Key::ControlRight)).strict(),
+///     || println!("Right Ctrl+Q"),
+/// );
+/// manager.unregister(id);
+/// // This will block.
+/// if let Err(error) = manager.listen() {
+///     println!("Error: {:?}", error)
+/// }
+/// ```
+#[derive(Clone, Default)]
+pub struct HotkeyManager {
+    state: Arc<Mutex<State>>,
+}
+
+impl HotkeyManager {
+    pub fn new() -> HotkeyManager {
+        HotkeyManager::default()
+    }
+
+    pub fn register<H, F>(&self, hotkey: H, callback: F) -> HotkeyId
+    where
+        H: Into<Hotkey>,
+        F: FnMut() + Send + 'static,
+    {
+        let mut state = self.lock();
+        let id = HotkeyId(state.next_id);
+        state.next_id += 1;
+        state
+            .hotkeys
+            .insert(id, (hotkey.into(), Arc::new(Mutex::new(callback))));
+        id
+    }
+
+    /// Returns whether the hotkey was still registered.
+    pub fn unregister(&self, id: HotkeyId) -> bool {
+        self.lock().hotkeys.remove(&id).is_some()
+    }
+
+    pub fn hotkeys(&self) -> Vec<(HotkeyId, Hotkey)> {
+        self.lock()
+            .hotkeys
+            .iter()
+            .map(|(id, (hotkey, _))| (*id, hotkey.clone()))
+            .collect()
+    }
+
+    /// Feeds an event to the manager, running the callbacks of the hotkeys it
+    /// completes. Returns whether the event should be swallowed, which only
+    /// matters when grabbing. Use this to drive the manager from your own
+    /// `listen` or `grab` callback, `HotkeyManager::listen` and
+    /// `HotkeyManager::grab` do it for you.
+    pub fn process(&self, event: &Event) -> bool {
+        let (callbacks, consume) = match event.event_type {
+            EventType::KeyPress(key) => {
+                let mut state = self.lock();
+                if !state.pressed.insert(key) {
+                    // Key repeat
+                    return state.consumed.contains(&key);
+                }
+                let held = state
+                    .pressed
+                    .iter()
+                    .filter(|pressed| **pressed != key)
+                    .copied()
+                    .collect();
+                let matching: Vec<&(Hotkey, HotkeyCallback)> = state
+                    .hotkeys
+                    .values()
+                    .filter(|(hotkey, _)| hotkey.matches(key, &held))
+                    .collect();
+                let consume = matching.iter().any(|(hotkey, _)| hotkey.consume);
+                let callbacks: Vec<HotkeyCallback> = matching
+                    .into_iter()
+                    .map(|(_, callback)| callback.clone())
+                    .collect();
+                if consume {
+                    state.consumed.insert(key);
+                }
+                (callbacks, consume)
+            }
+            EventType::KeyRelease(key) => {
+                let mut state = self.lock();
+                state.pressed.remove(&key);
+                return state.consumed.remove(&key);
+            }
+            _ => return false,
+        };
+        // The lock is released so callbacks can register and unregister hotkeys.
+        for callback in callbacks {
+            if let Ok(mut callback) = callback.lock() {
+                callback();
+            }
+        }
+        consume
+    }
+
+    /// Listens to global events and runs the hotkeys, consuming is not possible here.
+    pub fn listen(self) -> Result<(), ListenError> {
+        crate::listen(move |event| {
+            self.process(&event);
+        })
+    }
+
+    /// Grabs global events and runs the hotkeys, swallowing the ones that
+    /// should be consumed.
+    #[cfg(feature = "unstable_grab")]
+    pub fn grab(self) -> Result<(), GrabError> {
+        crate::grab(move |event| {
+            if self.process(&event) {
+                None
+            } else {
+                Some(event)
+            }
+        })
+    }
+
+    fn lock(&self) -> std::sync::MutexGuard<'_, State> {
+        // A panicking callback never holds this lock, the state stays consistent.
+        self.state
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::time::SystemTime;
+
+    fn event(event_type: EventType) -> Event {
+        Event {
+            time: SystemTime::now(),
+            name: None,
+            event_type,
+            device_id: None,
+        }
+    }
+
+    fn press(manager: &HotkeyManager, key: Key) -> bool {
+        manager.process(&event(EventType::KeyPress(key)))
+    }
+
+    fn release(manager: &HotkeyManager, key: Key) -> bool {
+        manager.process(&event(EventType::KeyRelease(key)))
+    }
+
+    fn counter(manager: &HotkeyManager, hotkey: impl Into<Hotkey>) -> (HotkeyId, Arc<AtomicUsize>) {
+        let count = Arc::new(AtomicUsize::new(0));
+        let inner = count.clone();
+        let id = manager.register(hotkey, move || {
+            inner.fetch_add(1, Ordering::SeqCst);
+        });
+        (id, count)
+    }
+
+    #[test]
+    fn test_agnostic_and_strict_matching() {
+        let manager = HotkeyManager::new();
+        let (_, agnostic) = counter(&manager, KeyCombo::new(Key::KeyK).ctrl().shift());
+        let (_, strict) = counter(
+            &manager,
+            Hotkey::new(KeyCombo::new(Key::KeyK).ctrl().shift()).strict(),
+        );
+
+        press(&manager, Key::ControlRight);
+        press(&manager, Key::ShiftLeft);
+        press(&manager, Key::KeyK);
+        release(&manager, Key::KeyK);
+        assert_eq!(agnostic.load(Ordering::SeqCst), 1);
+        assert_eq!(strict.load(Ordering::SeqCst), 0);
+        release(&manager, Key::ControlRight);
+
+        press(&manager, Key::ControlLeft);
+        press(&manager, Key::KeyK);
+        assert_eq!(agnostic.load(Ordering::SeqCst), 2);
+        assert_eq!(strict.load(Ordering::SeqCst), 1);
+    }
+
+    #[test]
+    fn test_extra_keys_prevent_match() {
+        let manager = HotkeyManager::new();
+        let (_, count) = counter(&manager, KeyCombo::new(Key::KeyK).ctrl());
+        press(&manager, Key::ControlLeft);
+        press(&manager, Key::Alt);
+        press(&manager, Key::KeyK);
+        assert_eq!(count.load(Ordering::SeqCst), 0);
+    }
+
+    #[test]
+    fn test_consume_press_repeat_and_release() {
+        let manager = HotkeyManager::new();
+        counter(
+            &manager,
+            Hotkey::new(KeyCombo::new(Key::KeyK).ctrl()).consume(),
+        );
+        assert!(!press(&manager, Key::ControlLeft));
+        assert!(press(&manager, Key::KeyK));
+        assert!(press(&manager, Key::KeyK));
+        assert!(release(&manager, Key::KeyK));
+        assert!(!release(&manager, Key::ControlLeft));
+        assert!(!press(&manager, Key::KeyK));
+    }
+
+    #[test]
+    fn test_unregister_at_runtime() {
+        let manager = HotkeyManager::new();
+        let (id, count) = counter(&manager, KeyCombo::new(Key::F5));
+        press(&manager, Key::F5);
+        release(&manager, Key::F5);
+        assert!(manager.unregister(id));
+        assert!(!manager.unregister(id));
+        press(&manager, Key::F5);
+        assert_eq!(count.load(Ordering::SeqCst), 1);
+    }
+
+    #[test]
+    fn test_register_from_callback() {
+        let manager = HotkeyManager::new();
+        let inner = manager.clone();
+        manager.register(KeyCombo::new(Key::F1), move || {
+            inner.register(KeyCombo::new(Key::F2), || {});
+        });
+        press(&manager, Key::F1);
+        assert_eq!(manager.hotkeys().len(), 2);
+    }
+}
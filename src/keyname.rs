@@ -0,0 +1,437 @@
+use std::fmt;
+use std::str::FromStr;
+
+use crate::hotkey::{left_side, KeyCombo};
+use crate::rdev::{Button, Key, ParseKeyError};
+
+/// Every named key, with the name it displays as and the other names it is
+/// parsed from. The `Debug` name of the variant is always accepted too, and
+/// names are case insensitive.
+const KEY_NAMES: &[(Key, &str, &[&str])] = &[
+    (
+        Key::ControlLeft,
+        "Ctrl",
+        &["Control", "LCtrl", "LeftCtrl", "LControl"],
+    ),
+    (
+        Key::ControlRight,
+        "RightCtrl",
+        &["RCtrl", "RControl", "RightControl"],
+    ),
+    (Key::ShiftLeft, "Shift", &["LShift", "LeftShift"]),
+    (Key::ShiftRight, "RightShift", &["RShift"]),
+    (Key::Alt, "Alt", &["LAlt", "LeftAlt", "Option", "Opt"]),
+    (Key::AltGr, "AltGr", &["RAlt", "RightAlt", "ROption"]),
+    (
+        Key::MetaLeft,
+        "Meta",
+        &[
+            "LMeta", "LeftMeta", "Cmd", "Command", "Super", "Win", "Windows",
+        ],
+    ),
+    (
+        Key::MetaRight,
+        "RightMeta",
+        &[
+            "RMeta",
+            "RCmd",
+            "RightCmd",
+            "RSuper",
+            "RightSuper",
+            "RWin",
+            "RightWin",
+        ],
+    ),
+    (Key::Function, "Fn", &[]),
+    (Key::KeyA, "A", &[]),
+    (Key::KeyB, "B", &[]),
+    (Key::KeyC, "C", &[]),
+    (Key::KeyD, "D", &[]),
+    (Key::KeyE, "E", &[]),
+    (Key::KeyF, "F", &[]),
+    (Key::KeyG, "G", &[]),
+    (Key::KeyH, "H", &[]),
+    (Key::KeyI, "I", &[]),
+    (Key::KeyJ, "J", &[]),
+    (Key::KeyK, "K", &[]),
+    (Key::KeyL, "L", &[]),
+    (Key::KeyM, "M", &[]),
+    (Key::KeyN, "N", &[]),
+    (Key::KeyO, "O", &[]),
+    (Key::KeyP, "P", &[]),
+    (Key::KeyQ, "Q", &[]),
+    (Key::KeyR, "R", &[]),
+    (Key::KeyS, "S", &[]),
+    (Key::KeyT, "T", &[]),
+    (Key::KeyU, "U", &[]),
+    (Key::KeyV, "V", &[]),
+    (Key::KeyW, "W", &[]),
+    (Key::KeyX, "X", &[]),
+    (Key::KeyY, "Y", &[]),
+    (Key::KeyZ, "Z", &[]),
+    (Key::Num1, "1", &[]),
+    (Key::Num2, "2", &[]),
+    (Key::Num3, "3", &[]),
+    (Key::Num4, "4", &[]),
+    (Key::Num5, "5", &[]),
+    (Key::Num6, "6", &[]),
+    (Key::Num7, "7", &[]),
+    (Key::Num8, "8", &[]),
+    (Key::Num9, "9", &[]),
+    (Key::Num0, "0", &[]),
+    (Key::F1, "F1", &[]),
+    (Key::F2, "F2", &[]),
+    (Key::F3, "F3", &[]),
+    (Key::F4, "F4", &[]),
+    (Key::F5, "F5", &[]),
+    (Key::F6, "F6", &[]),
+    (Key::F7, "F7", &[]),
+    (Key::F8, "F8", &[]),
+    (Key::F9, "F9", &[]),
+    (Key::F10, "F10", &[]),
+    (Key::F11, "F11", &[]),
+    (Key::F12, "F12", &[]),
+    (Key::F13, "F13", &[]),
+    (Key::F14, "F14", &[]),
+    (Key::F15, "F15", &[]),
+    (Key::F16, "F16", &[]),
+    (Key::F17, "F17", &[]),
+    (Key::F18, "F18", &[]),
+    (Key::F19, "F19", &[]),
+    (Key::F20, "F20", &[]),
+    (Key::F21, "F21", &[]),
+    (Key::F22, "F22", &[]),
+    (Key::F23, "F23", &[]),
+    (Key::F24, "F24", &[]),
+    (Key::Escape, "Escape", &["Esc"]),
+    (Key::Tab, "Tab", &[]),
+    (Key::CapsLock, "CapsLock", &["Caps"]),
+    (Key::Space, "Space", &[]),
+    (Key::Return, "Enter", &["Return"]),
+    (Key::Backspace, "Backspace", &[]),
+    (Key::Delete, "Delete", &["Del"]),
+    (Key::Insert, "Insert", &["Ins"]),
+    (Key::Home, "Home", &[]),
+    (Key::End, "End", &[]),
+    (Key::PageUp, "PageUp", &["PgUp"]),
+    (Key::PageDown, "PageDown", &["PgDn", "PgDown"]),
+    (Key::UpArrow, "Up", &[]),
+    (Key::DownArrow, "Down", &[]),
+    (Key::LeftArrow, "Left", &[]),
+    (Key::RightArrow, "Right", &[]),
+    (Key::PrintScreen, "PrintScreen", &["PrtSc", "Print"]),
+    (Key::ScrollLock, "ScrollLock", &[]),
+    (Key::Pause, "Pause", &["Break"]),
+    (Key::NumLock, "NumLock", &[]),
+    (Key::BackQuote, "Backquote", &["`", "Grave"]),
+    (Key::Minus, "Minus", &["-"]),
+    (Key::Equal, "Equal", &["="]),
+    (Key::LeftBracket, "LeftBracket", &["["]),
+    (Key::RightBracket, "RightBracket", &["]"]),
+    (Key::SemiColon, "Semicolon", &[";"]),
+    (Key::Quote, "Quote", &["'"]),
+    (Key::BackSlash, "Backslash", &["\\"]),
+    (Key::IntlBackslash, "IntlBackslash", &[]),
+    (Key::Comma, "Comma", &[","]),
+    (Key::Dot, "Period", &[".", "Dot"]),
+    (Key::Slash, "Slash", &["/"]),
+    (Key::KpReturn, "KpEnter", &["KpReturn", "NumpadEnter"]),
+    (Key::KpMinus, "KpMinus", &["NumpadMinus"]),
+    (Key::KpPlus, "KpPlus", &["NumpadPlus"]),
+    (Key::KpMultiply, "KpMultiply", &["NumpadMultiply"]),
+    (Key::KpDivide, "KpDivide", &["NumpadDivide"]),
+    (Key::Kp0, "Kp0", &["Numpad0"]),
+    (Key::Kp1, "Kp1", &["Numpad1"]),
+    (Key::Kp2, "Kp2", &["Numpad2"]),
+    (Key::Kp3, "Kp3", &["Numpad3"]),
+    (Key::Kp4, "Kp4", &["Numpad4"]),
+    (Key::Kp5, "Kp5", &["Numpad5"]),
+    (Key::Kp6, "Kp6", &["Numpad6"]),
+    (Key::Kp7, "Kp7", &["Numpad7"]),
+    (Key::Kp8, "Kp8", &["Numpad8"]),
+    (Key::Kp9, "Kp9", &["Numpad9"]),
+    (Key::KpDelete, "KpDelete", &["NumpadDelete"]),
+    (Key::VolumeMute, "VolumeMute", &["Mute"]),
+    (Key::VolumeDown, "VolumeDown", &[]),
+    (Key::VolumeUp, "VolumeUp", &[]),
+];
+
+const BUTTON_NAMES: &[(Button, &str, &[&str])] = &[
+    (Button::Left, "MouseLeft", &["LeftClick", "LMB"]),
+    (Button::Right, "MouseRight", &["RightClick", "RMB"]),
+    (Button::Middle, "MouseMiddle", &["MiddleClick", "MMB"]),
+    (Button::Forward, "MouseForward", &["MouseFwd"]),
+    (Button::Backward, "MouseBack", &["MouseBackward"]),
+];
+
+fn parse_unknown(s: &str, prefix: &str) -> Option<String> {
+    let rest = s.get(..prefix.len())?;
+    if !rest.eq_ignore_ascii_case(prefix) {
+        return None;
+    }
+    Some(s[prefix.len()..].to_string())
+}
+
+impl fmt::Display for Key {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match KEY_NAMES.iter().find(|(key, _, _)| key == self) {
+            Some((_, name, _)) => write!(f, "{}", name),
+            None => write!(f, "{:?}", self),
+        }
+    }
+}
+
+/// Parses the names `Key` displays as, plus common aliases (`Cmd`, `Super`
+/// and `Win` for `Meta`, `Return` for `Enter`...) and the variant names.
+/// `Unknown(42)` parses to `Key::Unknown(42)`.
+///
+/// ```
+/// use rdev::Key;
+///
+/// assert_eq!("Cmd".parse::<Key>().unwrap(), Key::MetaLeft);
+/// assert_eq!("return".parse::<Key>().unwrap(), Key::Return);
+/// assert_eq!(Key::Return.to_string(), "Enter");
+/// ```
+impl FromStr for Key {
+    type Err = ParseKeyError;
+
+    fn from_str(s: &str) -> Result<Key, ParseKeyError> {
+        let s = s.trim();
+        for (key, name, aliases) in KEY_NAMES {
+            if name.eq_ignore_ascii_case(s)
+                || aliases.iter().any(|alias| alias.eq_ignore_ascii_case(s))
+                || format!("{:?}", key).eq_ignore_ascii_case(s)
+            {
+                return Ok(*key);
+            }
+        }
+        parse_unknown(s, "Unknown(")
+            .and_then(|rest| rest.strip_suffix(')')?.parse().ok())
+            .map(Key::Unknown)
+            .ok_or_else(|| ParseKeyError::new(s))
+    }
+}
+
+impl fmt::Display for Button {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match BUTTON_NAMES.iter().find(|(button, _, _)| button == self) {
+            Some((_, name, _)) => write!(f, "{}", name),
+            None => match self {
+                Button::Unknown(code) => write!(f, "Mouse{}", code),
+                button => write!(f, "{:?}", button),
+            },
+        }
+    }
+}
+
+/// Parses `MouseLeft`, `MouseRight`, `MouseMiddle`, `MouseForward` and
+/// `MouseBack` with a few aliases, and `Mouse8` for `Button::Unknown(8)`.
+impl FromStr for Button {
+    type Err = ParseKeyError;
+
+    fn from_str(s: &str) -> Result<Button, ParseKeyError> {
+        let s = s.trim();
+        for (button, name, aliases) in BUTTON_NAMES {
+            if name.eq_ignore_ascii_case(s)
+                || aliases.iter().any(|alias| alias.eq_ignore_ascii_case(s))
+            {
+                return Ok(*button);
+            }
+        }
+        parse_unknown(s, "Mouse")
+            .and_then(|rest| rest.parse().ok())
+            .map(Button::Unknown)
+            .ok_or_else(|| ParseKeyError::new(s))
+    }
+}
+
+/// How `KeyCombo::format` writes a combo.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
+#[non_exhaustive]
+pub enum ComboStyle {
+    /// `Ctrl+Shift+K`, what `Display` writes and `FromStr` reads back.
+    Plain,
+    /// `Ctrl+Shift+Win+K`
+    Windows,
+    /// `Ctrl+Shift+Super+K`
+    Linux,
+    /// `⌃⇧⌘K`, ignoring the side of modifiers.
+    MacOs,
+}
+
+impl ComboStyle {
+    /// The style of the platform we are compiled for.
+    pub fn native() -> ComboStyle {
+        if cfg!(target_os = "macos") {
+            ComboStyle::MacOs
+        } else if cfg!(target_os = "windows") {
+            ComboStyle::Windows
+        } else {
+            ComboStyle::Linux
+        }
+    }
+}
+
+/// Modifiers are written in this order whatever order they were given in,
+/// other held keys come last.
+fn modifier_rank(key: Key) -> u8 {
+    match left_side(key) {
+        Key::ControlLeft => 0,
+        Key::Alt => 1,
+        Key::ShiftLeft => 2,
+        Key::MetaLeft => 3,
+        _ => 4,
+    }
+}
+
+fn mac_symbol(key: Key) -> Option<&'static str> {
+    let symbol = match left_side(key) {
+        Key::ControlLeft => "⌃",
+        Key::Alt => "⌥",
+        Key::ShiftLeft => "⇧",
+        Key::MetaLeft => "⌘",
+        Key::Return | Key::KpReturn => "↩",
+        Key::Backspace => "⌫",
+        Key::Delete => "⌦",
+        Key::Escape => "⎋",
+        Key::Tab => "⇥",
+        Key::CapsLock => "⇪",
+        Key::UpArrow => "↑",
+        Key::DownArrow => "↓",
+        Key::LeftArrow => "←",
+        Key::RightArrow => "→",
+        Key::PageUp => "⇞",
+        Key::PageDown => "⇟",
+        Key::Home => "↖",
+        Key::End => "↘",
+        _ => return None,
+    };
+    Some(symbol)
+}
+
+fn styled_name(key: Key, style: ComboStyle) -> String {
+    match (style, key) {
+        (ComboStyle::Windows, Key::MetaLeft) => "Win".to_string(),
+        (ComboStyle::Windows, Key::MetaRight) => "RightWin".to_string(),
+        (ComboStyle::Linux, Key::MetaLeft) => "Super".to_string(),
+        (ComboStyle::Linux, Key::MetaRight) => "RightSuper".to_string(),
+        (ComboStyle::MacOs, key) => mac_symbol(key)
+            .map(str::to_string)
+            .unwrap_or_else(|| key.to_string()),
+        (_, key) => key.to_string(),
+    }
+}
+
+impl KeyCombo {
+    /// Writes the combo the way the platform usually shows shortcuts.
+    ///
+    /// ```
+    /// use rdev::{ComboStyle, KeyCombo};
+    ///
+    /// let combo: KeyCombo = "Shift+Cmd+K".parse().unwrap();
+    /// assert_eq!(combo.format(ComboStyle::MacOs), "⇧⌘K");
+    /// assert_eq!(combo.format(ComboStyle::Windows), "Shift+Win+K");
+    /// assert_eq!(combo.to_string(), "Shift+Meta+K");
+    /// ```
+    pub fn format(&self, style: ComboStyle) -> String {
+        let mut modifiers: Vec<Key> = self.modifiers.iter().copied().collect();
+        modifiers.sort_by_key(|key| modifier_rank(*key));
+        let names = modifiers
+            .into_iter()
+            .chain(std::iter::once(self.key))
+            .map(|key| styled_name(key, style));
+        match style {
+            ComboStyle::MacOs => names.collect(),
+            _ => names.collect::<Vec<String>>().join("+"),
+        }
+    }
+}
+
+impl fmt::Display for KeyCombo {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.format(ComboStyle::Plain))
+    }
+}
+
+/// Parses `+` separated key names, the last one being the key to press and the
+/// others the keys to hold, see `Key::from_str` for the names.
+///
+/// ```
+/// use rdev::{Key, KeyCombo};
+///
+/// let combo: KeyCombo = "ctrl + alt + delete".parse().unwrap();
+/// assert_eq!(combo, KeyCombo::new(Key::Delete).ctrl().alt());
+/// ```
+impl FromStr for KeyCombo {
+    type Err = ParseKeyError;
+
+    fn from_str(s: &str) -> Result<KeyCombo, ParseKeyError> {
+        let mut keys = s
+            .split('+')
+            .map(str::parse)
+            .collect::<Result<Vec<Key>, ParseKeyError>>()?;
+        let key = keys.pop().ok_or_else(|| ParseKeyError::new(s))?;
+        Ok(keys
+            .into_iter()
+            .fold(KeyCombo::new(key), |combo, modifier| combo.with(modifier)))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_key_names_round_trip() {
+        for (key, name, aliases) in KEY_NAMES {
+            assert_eq!(key.to_string(), *name);
+            assert_eq!(name.parse::<Key>().unwrap(), *key);
+            assert_eq!(format!("{:?}", key).parse::<Key>().unwrap(), *key);
+            for alias in *aliases {
+                assert_eq!(alias.parse::<Key>().unwrap(), *key, "{}", alias);
+            }
+        }
+        assert_eq!(
+            Key::Unknown(42).to_string().parse::<Key>().unwrap(),
+            Key::Unknown(42)
+        );
+        assert!("Hyper".parse::<Key>().is_err());
+    }
+
+    #[test]
+    fn test_button_names_round_trip() {
+        for button in &[
+            Button::Left,
+            Button::Right,
+            Button::Middle,
+            Button::Forward,
+            Button::Backward,
+            Button::Unknown(8),
+        ] {
+            assert_eq!(button.to_string().parse::<Button>().unwrap(), *button);
+        }
+        assert_eq!("MouseBack".parse::<Button>().unwrap(), Button::Backward);
+        assert!("Mouse".parse::<Button>().is_err());
+    }
+
+    #[test]
+    fn test_combos() {
+        let combo: KeyCombo = "Ctrl+Alt+Delete".parse().unwrap();
+        assert_eq!(combo, KeyCombo::new(Key::Delete).ctrl().alt());
+        assert_eq!(combo.to_string(), "Ctrl+Alt+Delete");
+
+        let combo: KeyCombo = "Super+Space".parse().unwrap();
+        assert_eq!(combo, KeyCombo::new(Key::Space).meta());
+        assert_eq!(combo.format(ComboStyle::Linux), "Super+Space");
+
+        let combo: KeyCombo = "Shift+F12".parse().unwrap();
+        assert_eq!(combo.to_string().parse::<KeyCombo>().unwrap(), combo);
+
+        let combo: KeyCombo = "Meta+Shift+Ctrl+Enter".parse().unwrap();
+        assert_eq!(combo.to_string(), "Ctrl+Shift+Meta+Enter");
+        assert_eq!(combo.format(ComboStyle::MacOs), "⌃⇧⌘↩");
+
+        assert!("Ctrl+".parse::<KeyCombo>().is_err());
+        assert!("".parse::<KeyCombo>().is_err());
+    }
+}
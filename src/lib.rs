@@ -38,6 +38,31 @@
 //!
 //! ## Linux
 //! The `listen` function uses X11 APIs, and so will not work in Wayland or in the linux kernel virtual console
+//! unless the `evdev` feature is enabled, in which case it reads `/dev/input` directly when there
+//! is no X display. The same goes for `simulate`, which then writes to virtual `/dev/uinput`
+//! devices. On Wayland sessions with XWayland, X11 stays the default since it only sees
+//! XWayland clients; use `Backend::Evdev` to read every device there.
+//!
+//! # Choosing a backend
+//!
+//! `available_backends` reports what works in the current session, and the `Listener`,
+//! `Simulator` and `Grabber` builders force one backend instead of picking the best available.
+//!
+//! ```no_run
+//! use rdev::{available_backends, Backend, Listener};
+//!
+//! let evdev_listens = available_backends()
+//!     .iter()
+//!     .any(|info| info.backend == Backend::Evdev && info.listen);
+//! let mut builder = Listener::builder();
+//! if evdev_listens {
+//!     builder = builder.backend(Backend::Evdev);
+//! }
+//! // This will block.
+//! if let Err(error) = builder.build().listen(|event| println!("{:?}", event)) {
+//!     println!("Error: {:?}", error)
+//! }
+//! ```
 //!
 //! # Sending some events
 //!
@@ -89,6 +114,7 @@
 //!     pub time: SystemTime,
 //!     pub name: Option<String>,
 //!     pub event_type: EventType,
+//!     pub device_id: Option<u32>,
 //! }
 //! ```
 //!
@@ -208,20 +234,76 @@
 //!
 //! ### Linux
 //! The `grab` function use the `evdev` library to intercept events, so they will work with both X11 and Wayland
+//! and in a TTY. Without an X display, `Event.name` is `None`.
 //! In order for this to work, the process runnign the `listen` or `grab` loop needs to either run as root (not recommended),
 //! or run as a user who's a member of the `input` group (recommended)
 //! Note: on some distros, the group name for evdev access is called `plugdev`, and on some systems, both groups can exist.
 //! When in doubt, add your user to both groups if they exist.
 //!
+//! Desktop apps that cannot ask for that can grab keyboards through the X display instead,
+//! with `Grabber::builder().backend(Backend::X11)`. Grabbed keys the callback lets through
+//! are replayed with XTest, and mouse events are neither reported nor blocked. Without
+//! evdev access, `grab` falls back to this mode on its own when an X display is reachable.
+//!
+//! Touches reach the `grab` callback like any other event, but are always let through:
+//! blocking part of the fingers would leave the others stuck.
+//!
+//! Pressing `Ctrl+Alt+Pause` ends a grab and releases every device, whatever the callback
+//! does, so a buggy callback cannot leave the session without a keyboard. The combo can be
+//! changed with `GrabberBuilder::escape_combo`, and `GrabberBuilder::watchdog` also releases
+//! the devices when the callback stops returning.
+//!
+//! ## Rewriting events
+//! The evdev grab can also rewrite key and button events before the callback sees them,
+//! through processors added with `GrabberBuilder::processor`. `TapHold` makes keys act
+//! differently when tapped and held, `Layers` switches between remap tables, `Socd`
+//! keeps opposing keys from being held together, `Debounce` filters out key chatter, and
+//! `StickyKeys`, `SlowKeys` and `BounceKeys` are the usual accessibility filters, working
+//! the same whatever the desktop. `EventProcessor` can be implemented for more.
+//!
+//! `GrabberBuilder::gamepad` turns keys, mouse buttons and mouse movements into the
+//! buttons, sticks and triggers of a virtual gamepad instead, following a `GamepadMapping`,
+//! for games that only take controllers.
+//!
 //! # Serialization
 //!
 //! Event data returned by the `listen` and `grab` functions can be serialized and de-serialized with
 //! Serde if you install this library with the `serialize` feature.
+//!
+//! The feature also adds `RemapConfig`, remap profiles read from files in any Serde format,
+//! which a `Remapper` runs in the grab pipeline and can swap while grabbing.
+mod backend;
+#[cfg(feature = "unstable_grab")]
+mod gamepad;
+mod hotkey;
+mod keyname;
+mod processor;
 mod rdev;
+mod sequence;
+pub use crate::backend::{
+    available_backends, Backend, BackendInfo, Listener, ListenerBuilder, Simulator,
+    SimulatorBuilder,
+};
+#[cfg(feature = "unstable_grab")]
+pub use crate::backend::{GrabOutput, Grabber, GrabberBuilder};
+#[cfg(feature = "unstable_grab")]
+pub use crate::gamepad::{GamepadMapping, GamepadStick};
+pub use crate::hotkey::{Hotkey, HotkeyId, HotkeyManager, KeyCombo};
+pub use crate::keyname::ComboStyle;
+pub use crate::processor::{
+    BounceKeys, ChatterStats, Debounce, DebounceMode, EventProcessor, KeyChatter, Layer,
+    LayerAction, Layers, SlowKeys, Socd, SocdMode, StickyKeys, TapHold,
+};
+#[cfg(feature = "serialize")]
+pub use crate::processor::{BindingConfig, DeviceConfig, LayerConfig, RemapConfig, Remapper};
 pub use crate::rdev::{
-    Button, DisplayError, Event, EventType, GrabCallback, GrabError, Key, KeyboardState,
-    ListenError, SimulateError,
+    Button, DeviceIdentity, DisplayError, Event, EventType, GamepadAxis, GamepadButton, Gesture,
+    GrabCallback, GrabError, Key, KeyboardState, ListenError, Lock, ParseKeyError, SimulateError,
+    SwipeDirection, TouchPhase,
 };
+#[cfg(feature = "serialize")]
+pub use crate::rdev::ConfigError;
+pub use crate::sequence::{SequenceId, SequenceManager};
 
 #[cfg(target_os = "macos")]
 mod macos;
@@ -272,6 +354,44 @@ where
     _listen(callback)
 }
 
+#[cfg(all(target_os = "linux", feature = "evdev"))]
+use crate::backend::ListenOptions;
+#[cfg(all(target_os = "linux", feature = "evdev"))]
+use crate::linux::listen_evdev as _listen_evdev;
+
+/// Listening to global events by reading the input devices directly instead of
+/// going through the X server. (Linux only, requires the `evdev` feature)
+/// This works on Wayland compositors and in a TTY, for any process with read access to
+/// `/dev/input` (usually membership in the `input` group). Devices are not grabbed so
+/// events still reach other applications. `listen` falls back to this when there is
+/// no X display to connect to.
+/// Gamepads and joysticks are reported too, in the standard layout of `GamepadButton` and
+/// `GamepadAxis`, including the ones plugged in while listening. `Event.device_id` tells
+/// them apart. So are the fingers on touchscreens and touchpads, as `EventType::Touch`,
+/// see `ListenerBuilder::gestures` for the gestures they make.
+/// Caveat: `Event.name` is only filled in when an X display is reachable.
+///
+/// ```no_run
+/// use rdev::{listen_evdev, Event};
+///
+/// fn callback(event: Event) {
+///     println!("My callback {:?}", event);
+/// }
+/// fn main(){
+///     // This will block.
+///     if let Err(error) = listen_evdev(callback) {
+///         println!("Error: {:?}", error)
+///     }
+/// }
+/// ```
+#[cfg(all(target_os = "linux", feature = "evdev"))]
+pub fn listen_evdev<T>(callback: T) -> Result<(), ListenError>
+where
+    T: FnMut(Event) + 'static,
+{
+    _listen_evdev(ListenOptions::default(), callback)
+}
+
 /// Sending some events
 ///
 /// ```no_run
@@ -321,6 +441,104 @@ pub fn display_size() -> Result<(u64, u64), DisplayError> {
     _display_size()
 }
 
+#[cfg(all(target_os = "linux", feature = "evdev"))]
+use crate::linux::simulate_uinput as _simulate_uinput;
+#[cfg(all(target_os = "linux", feature = "evdev"))]
+pub use crate::linux::UInputSimulator;
+#[cfg(all(target_os = "linux", feature = "evdev"))]
+pub use crate::linux::{
+    AbsoluteAxis, Bus, Led, RelativeAxis, VirtualDevice, VirtualDeviceBuilder,
+};
+
+/// Sending some events through a virtual uinput device instead of the X server.
+/// (Linux only, requires the `evdev` feature)
+/// This works on Wayland compositors, in a TTY and in containers, as long as the process
+/// can write to `/dev/uinput`. The virtual devices are created on the first call, and
+/// `MouseMove` coordinates are mapped onto the X display size when there is one, or
+/// onto a 1920x1080 screen otherwise. Use `UInputSimulator::with_display_size` when
+/// you know better.
+///
+/// ```no_run
+/// use rdev::{simulate_uinput, EventType, Key};
+///
+/// simulate_uinput(&EventType::KeyPress(Key::KeyS)).unwrap();
+/// simulate_uinput(&EventType::KeyRelease(Key::KeyS)).unwrap();
+/// simulate_uinput(&EventType::MouseMoveRelative { delta_x: 10.0, delta_y: 0.0 }).unwrap();
+/// ```
+#[cfg(all(target_os = "linux", feature = "evdev"))]
+pub fn simulate_uinput(event_type: &EventType) -> Result<(), SimulateError> {
+    _simulate_uinput(event_type)
+}
+
+#[cfg(all(target_os = "linux", feature = "evdev"))]
+use crate::linux::devices as _devices;
+#[cfg(all(target_os = "linux", feature = "evdev"))]
+pub use crate::linux::DeviceInfo;
+
+/// Lists the evdev input devices with their identity and capabilities, to let
+/// users pick one. (Linux only, requires the `evdev` feature)
+/// Devices the process cannot read are left out, reading them usually requires
+/// being in the `input` group. `DeviceInfo.id` is the `Event.device_id` of the
+/// events of the device with the evdev backend, and `DeviceInfo::identity` what
+/// per-device `RemapConfig` rules match on.
+///
+/// ```no_run
+/// use rdev::devices;
+///
+/// for device in devices() {
+///     if device.is_keyboard {
+///         println!("{} at {}", device.name, device.path.display());
+///     }
+/// }
+/// ```
+#[cfg(all(target_os = "linux", feature = "evdev"))]
+pub fn devices() -> Vec<DeviceInfo> {
+    _devices()
+}
+
+#[cfg(target_os = "linux")]
+use crate::linux::lock_state as _lock_state;
+
+/// Whether the lock is on, `None` when it cannot be told. (Linux only)
+/// The X server is asked when there is one, otherwise with the `evdev` feature the
+/// keyboard LEDs are read, which compositors and the console keep in sync.
+/// Set the locks by simulating `EventType::LockChange`.
+///
+/// ```no_run
+/// use rdev::{lock_state, simulate, EventType, Lock};
+///
+/// if lock_state(Lock::NumLock) == Some(false) {
+///     simulate(&EventType::LockChange {
+///         lock: Lock::NumLock,
+///         on: true,
+///     })
+///     .unwrap();
+/// }
+/// ```
+#[cfg(target_os = "linux")]
+pub fn lock_state(lock: Lock) -> Option<bool> {
+    _lock_state(lock)
+}
+
+#[cfg(all(target_os = "linux", feature = "evdev"))]
+use crate::linux::set_led as _set_led;
+
+/// Turns a LED on or off on every keyboard having it, without changing the lock
+/// it usually shows. (Linux only, requires the `evdev` feature)
+/// This needs write access to the device nodes, and the compositor or the console
+/// sets the LED back when the lock changes.
+///
+/// ```no_run
+/// use rdev::{set_led, Led};
+///
+/// // Recording a macro
+/// set_led(Led::ScrollLock, true).unwrap();
+/// ```
+#[cfg(all(target_os = "linux", feature = "evdev"))]
+pub fn set_led(led: Led, on: bool) -> Result<(), SimulateError> {
+    _set_led(led, on)
+}
+
 #[cfg(feature = "unstable_grab")]
 #[cfg(target_os = "linux")]
 pub use crate::linux::grab as _grab;
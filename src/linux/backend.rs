@@ -0,0 +1,195 @@
+#[cfg(feature = "unstable_grab")]
+use crate::backend::GrabOptions;
+use crate::backend::{Backend, BackendInfo, ListenOptions};
+use crate::linux::common::Display;
+#[cfg(feature = "evdev")]
+use crate::linux::evdev::DEV_PATH;
+#[cfg(feature = "evdev")]
+use crate::linux::uinput::UInputSimulator;
+#[cfg(feature = "unstable_grab")]
+use crate::processor::{EventProcessor, Pipeline};
+#[cfg(feature = "unstable_grab")]
+use crate::rdev::GrabError;
+use crate::rdev::{Event, EventType, ListenError, SimulateError};
+use lazy_static::lazy_static;
+#[cfg(feature = "evdev")]
+use std::ffi::CString;
+#[cfg(feature = "evdev")]
+use std::fs::read_dir;
+#[cfg(feature = "evdev")]
+use std::os::unix::ffi::OsStrExt;
+
+#[cfg(feature = "evdev")]
+static UINPUT_PATH: &str = "/dev/uinput";
+
+lazy_static! {
+    // Probing opens a display connection, so it is only done once for `simulate`
+    static ref SIMULATE_BACKEND: Backend = automatic(uinput_writable());
+}
+
+fn x11_reachable() -> bool {
+    Display::new().is_some()
+}
+
+#[cfg(feature = "evdev")]
+fn accessible(path: &[u8], mode: libc::c_int) -> bool {
+    match CString::new(path) {
+        Ok(path) => unsafe { libc::access(path.as_ptr(), mode) == 0 },
+        Err(_) => false,
+    }
+}
+
+/// At least one `/dev/input/event*` node can be read.
+#[cfg(feature = "evdev")]
+fn input_devices_readable() -> bool {
+    let entries = match read_dir(DEV_PATH) {
+        Ok(entries) => entries,
+        Err(_) => return false,
+    };
+    entries.filter_map(Result::ok).any(|entry| {
+        entry.file_name().as_bytes().starts_with(b"event")
+            && accessible(entry.path().as_os_str().as_bytes(), libc::R_OK)
+    })
+}
+
+#[cfg(not(feature = "evdev"))]
+fn input_devices_readable() -> bool {
+    false
+}
+
+#[cfg(feature = "evdev")]
+fn uinput_writable() -> bool {
+    accessible(UINPUT_PATH.as_bytes(), libc::W_OK)
+}
+
+#[cfg(not(feature = "evdev"))]
+fn uinput_writable() -> bool {
+    false
+}
+
+/// X11 stays the default whenever the display is reachable, as it needs no special
+/// permissions and is what existing callers expect. Evdev only takes over without it,
+/// on Wayland without XWayland or in a TTY, and can be asked for explicitly otherwise.
+fn automatic(evdev_usable: bool) -> Backend {
+    if evdev_usable && !x11_reachable() {
+        Backend::Evdev
+    } else {
+        Backend::X11
+    }
+}
+
+pub fn available_backends() -> Vec<BackendInfo> {
+    let mut backends = Vec::new();
+    if let Some(display) = Display::new() {
+        // Simulating only needs XTest, the others need XInput 2.1 as well
+        let xinput2 = display.has_xinput2();
+        backends.push(BackendInfo {
+            backend: Backend::X11,
+            listen: xinput2,
+            simulate: true,
+            grab: cfg!(feature = "unstable_grab") && xinput2,
+        });
+    }
+    let readable = input_devices_readable();
+    let writable = uinput_writable();
+    if readable || writable {
+        backends.push(BackendInfo {
+            backend: Backend::Evdev,
+            listen: readable,
+            simulate: writable,
+            grab: cfg!(feature = "unstable_grab") && readable && writable,
+        });
+    }
+    backends
+}
+
+pub fn listen_with<T>(
+    backend: Option<Backend>,
+    #[cfg_attr(not(feature = "evdev"), allow(unused_variables))] options: ListenOptions,
+    callback: T,
+) -> Result<(), ListenError>
+where
+    T: FnMut(Event) + 'static,
+{
+    match backend.unwrap_or_else(|| automatic(input_devices_readable())) {
+        Backend::X11 => crate::linux::listen::listen(callback),
+        #[cfg(feature = "evdev")]
+        Backend::Evdev => crate::linux::evdev_listen::listen(options, callback),
+        _ => Err(ListenError::UnsupportedBackend),
+    }
+}
+
+pub fn listen<T>(callback: T) -> Result<(), ListenError>
+where
+    T: FnMut(Event) + 'static,
+{
+    listen_with(None, ListenOptions::default(), callback)
+}
+
+pub fn simulate(event_type: &EventType) -> Result<(), SimulateError> {
+    match *SIMULATE_BACKEND {
+        #[cfg(feature = "evdev")]
+        Backend::Evdev => crate::linux::uinput::simulate(event_type),
+        _ => crate::linux::simulate::simulate(event_type),
+    }
+}
+
+#[cfg(feature = "unstable_grab")]
+pub fn grab_with<T>(
+    backend: Option<Backend>,
+    processors: Vec<Box<dyn EventProcessor>>,
+    options: GrabOptions,
+    callback: T,
+) -> Result<(), GrabError>
+where
+    T: Fn(Event) -> Option<Event> + 'static,
+{
+    // The X11 grab only covers keyboards, so evdev stays the default when usable.
+    // Processors and the gamepad only run there.
+    let evdev_usable = input_devices_readable() && uinput_writable();
+    let pipeline = Pipeline::new(processors);
+    let evdev_only = !pipeline.is_empty() || options.gamepad.is_some();
+    match backend {
+        None if evdev_usable || !x11_reachable() || evdev_only => {
+            crate::linux::grab::grab(pipeline, options, callback)
+        }
+        None | Some(Backend::X11) if !evdev_only => crate::linux::x11_grab::grab(options, callback),
+        Some(Backend::Evdev) => crate::linux::grab::grab(pipeline, options, callback),
+        _ => Err(GrabError::UnsupportedBackend),
+    }
+}
+
+#[cfg(feature = "unstable_grab")]
+pub fn grab<T>(callback: T) -> Result<(), GrabError>
+where
+    T: Fn(Event) -> Option<Event> + 'static,
+{
+    grab_with(None, vec![], GrabOptions::default(), callback)
+}
+
+pub enum NativeSimulator {
+    X11,
+    #[cfg(feature = "evdev")]
+    Uinput(UInputSimulator),
+}
+
+impl NativeSimulator {
+    pub fn new(backend: Option<Backend>) -> Result<NativeSimulator, SimulateError> {
+        match backend.unwrap_or(*SIMULATE_BACKEND) {
+            Backend::X11 => Ok(NativeSimulator::X11),
+            #[cfg(feature = "evdev")]
+            Backend::Evdev => Ok(NativeSimulator::Uinput(
+                UInputSimulator::new().map_err(|_| SimulateError)?,
+            )),
+            _ => Err(SimulateError),
+        }
+    }
+
+    pub fn simulate(&self, event_type: &EventType) -> Result<(), SimulateError> {
+        match self {
+            NativeSimulator::X11 => crate::linux::simulate::simulate(event_type),
+            #[cfg(feature = "evdev")]
+            NativeSimulator::Uinput(simulator) => simulator.simulate(event_type),
+        }
+    }
+}
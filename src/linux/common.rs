@@ -1,17 +1,49 @@
-use crate::linux::keyboard::Keyboard;
 use crate::linux::keycodes::key_from_code;
-use crate::rdev::{Button, Event, EventType, KeyboardState};
+use crate::linux::locks::x11_lock_state;
+use crate::rdev::{Button, EventType, Lock};
 use std::convert::TryInto;
-use std::os::raw::{c_int, c_uchar, c_uint};
+use std::ffi::CStr;
+use std::os::raw::{c_int, c_uchar};
 use std::ptr::null;
-use std::time::SystemTime;
+use x11::xinput2;
 use x11::xlib;
 
 pub const TRUE: c_int = 1;
 pub const FALSE: c_int = 0;
 
-// A global for the callbacks.
-pub static mut KEYBOARD: Option<Keyboard> = None;
+/// Raw events on master devices and scroll classes appeared in XInput 2.1.
+const XI_MAJOR: c_int = 2;
+const XI_MINOR: c_int = 1;
+
+/// Checks XInput 2.1 is there, returns the extension opcode used to recognize
+/// its events.
+pub unsafe fn xinput2_opcode(display: *mut xlib::Display) -> Option<c_int> {
+    let mut opcode = 0;
+    let mut first_event = 0;
+    let mut first_error = 0;
+    let name = CStr::from_bytes_with_nul(b"XInputExtension\0").expect("ends with a nul byte");
+    if xlib::XQueryExtension(
+        display,
+        name.as_ptr(),
+        &mut opcode,
+        &mut first_event,
+        &mut first_error,
+    ) == FALSE
+    {
+        return None;
+    }
+    let mut major = XI_MAJOR;
+    let mut minor = XI_MINOR;
+    if xinput2::XIQueryVersion(display, &mut major, &mut minor) != xlib::Success as c_int {
+        return None;
+    }
+    // The server answers with the version it supports, which may be older. Raw
+    // events and scroll classes need 2.1.
+    if (major, minor) < (XI_MAJOR, XI_MINOR) {
+        return None;
+    }
+    Some(opcode)
+}
 
 pub fn convert_event(code: c_uchar, type_: c_int, x: f64, y: f64) -> Option<EventType> {
     match type_ {
@@ -57,23 +89,6 @@ pub fn convert_event(code: c_uchar, type_: c_int, x: f64, y: f64) -> Option<Even
     }
 }
 
-pub fn convert(
-    keyboard: &mut Option<Keyboard>,
-    code: c_uint,
-    type_: c_int,
-    x: f64,
-    y: f64,
-) -> Option<Event> {
-    let event_type = convert_event(code as c_uchar, type_, x, y)?;
-    let kb: &mut Keyboard = (*keyboard).as_mut()?;
-    let name = kb.add(&event_type);
-    Some(Event {
-        event_type,
-        time: SystemTime::now(),
-        name,
-    })
-}
-
 pub struct Display {
     display: *mut xlib::Display,
 }
@@ -103,7 +118,16 @@ impl Display {
         }
     }
 
-    #[cfg(feature = "unstable_grab")]
+    /// Whether the server has the XInput 2.1 that listening and grabbing need.
+    pub fn has_xinput2(&self) -> bool {
+        unsafe { xinput2_opcode(self.display).is_some() }
+    }
+
+    pub fn get_lock_state(&self, lock: Lock) -> Option<bool> {
+        unsafe { x11_lock_state(self.display, lock) }
+    }
+
+    #[cfg(feature = "evdev")]
     pub fn get_mouse_pos(&self) -> Option<(u64, u64)> {
         unsafe {
             let root_window = xlib::XRootWindow(self.display, 0);
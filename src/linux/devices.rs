@@ -0,0 +1,201 @@
+use crate::linux::evdev::{get_device_nodes, DEV_PATH};
+use crate::linux::evdev_keycodes::{evdev_key_to_rdev_button, evdev_key_to_rdev_key};
+use crate::linux::virtual_device::{AbsoluteAxis, Bus, Led, RelativeAxis};
+use crate::rdev::{Button, DeviceIdentity, Key};
+use evdev_rs::enums::{int_to_ev_key, EventCode, EV_ABS, EV_KEY, EV_REL};
+use evdev_rs::{Device, DeviceWrapper};
+use std::fs::File;
+use std::path::PathBuf;
+
+// KEY_MAX
+const MAX_CODE: u32 = 0x2ff;
+
+/// An input device, as returned by `devices`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[non_exhaustive]
+pub struct DeviceInfo {
+    /// The `/dev/input/event*` node.
+    pub path: PathBuf,
+    /// The `Event.device_id` of its events with the evdev backend, the number
+    /// of its node.
+    pub id: u32,
+    pub name: String,
+    /// Where the device is plugged, like `usb-0000:00:14.0-2/input0`.
+    pub phys: Option<String>,
+    /// A serial number, most devices have none.
+    pub uniq: Option<String>,
+    pub vendor: u16,
+    pub product: u16,
+    pub version: u16,
+    pub bus: Bus,
+    /// The keyboard keys rdev knows about.
+    pub keys: Vec<Key>,
+    /// The mouse buttons rdev knows about.
+    pub buttons: Vec<Button>,
+    pub relative_axes: Vec<RelativeAxis>,
+    pub absolute_axes: Vec<AbsoluteAxis>,
+    pub leds: Vec<Led>,
+    /// Has the letters and the usual keys around them, power buttons and
+    /// headset remotes with a handful of keys are not keyboards.
+    pub is_keyboard: bool,
+    /// Moves relatively and has a left button.
+    pub is_mouse: bool,
+    /// Moves absolutely and tells fingers apart from pens.
+    pub is_touchpad: bool,
+    pub is_gamepad: bool,
+}
+
+impl DeviceInfo {
+    /// What `DeviceConfig` rules match on, which stays the same when the device
+    /// is plugged again.
+    pub fn identity(&self) -> DeviceIdentity {
+        DeviceIdentity {
+            name: self.name.clone(),
+            vendor: self.vendor,
+            product: self.product,
+            phys: self.phys.clone(),
+        }
+    }
+
+    fn new(id: u32, path: PathBuf, device: &Device) -> DeviceInfo {
+        let codes: Vec<EV_KEY> = (0..=MAX_CODE)
+            .filter_map(int_to_ev_key)
+            .filter(|key| device.has(EventCode::EV_KEY(*key)))
+            .collect();
+        let has_key = |key| codes.contains(&key);
+        let has_rel = |axis| device.has(EventCode::EV_REL(axis));
+        let has_abs = |axis| device.has(EventCode::EV_ABS(axis));
+        DeviceInfo {
+            path,
+            id,
+            name: device.name().unwrap_or_default().to_string(),
+            phys: device.phys().map(str::to_string),
+            uniq: device.uniq().map(str::to_string),
+            vendor: device.vendor_id(),
+            product: device.product_id(),
+            version: device.version(),
+            bus: Bus::from_code(device.bustype()),
+            keys: codes.iter().filter_map(evdev_key_to_rdev_key).collect(),
+            buttons: codes.iter().filter_map(evdev_key_to_rdev_button).collect(),
+            relative_axes: RelativeAxis::ALL
+                .iter()
+                .copied()
+                .filter(|axis| has_rel(axis.codes()[0]))
+                .collect(),
+            absolute_axes: AbsoluteAxis::ALL
+                .iter()
+                .copied()
+                .filter(|axis| has_abs(axis.code()))
+                .collect(),
+            leds: Led::ALL
+                .iter()
+                .copied()
+                .filter(|led| device.has(EventCode::EV_LED(led.code())))
+                .collect(),
+            is_keyboard: is_keyboard(has_key),
+            is_mouse: is_mouse(has_key, has_rel),
+            is_touchpad: is_touchpad(has_key, has_abs),
+            is_gamepad: is_gamepad(has_key),
+        }
+    }
+}
+
+fn is_keyboard(has_key: impl Fn(EV_KEY) -> bool) -> bool {
+    [
+        EV_KEY::KEY_ESC,
+        EV_KEY::KEY_ENTER,
+        EV_KEY::KEY_SPACE,
+        EV_KEY::KEY_A,
+        EV_KEY::KEY_Z,
+    ]
+    .into_iter()
+    .all(has_key)
+}
+
+fn is_mouse(has_key: impl Fn(EV_KEY) -> bool, has_rel: impl Fn(EV_REL) -> bool) -> bool {
+    has_rel(EV_REL::REL_X) && has_rel(EV_REL::REL_Y) && has_key(EV_KEY::BTN_LEFT)
+}
+
+fn is_touchpad(has_key: impl Fn(EV_KEY) -> bool, has_abs: impl Fn(EV_ABS) -> bool) -> bool {
+    has_abs(EV_ABS::ABS_X)
+        && has_abs(EV_ABS::ABS_Y)
+        && has_key(EV_KEY::BTN_TOOL_FINGER)
+        && !has_key(EV_KEY::BTN_TOOL_PEN)
+}
+
+fn is_gamepad(has_key: impl Fn(EV_KEY) -> bool) -> bool {
+    has_key(EV_KEY::BTN_SOUTH)
+}
+
+pub fn devices() -> Vec<DeviceInfo> {
+    get_device_nodes(DEV_PATH)
+        .unwrap_or_default()
+        .into_iter()
+        .filter_map(|(id, path)| {
+            let device = Device::new_from_file(File::open(&path).ok()?).ok()?;
+            Some(DeviceInfo::new(id, path, &device))
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn has<T: PartialEq>(codes: &[T]) -> impl Fn(T) -> bool + '_ {
+        move |code| codes.contains(&code)
+    }
+
+    #[test]
+    fn test_keyboard() {
+        let keys = [
+            EV_KEY::KEY_ESC,
+            EV_KEY::KEY_ENTER,
+            EV_KEY::KEY_SPACE,
+            EV_KEY::KEY_A,
+            EV_KEY::KEY_Z,
+            EV_KEY::KEY_LEFTSHIFT,
+        ];
+        assert!(is_keyboard(has(&keys)));
+        // Power buttons and headset remotes
+        assert!(!is_keyboard(has(&[EV_KEY::KEY_POWER])));
+        assert!(!is_keyboard(has(&[
+            EV_KEY::KEY_VOLUMEUP,
+            EV_KEY::KEY_VOLUMEDOWN,
+            EV_KEY::KEY_ENTER
+        ])));
+    }
+
+    #[test]
+    fn test_mouse() {
+        let axes = [EV_REL::REL_X, EV_REL::REL_Y, EV_REL::REL_WHEEL];
+        assert!(is_mouse(has(&[EV_KEY::BTN_LEFT]), has(&axes)));
+        // A scroll wheel alone
+        assert!(!is_mouse(
+            has(&[EV_KEY::BTN_LEFT]),
+            has(&[EV_REL::REL_WHEEL])
+        ));
+        assert!(!is_mouse(has(&[]), has(&axes)));
+    }
+
+    #[test]
+    fn test_touchpad() {
+        let axes = [EV_ABS::ABS_X, EV_ABS::ABS_Y, EV_ABS::ABS_MT_SLOT];
+        let touchpad = [EV_KEY::BTN_LEFT, EV_KEY::BTN_TOOL_FINGER, EV_KEY::BTN_TOUCH];
+        assert!(is_touchpad(has(&touchpad), has(&axes)));
+        let tablet = [
+            EV_KEY::BTN_TOOL_FINGER,
+            EV_KEY::BTN_TOOL_PEN,
+            EV_KEY::BTN_TOUCH,
+        ];
+        assert!(!is_touchpad(has(&tablet), has(&axes)));
+        // Gamepads have absolute axes too
+        assert!(!is_touchpad(has(&[EV_KEY::BTN_SOUTH]), has(&axes)));
+    }
+
+    #[test]
+    fn test_gamepad() {
+        assert!(is_gamepad(has(&[EV_KEY::BTN_SOUTH, EV_KEY::BTN_EAST])));
+        assert!(!is_gamepad(has(&[EV_KEY::BTN_LEFT, EV_KEY::BTN_RIGHT])));
+    }
+}
@@ -0,0 +1,269 @@
+use crate::linux::common::Display;
+use crate::linux::evdev_keycodes::{evdev_key_to_rdev_button, evdev_key_to_rdev_key};
+use crate::rdev::{EventType, Lock};
+use epoll::ControlOptions::EPOLL_CTL_ADD;
+use evdev_rs::{
+    enums::{EventCode, EV_ABS, EV_LED, EV_REL},
+    Device, DeviceWrapper, InputEvent,
+};
+use inotify::{Inotify, WatchMask};
+use std::collections::HashSet;
+use std::ffi::{OsStr, OsString};
+use std::fs::{read_dir, File};
+use std::io;
+use std::os::unix::{
+    fs::FileTypeExt,
+    io::{AsRawFd, FromRawFd, OwnedFd, RawFd},
+};
+use std::path::{Path, PathBuf};
+
+pub static DEV_PATH: &str = "/dev/input";
+pub const INOTIFY_DATA: u64 = u64::MAX;
+pub const EPOLLIN: epoll::Events = epoll::Events::EPOLLIN;
+/// `REL_WHEEL_HI_RES` units in one wheel notch.
+pub const HI_RES_NOTCH: f64 = 120.0;
+/// Used for the pointer position when there is no X display to ask.
+pub const DEFAULT_DISPLAY_SIZE: (u64, u64) = (1920, 1080);
+
+/// Size of the X display if there is one, `DEFAULT_DISPLAY_SIZE` otherwise.
+pub fn display_size_or_default() -> (u64, u64) {
+    Display::new()
+        .and_then(|display| display.get_size())
+        .unwrap_or(DEFAULT_DISPLAY_SIZE)
+}
+
+// TODO The x, y coordinates are currently wrong !! Is there mouse acceleration
+// to take into account ??
+
+pub fn evdev_event_to_rdev_event(
+    event: &InputEvent,
+    device: &Device,
+    x: &mut f64,
+    y: &mut f64,
+    w: f64,
+    h: f64,
+) -> Option<EventType> {
+    match &event.event_code {
+        EventCode::EV_KEY(key) => {
+            if let Some(button) = evdev_key_to_rdev_button(key) {
+                // first check if pressed key is a mouse button
+                match event.value {
+                    0 => Some(EventType::ButtonRelease(button)),
+                    _ => Some(EventType::ButtonPress(button)),
+                }
+            } else if let Some(key) = evdev_key_to_rdev_key(key) {
+                // check if pressed key is a keyboard key
+                match event.value {
+                    0 => Some(EventType::KeyRelease(key)),
+                    _ => Some(EventType::KeyPress(key)),
+                }
+            } else {
+                // if neither mouse button nor keyboard key, return none
+                None
+            }
+        }
+        EventCode::EV_REL(mouse) => match mouse {
+            EV_REL::REL_X => {
+                let dx = event.value as f64;
+                *x += dx;
+                if *x < 0.0 {
+                    *x = 0.0;
+                }
+                if *x > w {
+                    *x = w;
+                }
+                Some(EventType::MouseMove { x: *x, y: *y })
+            }
+            EV_REL::REL_Y => {
+                let dy = event.value as f64;
+                *y += dy;
+                if *y < 0.0 {
+                    *y = 0.0;
+                }
+                if *y > h {
+                    *y = h;
+                }
+                Some(EventType::MouseMove { x: *x, y: *y })
+            }
+            EV_REL::REL_HWHEEL => Some(EventType::Wheel {
+                delta_x: event.value.into(),
+                delta_y: 0,
+            }),
+            EV_REL::REL_WHEEL => Some(EventType::Wheel {
+                delta_x: 0,
+                delta_y: event.value.into(),
+            }),
+            EV_REL::REL_HWHEEL_HI_RES => Some(EventType::SmoothWheel {
+                delta_x: f64::from(event.value) / HI_RES_NOTCH,
+                delta_y: 0.0,
+            }),
+            EV_REL::REL_WHEEL_HI_RES => Some(EventType::SmoothWheel {
+                delta_x: 0.0,
+                delta_y: f64::from(event.value) / HI_RES_NOTCH,
+            }),
+            // Other EV_REL events cannot be represented by rdev
+            _ => None,
+        },
+        // Multitouch slots only make sense together
+        EventCode::EV_ABS(axis) if *axis as u16 >= EV_ABS::ABS_MT_SLOT as u16 => None,
+        EventCode::EV_ABS(axis) => {
+            let info = device.abs_info(&event.event_code)?;
+            Some(EventType::Axis {
+                code: *axis as u16,
+                value: event.value,
+                min: info.minimum,
+                max: info.maximum,
+            })
+        }
+        // Keyboards report their LEDs changing, which follow the locks
+        EventCode::EV_LED(led) => {
+            let lock = match led {
+                EV_LED::LED_CAPSL => Lock::CapsLock,
+                EV_LED::LED_NUML => Lock::NumLock,
+                EV_LED::LED_SCROLLL => Lock::ScrollLock,
+                _ => return None,
+            };
+            Some(EventType::LockChange {
+                lock,
+                on: event.value != 0,
+            })
+        }
+        // Other event_codes cannot be represented by rdev,
+        // and some never will e.g. EV_SYN
+        _ => None,
+    }
+}
+
+/// The evdev nodes in `path` by number, see `get_device_nodes`.
+pub fn get_device_files<T>(path: T) -> io::Result<Vec<(u32, File)>>
+where
+    T: AsRef<Path>,
+{
+    get_device_nodes(path)?
+        .into_iter()
+        .map(|(number, path)| Ok((number, File::open(path)?)))
+        .collect()
+}
+
+/// The N of an `eventN` node, which is the `Event.device_id` of its events.
+/// The other files are the legacy "mouse.*", "mice" and "js.*" nodes, which
+/// don't play nice with libevdev, as they are not evdev nodes,
+/// see: https://askubuntu.com/questions/1043832/difference-between-dev-input-mouse0-and-dev-input-mice
+/// Gamepads and joysticks are read through their evdev node instead of `js*`.
+fn node_number(file_name: &OsStr) -> Option<u32> {
+    file_name.to_str()?.strip_prefix("event")?.parse().ok()
+}
+
+/// The evdev nodes in `path`, without the legacy mouse and joystick ones.
+pub fn get_device_paths<T>(path: T) -> io::Result<Vec<PathBuf>>
+where
+    T: AsRef<Path>,
+{
+    Ok(get_device_nodes(path)?
+        .into_iter()
+        .map(|(_, path)| path)
+        .collect())
+}
+
+/// The evdev nodes in `path` with their number, in order.
+pub fn get_device_nodes<T>(path: T) -> io::Result<Vec<(u32, PathBuf)>>
+where
+    T: AsRef<Path>,
+{
+    let mut res = Vec::new();
+    for entry in read_dir(path)? {
+        let entry = entry?;
+        // /dev/input files are character devices
+        if !entry.file_type()?.is_char_device() {
+            continue;
+        }
+        if let Some(number) = node_number(&entry.file_name()) {
+            res.push((number, entry.path()));
+        }
+    }
+    res.sort();
+    Ok(res)
+}
+
+/// The epoll instance is closed when dropped, whichever way listening or
+/// grabbing ends.
+pub fn epoll_watch_all<'a, T>(device_files: T) -> io::Result<OwnedFd>
+where
+    T: Iterator<Item = &'a File>,
+{
+    let epoll = unsafe { OwnedFd::from_raw_fd(epoll::create(true)?) };
+    let epoll_fd = epoll.as_raw_fd();
+    // add file descriptors to epoll
+    for (file_idx, file) in device_files.enumerate() {
+        let epoll_event = epoll::Event::new(EPOLLIN, file_idx as u64);
+        epoll::ctl(epoll_fd, EPOLL_CTL_ADD, file.as_raw_fd(), epoll_event)?;
+    }
+    Ok(epoll)
+}
+
+pub fn inotify_devices() -> io::Result<Inotify> {
+    let mut inotify = Inotify::init()?;
+    // New nodes belong to root until udev gives access to them, which changes
+    // their attributes.
+    inotify.add_watch(DEV_PATH, WatchMask::CREATE | WatchMask::ATTRIB)?;
+    Ok(inotify)
+}
+
+/// Opens the node just created, or just made readable, and adds it at the end of
+/// `devices`, returning its number. `None` for the files that are not evdev
+/// nodes and for the nodes udev has not given access to yet: those are kept in
+/// `pending` until their attributes change, instead of waiting for udev here.
+pub fn add_device_to_epoll_from_inotify_event(
+    epoll_fd: RawFd,
+    event: inotify::Event<&OsStr>,
+    devices: &mut Vec<Device>,
+    pending: &mut HashSet<OsString>,
+) -> io::Result<Option<u32>> {
+    let name = event.name.unwrap();
+    // Gamepads come with a js node besides their evdev one
+    let number = match node_number(name) {
+        Some(number) => number,
+        None => return Ok(None),
+    };
+    // Attributes also change on the nodes already opened
+    if !pending.remove(name) && !event.mask.contains(inotify::EventMask::CREATE) {
+        return Ok(None);
+    }
+    let mut device_path = OsString::from(DEV_PATH);
+    device_path.push(OsString::from("/"));
+    device_path.push(name);
+    // new plug events
+    let file = match File::open(&device_path) {
+        Ok(file) => file,
+        Err(err) if err.kind() == io::ErrorKind::PermissionDenied => {
+            pending.insert(name.to_os_string());
+            return Ok(None);
+        }
+        Err(err) => return Err(err),
+    };
+    let fd = file.as_raw_fd();
+    let device = Device::new_from_fd(file)?;
+    let event = epoll::Event::new(EPOLLIN, devices.len() as u64);
+    // Once watched, so that failing leaves `devices` as it was
+    epoll::ctl(epoll_fd, EPOLL_CTL_ADD, fd, event)?;
+    devices.push(device);
+    Ok(Some(number))
+}
+
+/// Creates an inotify instance looking at /dev/input and adds it to an epoll instance.
+/// Ensures devices isnt too long, which would make the epoll data ambigious.
+pub fn setup_inotify(epoll_fd: RawFd, devices: &[Device]) -> io::Result<Inotify> {
+    //Ensure there is space for inotify at last epoll index.
+    if devices.len() as u64 >= INOTIFY_DATA {
+        eprintln!("number of devices: {}", devices.len());
+        return Err(io::Error::new(
+            io::ErrorKind::Other,
+            "too many device files!",
+        ));
+    }
+    // Set up inotify to listen for new devices being plugged in
+    let inotify = inotify_devices()?;
+    let epoll_event = epoll::Event::new(EPOLLIN, INOTIFY_DATA);
+    epoll::ctl(epoll_fd, EPOLL_CTL_ADD, inotify.as_raw_fd(), epoll_event)?;
+    Ok(inotify)
+}
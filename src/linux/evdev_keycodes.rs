@@ -0,0 +1,263 @@
+use crate::rdev::{Button, GamepadAxis, GamepadButton, Key};
+use evdev_rs::enums::{int_to_ev_key, EV_ABS, EV_KEY};
+
+macro_rules! convert_keys {
+    ($($ev_key:ident, $rdev_key:ident),*) => {
+        //TODO: make const when rust lang issue #49146 is fixed
+        #[allow(unreachable_patterns)]
+        pub fn evdev_key_to_rdev_key(key: &EV_KEY) -> Option<Key> {
+            match key {
+                $(
+                    EV_KEY::$ev_key => Some(Key::$rdev_key),
+                )*
+                _ => None,
+            }
+        }
+
+        //TODO: make const when rust lang issue #49146 is fixed
+        #[allow(unreachable_patterns)]
+        pub fn rdev_key_to_evdev_key(key: &Key) -> Option<EV_KEY> {
+            match key {
+                $(
+                    Key::$rdev_key => Some(EV_KEY::$ev_key),
+                )*
+                // Unknown keys carry X11 keycodes, which are offset by 8 from evdev codes
+                Key::Unknown(code) => code.checked_sub(8).and_then(int_to_ev_key),
+                _ => None
+            }
+        }
+    };
+}
+
+macro_rules! convert_buttons {
+    ($($ev_key:ident, $rdev_key:ident),*) => {
+        //TODO: make const when rust lang issue #49146 is fixed
+        pub fn evdev_key_to_rdev_button(key: &EV_KEY) -> Option<Button> {
+            match key {
+                $(
+                    EV_KEY::$ev_key => Some(Button::$rdev_key),
+                )*
+                _ => None,
+            }
+        }
+
+        //TODO: make const when rust lang issue #49146 is fixed
+        pub fn rdev_button_to_evdev_key(button: &Button) -> Option<EV_KEY> {
+            match button {
+                $(
+                    Button::$rdev_key => Some(EV_KEY::$ev_key),
+                )*
+                _ => None
+            }
+        }
+    };
+}
+
+#[rustfmt::skip]
+convert_buttons!(
+    BTN_LEFT, Left,
+    BTN_RIGHT, Right,
+    BTN_MIDDLE, Middle,
+    BTN_SIDE, Backward,
+    BTN_EXTRA, Forward
+);
+
+//TODO: IntlBackslash, kpDelete
+#[rustfmt::skip]
+convert_keys!(
+    KEY_ESC, Escape,
+    KEY_1, Num1,
+    KEY_2, Num2,
+    KEY_3, Num3,
+    KEY_4, Num4,
+    KEY_5, Num5,
+    KEY_6, Num6,
+    KEY_7, Num7,
+    KEY_8, Num8,
+    KEY_9, Num9,
+    KEY_0, Num0,
+    KEY_MINUS, Minus,
+    KEY_EQUAL, Equal,
+    KEY_BACKSPACE, Backspace,
+    KEY_TAB, Tab,
+    KEY_Q, KeyQ,
+    KEY_W, KeyW,
+    KEY_E, KeyE,
+    KEY_R, KeyR,
+    KEY_T, KeyT,
+    KEY_Y, KeyY,
+    KEY_U, KeyU,
+    KEY_I, KeyI,
+    KEY_O, KeyO,
+    KEY_P, KeyP,
+    KEY_LEFTBRACE, LeftBracket,
+    KEY_RIGHTBRACE, RightBracket,
+    KEY_ENTER, Return,
+    KEY_LEFTCTRL, ControlLeft,
+    KEY_A, KeyA,
+    KEY_S, KeyS,
+    KEY_D, KeyD,
+    KEY_F, KeyF,
+    KEY_G, KeyG,
+    KEY_H, KeyH,
+    KEY_J, KeyJ,
+    KEY_K, KeyK,
+    KEY_L, KeyL,
+    KEY_SEMICOLON, SemiColon,
+    KEY_APOSTROPHE, Quote,
+    KEY_GRAVE, BackQuote,
+    KEY_LEFTSHIFT, ShiftLeft,
+    KEY_BACKSLASH, BackSlash,
+    KEY_Z, KeyZ,
+    KEY_X, KeyX,
+    KEY_C, KeyC,
+    KEY_V, KeyV,
+    KEY_B, KeyB,
+    KEY_N, KeyN,
+    KEY_M, KeyM,
+    KEY_COMMA, Comma,
+    KEY_DOT, Dot,
+    KEY_SLASH, Slash,
+    KEY_RIGHTSHIFT, ShiftRight,
+    KEY_KPASTERISK , KpMultiply,
+    KEY_LEFTALT, Alt,
+    KEY_SPACE, Space,
+    KEY_CAPSLOCK, CapsLock,
+    KEY_F1, F1,
+    KEY_F2, F2,
+    KEY_F3, F3,
+    KEY_F4, F4,
+    KEY_F5, F5,
+    KEY_F6, F6,
+    KEY_F7, F7,
+    KEY_F8, F8,
+    KEY_F9, F9,
+    KEY_F10, F10,
+    KEY_NUMLOCK, NumLock,
+    KEY_SCROLLLOCK, ScrollLock,
+    KEY_KP7, Kp7,
+    KEY_KP8, Kp8,
+    KEY_KP9, Kp9,
+    KEY_KPMINUS, KpMinus,
+    KEY_KP4, Kp4,
+    KEY_KP5, Kp5,
+    KEY_KP6, Kp6,
+    KEY_KPPLUS, KpPlus,
+    KEY_KP1, Kp1,
+    KEY_KP2, Kp2,
+    KEY_KP3, Kp3,
+    KEY_KP0, Kp0,
+    KEY_F11, F11,
+    KEY_F12, F12,
+    KEY_KPENTER, KpReturn,
+    KEY_RIGHTCTRL, ControlRight,
+    KEY_KPSLASH, KpDivide,
+    KEY_RIGHTALT, AltGr,
+    KEY_HOME , Home,
+    KEY_UP, UpArrow,
+    KEY_PAGEUP, PageUp,
+    KEY_LEFT, LeftArrow,
+    KEY_RIGHT, RightArrow,
+    KEY_END, End,
+    KEY_DOWN, DownArrow,
+    KEY_PAGEDOWN, PageDown,
+    KEY_INSERT, Insert,
+    KEY_DELETE, Delete,
+    KEY_PAUSE, Pause,
+    KEY_LEFTMETA, MetaLeft,
+    KEY_RIGHTMETA, MetaRight,
+    KEY_PRINT, PrintScreen,
+    KEY_F13, F13,
+    KEY_F14, F14,
+    KEY_F15, F15,
+    KEY_F16, F16,
+    KEY_F17, F17,
+    KEY_F18, F18,
+    KEY_F19, F19,
+    KEY_F20, F20,
+    KEY_F21, F21,
+    KEY_F22, F22,
+    KEY_F23, F23,
+    KEY_F24, F24,
+    KEY_MUTE, VolumeMute,
+    KEY_VOLUMEDOWN, VolumeDown,
+    KEY_VOLUMEUP, VolumeUp,
+    KEY_FN, Function,
+    // KpDelete behaves like normal Delete most of the time
+    KEY_DELETE, KpDelete,
+    // Linux doesn't have an IntlBackslash key
+    KEY_BACKSLASH, IntlBackslash
+);
+
+/// The joystick and gamepad buttons, as opposed to keys and mouse buttons.
+fn is_gamepad_button(code: u32) -> bool {
+    // BTN_JOYSTICK to BTN_THUMBR, then BTN_TRIGGER_HAPPY1 to BTN_TRIGGER_HAPPY40
+    (0x120..=0x13e).contains(&code) || (0x2c0..=0x2e7).contains(&code)
+}
+
+macro_rules! convert_gamepad_buttons {
+    ($($ev_key:ident, $rdev_button:ident),*) => {
+        pub fn evdev_key_to_rdev_gamepad_button(key: &EV_KEY) -> Option<GamepadButton> {
+            match key {
+                $(
+                    EV_KEY::$ev_key => Some(GamepadButton::$rdev_button),
+                )*
+                key if is_gamepad_button(*key as u32) => Some(GamepadButton::Other(*key as u16)),
+                _ => None,
+            }
+        }
+
+        pub fn rdev_gamepad_button_to_evdev_key(button: &GamepadButton) -> Option<EV_KEY> {
+            match button {
+                $(
+                    GamepadButton::$rdev_button => Some(EV_KEY::$ev_key),
+                )*
+                GamepadButton::Other(code) => int_to_ev_key((*code).into())
+                    .filter(|key| is_gamepad_button(*key as u32)),
+            }
+        }
+    };
+}
+
+// Following the layout of the kernel's gamepad documentation
+#[rustfmt::skip]
+convert_gamepad_buttons!(
+    BTN_SOUTH, South,
+    BTN_EAST, East,
+    BTN_NORTH, North,
+    BTN_WEST, West,
+    BTN_TL, LeftBumper,
+    BTN_TR, RightBumper,
+    BTN_SELECT, Select,
+    BTN_START, Start,
+    BTN_MODE, Mode,
+    BTN_THUMBL, LeftThumb,
+    BTN_THUMBR, RightThumb,
+    BTN_DPAD_UP, DpadUp,
+    BTN_DPAD_DOWN, DpadDown,
+    BTN_DPAD_LEFT, DpadLeft,
+    BTN_DPAD_RIGHT, DpadRight
+);
+
+pub fn evdev_abs_to_rdev_gamepad_axis(axis: &EV_ABS) -> Option<GamepadAxis> {
+    match axis {
+        EV_ABS::ABS_X => Some(GamepadAxis::LeftStickX),
+        EV_ABS::ABS_Y => Some(GamepadAxis::LeftStickY),
+        EV_ABS::ABS_RX => Some(GamepadAxis::RightStickX),
+        EV_ABS::ABS_RY => Some(GamepadAxis::RightStickY),
+        EV_ABS::ABS_Z => Some(GamepadAxis::LeftTrigger),
+        EV_ABS::ABS_RZ => Some(GamepadAxis::RightTrigger),
+        _ => None,
+    }
+}
+
+pub fn rdev_gamepad_axis_to_evdev_abs(axis: &GamepadAxis) -> EV_ABS {
+    match axis {
+        GamepadAxis::LeftStickX => EV_ABS::ABS_X,
+        GamepadAxis::LeftStickY => EV_ABS::ABS_Y,
+        GamepadAxis::RightStickX => EV_ABS::ABS_RX,
+        GamepadAxis::RightStickY => EV_ABS::ABS_RY,
+        GamepadAxis::LeftTrigger => EV_ABS::ABS_Z,
+        GamepadAxis::RightTrigger => EV_ABS::ABS_RZ,
+    }
+}
@@ -0,0 +1,121 @@
+use crate::backend::ListenOptions;
+use crate::linux::common::Display;
+use crate::linux::evdev::{
+    add_device_to_epoll_from_inotify_event, display_size_or_default, epoll_watch_all,
+    evdev_event_to_rdev_event, get_device_files, setup_inotify, DEV_PATH, INOTIFY_DATA,
+};
+use crate::linux::gamepads::GamepadStates;
+use crate::linux::keyboard::Keyboard;
+use crate::linux::locks::LockStates;
+use crate::linux::touch::TouchStates;
+use crate::rdev::{Event, EventType, KeyboardState, ListenError};
+use epoll::ControlOptions::EPOLL_CTL_DEL;
+use evdev_rs::{Device, ReadFlag};
+use std::collections::HashSet;
+use std::fs::File;
+use std::io;
+use std::os::unix::io::AsRawFd;
+use std::panic::{self, AssertUnwindSafe};
+use std::time::SystemTime;
+
+/// Reads every device in /dev/input without grabbing them, so it only needs read
+/// access to the device nodes and no X display. Event names are only filled in
+/// when an X display is reachable, as the layout comes from the X server.
+pub fn listen<T>(options: ListenOptions, mut callback: T) -> Result<(), ListenError>
+where
+    T: FnMut(Event) + 'static,
+{
+    let mut keyboard = Keyboard::new();
+    let mut locks = LockStates::default();
+    let mut gamepads = GamepadStates::default();
+    let (width, height) = display_size_or_default();
+    let (current_x, current_y) = Display::new()
+        .and_then(|display| display.get_mouse_pos())
+        .unwrap_or((0, 0));
+    let mut x = current_x as f64;
+    let mut y = current_y as f64;
+    let w = width as f64;
+    let h = height as f64;
+    let mut touches = TouchStates::new(w, h, options.gestures);
+
+    let (mut device_ids, device_files): (Vec<u32>, Vec<File>) =
+        get_device_files(DEV_PATH)?.into_iter().unzip();
+    let epoll = epoll_watch_all(device_files.iter())?;
+    let epoll_fd = epoll.as_raw_fd();
+    let mut devices = device_files
+        .into_iter()
+        .map(Device::new_from_file)
+        .collect::<io::Result<Vec<Device>>>()?;
+    let mut inotify = setup_inotify(epoll_fd, &devices)?;
+    let mut pending = HashSet::new();
+
+    let mut epoll_buffer = [epoll::Event::new(epoll::Events::empty(), 0); 4];
+    let mut inotify_buffer = vec![0_u8; 4096];
+    loop {
+        let num_events = epoll::wait(epoll_fd, -1, &mut epoll_buffer)?;
+        'events: for event in &epoll_buffer[0..num_events] {
+            if event.data == INOTIFY_DATA {
+                for event in inotify.read_events(&mut inotify_buffer)? {
+                    // The node may not be readable yet or not be an evdev device at all,
+                    // neither is a reason to stop listening to the others.
+                    if let Ok(Some(device_id)) = add_device_to_epoll_from_inotify_event(
+                        epoll_fd,
+                        event,
+                        &mut devices,
+                        &mut pending,
+                    ) {
+                        device_ids.push(device_id);
+                    }
+                }
+                continue;
+            }
+            let device = match devices.get(event.data as usize) {
+                Some(device) => device,
+                None => continue,
+            };
+            while device.has_event_pending() {
+                let (_, input_event) = match device.next_event(ReadFlag::NORMAL) {
+                    Ok(event) => event,
+                    Err(_) => {
+                        // Device was unplugged
+                        let empty_event = epoll::Event::new(epoll::Events::empty(), 0);
+                        epoll::ctl(
+                            epoll_fd,
+                            EPOLL_CTL_DEL,
+                            device.file().as_raw_fd(),
+                            empty_event,
+                        )?;
+                        continue 'events;
+                    }
+                };
+                let device_idx = event.data as usize;
+                let event_types = gamepads
+                    .convert(&input_event, device, device_idx)
+                    .or_else(|| touches.convert(&input_event, device, device_idx))
+                    .unwrap_or_else(|| {
+                        evdev_event_to_rdev_event(&input_event, device, &mut x, &mut y, w, h)
+                            .and_then(|event_type| match event_type {
+                                // Once, not once per keyboard
+                                EventType::LockChange { lock, on } => locks.update(lock, on),
+                                event_type => Some(event_type),
+                            })
+                            .into_iter()
+                            .collect()
+                    });
+                for event_type in event_types {
+                    let name = keyboard
+                        .as_mut()
+                        .and_then(|keyboard| keyboard.add(&event_type));
+                    let event = Event {
+                        time: SystemTime::now(),
+                        name,
+                        event_type,
+                        device_id: Some(device_ids[device_idx]),
+                    };
+                    panic::catch_unwind(AssertUnwindSafe(|| callback(event)))
+                        .map_err(ListenError::CallbackPanic)?;
+                }
+            }
+        }
+    }
+}
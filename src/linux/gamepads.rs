@@ -0,0 +1,174 @@
+use crate::linux::evdev_keycodes::{
+    evdev_abs_to_rdev_gamepad_axis, evdev_key_to_rdev_gamepad_button,
+};
+use crate::rdev::{EventType, GamepadAxis, GamepadButton};
+use evdev_rs::enums::{EventCode, EV_ABS, EV_KEY};
+use evdev_rs::{AbsInfo, Device, DeviceWrapper, InputEvent};
+use std::collections::HashMap;
+
+/// Gamepads have the South button, joysticks a trigger.
+pub fn is_gamepad(device: &Device) -> bool {
+    device.has(EventCode::EV_KEY(EV_KEY::BTN_SOUTH))
+        || device.has(EventCode::EV_KEY(EV_KEY::BTN_TRIGGER))
+}
+
+/// Turns the events of gamepads and joysticks into the standard layout of
+/// `GamepadButton` and `GamepadAxis`. Hats are reported as d-pad buttons, which
+/// needs their previous position to tell which button a centered hat releases.
+#[derive(Debug, Default)]
+pub struct GamepadStates {
+    /// The last direction of each hat axis, by device index.
+    hats: HashMap<(usize, EV_ABS), i32>,
+}
+
+impl GamepadStates {
+    /// The events of `device` in the standard layout, possibly none for a hat
+    /// that did not change direction. `None` when the device is not a gamepad or
+    /// the event is outside the layout, which is then converted as usual.
+    pub fn convert(
+        &mut self,
+        event: &InputEvent,
+        device: &Device,
+        device_idx: usize,
+    ) -> Option<Vec<EventType>> {
+        if !is_gamepad(device) {
+            return None;
+        }
+        match &event.event_code {
+            EventCode::EV_KEY(key) => {
+                let button = evdev_key_to_rdev_gamepad_button(key)?;
+                Some(vec![match event.value {
+                    0 => EventType::GamepadButtonRelease(button),
+                    _ => EventType::GamepadButtonPress(button),
+                }])
+            }
+            EventCode::EV_ABS(axis @ (EV_ABS::ABS_HAT0X | EV_ABS::ABS_HAT0Y)) => {
+                let direction = event.value.signum();
+                let previous = self
+                    .hats
+                    .insert((device_idx, *axis), direction)
+                    .unwrap_or(0);
+                Some(hat_events(*axis, previous, direction))
+            }
+            EventCode::EV_ABS(axis) => {
+                let gamepad_axis = standard_axis(device, axis)?;
+                let info = device.abs_info(&event.event_code)?;
+                Some(vec![EventType::GamepadAxis {
+                    axis: gamepad_axis,
+                    value: normalize(gamepad_axis, event.value, &info),
+                }])
+            }
+            _ => None,
+        }
+    }
+}
+
+/// Where the axis is in the standard layout. Joysticks only have a stick there,
+/// the rest of their axes are throttles, rudders and such.
+fn standard_axis(device: &Device, axis: &EV_ABS) -> Option<GamepadAxis> {
+    if !device.has(EventCode::EV_KEY(EV_KEY::BTN_SOUTH)) {
+        return match axis {
+            EV_ABS::ABS_X | EV_ABS::ABS_Y => evdev_abs_to_rdev_gamepad_axis(axis),
+            _ => None,
+        };
+    }
+    // Most generic USB gamepads have their right stick there, and no triggers
+    let z_stick = !device.has(EventCode::EV_ABS(EV_ABS::ABS_RX));
+    match axis {
+        EV_ABS::ABS_Z if z_stick => Some(GamepadAxis::RightStickX),
+        EV_ABS::ABS_RZ if z_stick => Some(GamepadAxis::RightStickY),
+        // Xbox controllers over Bluetooth have their triggers there
+        EV_ABS::ABS_BRAKE => Some(GamepadAxis::LeftTrigger),
+        EV_ABS::ABS_GAS => Some(GamepadAxis::RightTrigger),
+        axis => evdev_abs_to_rdev_gamepad_axis(axis),
+    }
+}
+
+/// Maps the range of the axis onto the one of `GamepadAxis::min`, sticks in the
+/// flat zone the device advertises being centered.
+fn normalize(axis: GamepadAxis, value: i32, info: &AbsInfo) -> f64 {
+    if info.minimum >= info.maximum {
+        return 0.0;
+    }
+    let range = f64::from(info.maximum) - f64::from(info.minimum);
+    let ratio = (f64::from(value) - f64::from(info.minimum)) / range;
+    let position = axis.min() + ratio.clamp(0.0, 1.0) * (1.0 - axis.min());
+    let flat = f64::from(info.flat) / range * (1.0 - axis.min());
+    if axis.min() < 0.0 && position.abs() <= flat {
+        0.0
+    } else {
+        position
+    }
+}
+
+fn hat_events(axis: EV_ABS, previous: i32, direction: i32) -> Vec<EventType> {
+    let button = |direction| match (axis, direction) {
+        (EV_ABS::ABS_HAT0X, -1) => GamepadButton::DpadLeft,
+        (EV_ABS::ABS_HAT0X, _) => GamepadButton::DpadRight,
+        (_, -1) => GamepadButton::DpadUp,
+        _ => GamepadButton::DpadDown,
+    };
+    let mut events = vec![];
+    if previous == direction {
+        return events;
+    }
+    if previous != 0 {
+        events.push(EventType::GamepadButtonRelease(button(previous)));
+    }
+    if direction != 0 {
+        events.push(EventType::GamepadButtonPress(button(direction)));
+    }
+    events
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn info(minimum: i32, maximum: i32, flat: i32) -> AbsInfo {
+        AbsInfo {
+            value: 0,
+            minimum,
+            maximum,
+            fuzz: 0,
+            flat,
+            resolution: 0,
+        }
+    }
+
+    #[test]
+    fn test_normalize() {
+        let stick = info(-32768, 32767, 128);
+        assert_eq!(normalize(GamepadAxis::LeftStickX, -32768, &stick), -1.0);
+        assert_eq!(normalize(GamepadAxis::LeftStickX, 32767, &stick), 1.0);
+        assert_eq!(normalize(GamepadAxis::LeftStickX, 100, &stick), 0.0);
+        let trigger = info(0, 1023, 0);
+        assert_eq!(normalize(GamepadAxis::LeftTrigger, 0, &trigger), 0.0);
+        assert_eq!(normalize(GamepadAxis::LeftTrigger, 1023, &trigger), 1.0);
+        // Generic pads center their sticks on 128
+        let stick = info(0, 255, 15);
+        assert_eq!(normalize(GamepadAxis::RightStickY, 128, &stick), 0.0);
+        assert_eq!(normalize(GamepadAxis::RightStickY, 0, &stick), -1.0);
+    }
+
+    #[test]
+    fn test_hat_events() {
+        use EventType::{GamepadButtonPress as Press, GamepadButtonRelease as Release};
+        assert_eq!(
+            hat_events(EV_ABS::ABS_HAT0X, 0, -1),
+            vec![Press(GamepadButton::DpadLeft)]
+        );
+        assert_eq!(
+            hat_events(EV_ABS::ABS_HAT0X, -1, 1),
+            vec![
+                Release(GamepadButton::DpadLeft),
+                Press(GamepadButton::DpadRight)
+            ]
+        );
+        assert_eq!(
+            hat_events(EV_ABS::ABS_HAT0Y, 1, 0),
+            vec![Release(GamepadButton::DpadDown)]
+        );
+        assert_eq!(hat_events(EV_ABS::ABS_HAT0Y, 1, 1), vec![]);
+    }
+}
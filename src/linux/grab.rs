@@ -1,370 +1,496 @@
+use crate::backend::{GrabOptions, GrabOutput};
+use crate::gamepad::{GamepadChange, GamepadEmulator};
+use crate::hotkey::{left_side, KeyCombo};
 use crate::linux::common::Display;
+use crate::linux::evdev::{
+    add_device_to_epoll_from_inotify_event, display_size_or_default, epoll_watch_all,
+    evdev_event_to_rdev_event, get_device_nodes, setup_inotify, DEV_PATH, INOTIFY_DATA,
+};
+use crate::linux::evdev_keycodes::{rdev_button_to_evdev_key, rdev_key_to_evdev_key};
+use crate::linux::gamepads::GamepadStates;
 use crate::linux::keyboard::Keyboard;
-use crate::rdev::{Button, Event, EventType, GrabError, Key, KeyboardState};
-use epoll::ControlOptions::{EPOLL_CTL_ADD, EPOLL_CTL_DEL};
+use crate::linux::locks::LockStates;
+use crate::linux::touch::TouchStates;
+use crate::linux::virtual_device::VirtualDevice;
+use crate::processor::Pipeline;
+use crate::rdev::{DeviceIdentity, Event, EventType, GrabError, Key, KeyboardState};
+use epoll::ControlOptions::EPOLL_CTL_DEL;
+use evdev_rs::enums::{BusType, EventCode, EventType as EvdevType, EV_KEY, EV_REL, EV_SYN};
 use evdev_rs::{
-    enums::{EventCode, EV_KEY, EV_REL},
-    Device, InputEvent, UInputDevice,
+    Device, DeviceWrapper, EventCodeIterator, InputEvent, TimeVal, UInputDevice, UninitDevice,
 };
-use inotify::{Inotify, WatchMask};
-use std::ffi::{OsStr, OsString};
-use std::fs::{read_dir, File};
+use std::collections::HashSet;
+use std::fs::File;
 use std::io;
-use std::os::unix::{
-    ffi::OsStrExt,
-    fs::FileTypeExt,
-    io::{AsRawFd, IntoRawFd, RawFd},
-};
+use std::os::unix::io::{AsRawFd, IntoRawFd, OwnedFd, RawFd};
+use std::panic::{self, AssertUnwindSafe};
 use std::path::Path;
-use std::time::SystemTime;
-
-// TODO The x, y coordinates are currently wrong !! Is there mouse acceleration
-// to take into account ??
-
-macro_rules! convert_keys {
-    ($($ev_key:ident, $rdev_key:ident),*) => {
-        //TODO: make const when rust lang issue #49146 is fixed
-        #[allow(unreachable_patterns)]
-        fn evdev_key_to_rdev_key(key: &EV_KEY) -> Option<Key> {
-            match key {
-                $(
-                    EV_KEY::$ev_key => Some(Key::$rdev_key),
-                )*
-                _ => None,
-            }
-        }
+use std::sync::{Arc, Condvar, Mutex, MutexGuard};
+use std::thread;
+use std::time::{Duration, Instant, SystemTime};
+
+/// `_IOW('E', 0x90, int)`, from linux/input.h
+const EVIOCGRAB: libc::c_ulong = 0x4004_4590;
+/// `_IO('U', 2)`, from linux/uinput.h
+const UI_DEV_DESTROY: libc::c_ulong = 0x5502;
+/// Bytes in a bitmask of every key and button, up to `KEY_MAX`.
+const KEY_BYTES: usize = 0x300 / 8;
+/// `_IOR('E', 0x18, len)`, the keys and buttons held on a device, from linux/input.h
+const EVIOCGKEY: libc::c_ulong = 0x8000_4518 | ((KEY_BYTES as libc::c_ulong) << 16);
+/// How long grabbing waits for the keys held when it starts to be released.
+pub(crate) const RELEASE_TIMEOUT: Duration = Duration::from_secs(2);
+static GAMEPAD_NAME: &str = "rdev gamepad";
 
-        // //TODO: make const when rust lang issue #49146 is fixed
-        // fn rdev_key_to_evdev_key(key: &Key) -> Option<EV_KEY> {
-        //     match key {
-        //         $(
-        //             Key::$rdev_key => Some(EV_KEY::$ev_key),
-        //         )*
-        //         _ => None
-        //     }
-        // }
+pub fn grab<T>(pipeline: Pipeline, options: GrabOptions, callback: T) -> Result<(), GrabError>
+where
+    T: Fn(Event) -> Option<Event> + 'static,
+{
+    // Like listening, grabbing works without an X display, only without names
+    let keyboard = Keyboard::new();
+    let (width, height) = display_size_or_default();
+    let (current_x, current_y) = Display::new()
+        .and_then(|display| display.get_mouse_pos())
+        .unwrap_or((0, 0));
+    let gamepad = match &options.gamepad {
+        Some(mapping) => Some((
+            GamepadEmulator::new(mapping.clone()),
+            VirtualDevice::builder()
+                .name(GAMEPAD_NAME)
+                .gamepad()
+                .build()?,
+        )),
+        None => None,
+    };
+    let mut filter = GrabFilter {
+        keyboard,
+        x: current_x as f64,
+        y: current_y as f64,
+        w: width as f64,
+        h: height as f64,
+        pipeline,
+        callback,
+        escape: EscapeCombo::new(&options.escape_combo),
+        escaped: false,
+        down: HashSet::new(),
+        last_device: 0,
+        device_ids: Vec::new(),
+        locks: LockStates::default(),
+        gamepads: GamepadStates::default(),
+        touches: TouchStates::new(width as f64, height as f64, false),
+        gamepad,
     };
+    // Unwinding drops the devices, which releases them and the keys held on the
+    // virtual ones
+    panic::catch_unwind(AssertUnwindSafe(|| {
+        filter_map_events(&mut filter, &options)
+    }))
+    .map_err(GrabError::CallbackPanic)??;
+    if filter.escaped {
+        return Err(GrabError::EscapeCombo);
+    }
+    Ok(())
 }
 
-macro_rules! convert_buttons {
-    ($($ev_key:ident, $rdev_key:ident),*) => {
-        //TODO: make const when rust lang issue #49146 is fixed
-        fn evdev_key_to_rdev_button(key: &EV_KEY) -> Option<Button> {
-            match key {
-                $(
-                    EV_KEY::$ev_key => Some(Button::$rdev_key),
-                )*
-                _ => None,
+/// Watches physical keys for the combo ending the grab.
+pub(crate) struct EscapeCombo {
+    combo: KeyCombo,
+    held: HashSet<Key>,
+}
+
+impl EscapeCombo {
+    pub(crate) fn new(combo: &KeyCombo) -> EscapeCombo {
+        EscapeCombo {
+            combo: combo.sideless(),
+            held: HashSet::new(),
+        }
+    }
+
+    /// Whether this event completes the combo.
+    pub(crate) fn pressed(&mut self, event_type: &EventType) -> bool {
+        match *event_type {
+            EventType::KeyPress(key) => {
+                let key = left_side(key);
+                if key == self.combo.key
+                    && self
+                        .combo
+                        .modifiers
+                        .iter()
+                        .all(|modifier| self.held.contains(modifier))
+                {
+                    return true;
+                }
+                self.held.insert(key);
             }
+            EventType::KeyRelease(key) => {
+                self.held.remove(&left_side(key));
+            }
+            _ => {}
         }
+        false
+    }
+}
 
-        // //TODO: make const when rust lang issue #49146 is fixed
-        // fn rdev_button_to_evdev_key(event: &Button) -> Option<EV_KEY> {
-        //     match event {
-        //         $(
-        //             Button::$rdev_key => Some(EV_KEY::$ev_key),
-        //         )*
-        //         _ => None
-        //     }
-        // }
-    };
+/// Decides what `filter_map_events` writes to the virtual devices.
+pub trait EventFilter {
+    /// Called with each event read from `device`, at `device_idx`. The events
+    /// pushed to `output` are written to the clones of the devices they come with.
+    fn filter(
+        &mut self,
+        event: InputEvent,
+        device: &Device,
+        device_idx: usize,
+        output: &mut Vec<(usize, InputEvent)>,
+    ) -> GrabStatus;
+
+    /// When `timeout` should be called, if events are being held back.
+    fn deadline(&self) -> Option<Instant> {
+        None
+    }
+
+    /// Called once `deadline` is reached.
+    fn timeout(&mut self, _output: &mut Vec<(usize, InputEvent)>) {}
+
+    /// Whether the device at `path` belongs to the filter, which keeps it from
+    /// being grabbed.
+    fn owns(&self, _path: &Path) -> bool {
+        false
+    }
+
+    /// Called when `device` is opened at `device_idx`, before any of its events.
+    /// `device_id` is the number of its node.
+    fn device_added(&mut self, _device_idx: usize, _device_id: u32, _device: &Device) {}
 }
 
-#[rustfmt::skip]
-convert_buttons!(
-    BTN_LEFT, Left,
-    BTN_RIGHT, Right,
-    BTN_MIDDLE, Middle
-);
-
-//TODO: IntlBackslash, kpDelete
-#[rustfmt::skip]
-convert_keys!(
-    KEY_ESC, Escape,
-    KEY_1, Num1,
-    KEY_2, Num2,
-    KEY_3, Num3,
-    KEY_4, Num4,
-    KEY_5, Num5,
-    KEY_6, Num6,
-    KEY_7, Num7,
-    KEY_8, Num8,
-    KEY_9, Num9,
-    KEY_0, Num0,
-    KEY_MINUS, Minus,
-    KEY_EQUAL, Equal,
-    KEY_BACKSPACE, Backspace,
-    KEY_TAB, Tab,
-    KEY_Q, KeyQ,
-    KEY_W, KeyW,
-    KEY_E, KeyE,
-    KEY_R, KeyR,
-    KEY_T, KeyT,
-    KEY_Y, KeyY,
-    KEY_U, KeyU,
-    KEY_I, KeyI,
-    KEY_O, KeyO,
-    KEY_P, KeyP,
-    KEY_LEFTBRACE, LeftBracket,
-    KEY_RIGHTBRACE, RightBracket,
-    KEY_ENTER, Return,
-    KEY_LEFTCTRL, ControlLeft,
-    KEY_A, KeyA,
-    KEY_S, KeyS,
-    KEY_D, KeyD,
-    KEY_F, KeyF,
-    KEY_G, KeyG,
-    KEY_H, KeyH,
-    KEY_J, KeyJ,
-    KEY_K, KeyK,
-    KEY_L, KeyL,
-    KEY_SEMICOLON, SemiColon,
-    KEY_APOSTROPHE, Quote,
-    KEY_GRAVE, BackQuote,
-    KEY_LEFTSHIFT, ShiftLeft,
-    KEY_BACKSLASH, BackSlash,
-    KEY_Z, KeyZ,
-    KEY_X, KeyX,
-    KEY_C, KeyC,
-    KEY_V, KeyV,
-    KEY_B, KeyB,
-    KEY_N, KeyN,
-    KEY_M, KeyM,
-    KEY_COMMA, Comma,
-    KEY_DOT, Dot,
-    KEY_SLASH, Slash,
-    KEY_RIGHTSHIFT, ShiftRight,
-    KEY_KPASTERISK , KpMultiply,
-    KEY_LEFTALT, Alt,
-    KEY_SPACE, Space,
-    KEY_CAPSLOCK, CapsLock,
-    KEY_F1, F1,
-    KEY_F2, F2,
-    KEY_F3, F3,
-    KEY_F4, F4,
-    KEY_F5, F5,
-    KEY_F6, F6,
-    KEY_F7, F7,
-    KEY_F8, F8,
-    KEY_F9, F9,
-    KEY_F10, F10,
-    KEY_NUMLOCK, NumLock,
-    KEY_SCROLLLOCK, ScrollLock,
-    KEY_KP7, Kp7,
-    KEY_KP8, Kp8,
-    KEY_KP9, Kp9,
-    KEY_KPMINUS, KpMinus,
-    KEY_KP4, Kp4,
-    KEY_KP5, Kp5,
-    KEY_KP6, Kp6,
-    KEY_KPPLUS, KpPlus,
-    KEY_KP1, Kp1,
-    KEY_KP2, Kp2,
-    KEY_KP3, Kp3,
-    KEY_KP0, Kp0,
-    KEY_F11, F11,
-    KEY_F12, F12,
-    KEY_KPENTER, KpReturn,
-    KEY_RIGHTCTRL, ControlRight,
-    KEY_KPSLASH, KpDivide,
-    KEY_RIGHTALT, AltGr,
-    KEY_HOME , Home,
-    KEY_UP, UpArrow,
-    KEY_PAGEUP, PageUp,
-    KEY_LEFT, LeftArrow,
-    KEY_RIGHT, RightArrow,
-    KEY_END, End,
-    KEY_DOWN, DownArrow,
-    KEY_PAGEDOWN, PageDown,
-    KEY_INSERT, Insert,
-    KEY_DELETE, Delete,
-    KEY_PAUSE, Pause,
-    KEY_LEFTMETA, MetaLeft,
-    KEY_RIGHTMETA, MetaRight,
-    KEY_PRINT, PrintScreen,
-    // KpDelete behaves like normal Delete most of the time
-    KEY_DELETE, KpDelete,
-    // Linux doesn't have an IntlBackslash key
-    KEY_BACKSLASH, IntlBackslash
-);
-
-fn evdev_event_to_rdev_event(
-    event: &InputEvent,
-    x: &mut f64,
-    y: &mut f64,
+struct GrabFilter<T> {
+    /// Names the events when there is an X display.
+    keyboard: Option<Keyboard>,
+    x: f64,
+    y: f64,
     w: f64,
     h: f64,
-) -> Option<EventType> {
-    match &event.event_code {
-        EventCode::EV_KEY(key) => {
-            if let Some(button) = evdev_key_to_rdev_button(key) {
-                // first check if pressed key is a mouse button
-                match event.value {
-                    0 => Some(EventType::ButtonRelease(button)),
-                    _ => Some(EventType::ButtonPress(button)),
-                }
-            } else if let Some(key) = evdev_key_to_rdev_key(key) {
-                // check if pressed key is a keyboard key
-                match event.value {
-                    0 => Some(EventType::KeyRelease(key)),
-                    _ => Some(EventType::KeyPress(key)),
-                }
-            } else {
-                // if neither mouse button nor keyboard key, return none
-                None
-            }
-        }
-        EventCode::EV_REL(mouse) => match mouse {
-            EV_REL::REL_X => {
-                let dx = event.value as f64;
-                *x += dx;
-                if *x < 0.0 {
-                    *x = 0.0;
-                }
-                if *x > w {
-                    *x = w;
-                }
-                Some(EventType::MouseMove { x: *x, y: *y })
+    pipeline: Pipeline,
+    callback: T,
+    escape: EscapeCombo,
+    /// Whether the grab ended because of the escape combo.
+    escaped: bool,
+    /// Keys and buttons down on each virtual device, to tell presses from repeats.
+    down: HashSet<(usize, EV_KEY)>,
+    /// Where events made up by processors go.
+    last_device: usize,
+    /// The `Event.device_id` of each device, by index.
+    device_ids: Vec<u32>,
+    locks: LockStates,
+    gamepads: GamepadStates,
+    touches: TouchStates,
+    gamepad: Option<(GamepadEmulator, VirtualDevice)>,
+}
+
+impl<T> GrabFilter<T>
+where
+    T: Fn(Event) -> Option<Event>,
+{
+    fn allowed(&mut self, mut event: Event) -> bool {
+        event.name = self
+            .keyboard
+            .as_mut()
+            .and_then(|keyboard| keyboard.add(&event.event_type));
+        (self.callback)(event).is_some()
+    }
+
+    /// Writes the events coming out of the processors, each one to the device
+    /// it comes from, in its own frame since it may have been held back.
+    fn send(&mut self, events: Vec<Event>, output: &mut Vec<(usize, InputEvent)>) {
+        for event in events {
+            let device_idx = event
+                .device_id
+                .and_then(|device_id| self.device_ids.iter().position(|id| *id == device_id))
+                .unwrap_or(self.last_device);
+            let (key, pressed) = match event.event_type {
+                EventType::KeyPress(key) => (rdev_key_to_evdev_key(&key), true),
+                EventType::KeyRelease(key) => (rdev_key_to_evdev_key(&key), false),
+                EventType::ButtonPress(button) => (rdev_button_to_evdev_key(&button), true),
+                EventType::ButtonRelease(button) => (rdev_button_to_evdev_key(&button), false),
+                _ => continue,
+            };
+            if !self.allowed(event) {
+                continue;
             }
-            EV_REL::REL_Y => {
-                let dy = event.value as f64;
-                *y += dy;
-                if *y < 0.0 {
-                    *y = 0.0;
-                }
-                if *y > h {
-                    *y = h;
+            let key = match key {
+                Some(key) => key,
+                None => continue,
+            };
+            let value = match (pressed, self.down.insert((device_idx, key))) {
+                (true, true) => 1,
+                // Key repeat
+                (true, false) => 2,
+                (false, _) => {
+                    self.down.remove(&(device_idx, key));
+                    0
                 }
-                Some(EventType::MouseMove { x: *x, y: *y })
+            };
+            let time = TimeVal::new(0, 0);
+            output.push((
+                device_idx,
+                InputEvent::new(&time, &EventCode::EV_KEY(key), value),
+            ));
+            output.push((
+                device_idx,
+                InputEvent::new(&time, &EventCode::EV_SYN(EV_SYN::SYN_REPORT), 0),
+            ));
+        }
+    }
+
+    /// Hands the event to the gamepad, returns whether it took it.
+    fn drive_gamepad(&mut self, event_type: &EventType) -> bool {
+        let (emulator, device) = match &mut self.gamepad {
+            Some(gamepad) => gamepad,
+            None => return false,
+        };
+        match emulator.handle(event_type, Instant::now()) {
+            Some(changes) => {
+                write_gamepad(device, &changes);
+                true
             }
-            EV_REL::REL_HWHEEL => Some(EventType::Wheel {
-                delta_x: event.value.into(),
-                delta_y: 0,
-            }),
-            EV_REL::REL_WHEEL => Some(EventType::Wheel {
-                delta_x: 0,
-                delta_y: event.value.into(),
-            }),
-            // Other EV_REL events cannot be represented by rdev
-            _ => None,
-        },
-        // Other event_codes cannot be represented by rdev,
-        // and some never will e.g. EV_SYN
-        _ => None,
+            None => false,
+        }
     }
 }
 
-// fn rdev_event_to_evdev_event(event: &EventType, time: &TimeVal) -> Option<InputEvent> {
-//     match event {
-//         EventType::KeyPress(key) => {
-//             let key = rdev_key_to_evdev_key(&key)?;
-//             Some(InputEvent::new(&time, &EventCode::EV_KEY(key), 1))
-//         }
-//         EventType::KeyRelease(key) => {
-//             let key = rdev_key_to_evdev_key(&key)?;
-//             Some(InputEvent::new(&time, &EventCode::EV_KEY(key), 0))
-//         }
-//         EventType::ButtonPress(button) => {
-//             let button = rdev_button_to_evdev_key(&button)?;
-//             Some(InputEvent::new(&time, &EventCode::EV_KEY(button), 1))
-//         }
-//         EventType::ButtonRelease(button) => {
-//             let button = rdev_button_to_evdev_key(&button)?;
-//             Some(InputEvent::new(&time, &EventCode::EV_KEY(button), 0))
-//         }
-//         EventType::MouseMove { x, y } => {
-//             let (x, y) = (*x as i32, *y as i32);
-//             //TODO allow both x and y movements simultaneously
-//             if x != 0 {
-//                 Some(InputEvent::new(&time, &EventCode::EV_REL(EV_REL::REL_X), x))
-//             } else {
-//                 Some(InputEvent::new(&time, &EventCode::EV_REL(EV_REL::REL_Y), y))
-//             }
-//         }
-//         EventType::Wheel { delta_x, delta_y } => {
-//             let (x, y) = (*delta_x as i32, *delta_y as i32);
-//             //TODO allow both x and y movements simultaneously
-//             if x != 0 {
-//                 Some(InputEvent::new(
-//                     &time,
-//                     &EventCode::EV_REL(EV_REL::REL_HWHEEL),
-//                     x,
-//                 ))
-//             } else {
-//                 Some(InputEvent::new(
-//                     &time,
-//                     &EventCode::EV_REL(EV_REL::REL_WHEEL),
-//                     y,
-//                 ))
-//             }
-//         }
-//     }
-// }
-
-pub fn grab<T>(callback: T) -> Result<(), GrabError>
+/// What `DeviceConfig` rules match on, see `DeviceInfo::identity`.
+fn device_identity(device: &Device) -> DeviceIdentity {
+    DeviceIdentity {
+        name: device.name().unwrap_or_default().to_string(),
+        vendor: device.vendor_id(),
+        product: device.product_id(),
+        phys: device.phys().map(str::to_string),
+    }
+}
+
+/// With the kernel timestamp, which processors like `Debounce` rely on.
+fn kernel_event(event: &InputEvent, event_type: EventType, device_id: u32) -> Event {
+    let time: Result<SystemTime, ()> = event.time.try_into();
+    Event {
+        time: time.unwrap_or_else(|_| SystemTime::now()),
+        name: None,
+        event_type,
+        device_id: Some(device_id),
+    }
+}
+
+/// A gamepad that cannot be written to anymore is not worth ending the grab,
+/// so errors are ignored.
+fn write_gamepad(device: &VirtualDevice, changes: &[GamepadChange]) {
+    for change in changes {
+        let _ = match *change {
+            GamepadChange::Button(button, pressed) => device.set_gamepad_button(button, pressed),
+            GamepadChange::Axis(axis, position) => device.set_gamepad_axis(axis, position),
+        };
+    }
+}
+
+impl<T> EventFilter for GrabFilter<T>
 where
-    T: Fn(Event) -> Option<Event> + 'static,
+    T: Fn(Event) -> Option<Event>,
 {
-    let mut kb = Keyboard::new().ok_or(GrabError::KeyboardError)?;
-    let display = Display::new().ok_or(GrabError::MissingDisplayError)?;
-    let (width, height) = display.get_size().ok_or(GrabError::MissingDisplayError)?;
-    let (current_x, current_y) = display
-        .get_mouse_pos()
-        .ok_or(GrabError::MissingDisplayError)?;
-    let mut x = current_x as f64;
-    let mut y = current_y as f64;
-    let w = width as f64;
-    let h = height as f64;
-    filter_map_events(|event| {
-        let event_type = match evdev_event_to_rdev_event(&event, &mut x, &mut y, w, h) {
+    fn filter(
+        &mut self,
+        event: InputEvent,
+        device: &Device,
+        device_idx: usize,
+        output: &mut Vec<(usize, InputEvent)>,
+    ) -> GrabStatus {
+        // Before the conversion, which turns movements into positions
+        let delta = match event.event_code {
+            EventCode::EV_REL(EV_REL::REL_X) => Some((event.value, 0)),
+            EventCode::EV_REL(EV_REL::REL_Y) => Some((0, event.value)),
+            _ => None,
+        };
+        if let Some((delta_x, delta_y)) = delta {
+            let moved = EventType::MouseMoveRelative {
+                delta_x: delta_x.into(),
+                delta_y: delta_y.into(),
+            };
+            if self.drive_gamepad(&moved) {
+                return GrabStatus::Continue;
+            }
+        }
+        if let Some(event_types) = self.gamepads.convert(&event, device, device_idx) {
+            // Hats can turn into two events, both have to be let through
+            let mut allowed = true;
+            for event_type in event_types {
+                allowed &= self.allowed(kernel_event(
+                    &event,
+                    event_type,
+                    self.device_ids[device_idx],
+                ));
+            }
+            if allowed {
+                output.push((device_idx, event));
+            }
+            return GrabStatus::Continue;
+        }
+        if let Some(event_types) = self.touches.convert(&event, device, device_idx) {
+            // Fingers are only reported, a frame left half written would get
+            // them stuck
+            for event_type in event_types {
+                self.allowed(kernel_event(
+                    &event,
+                    event_type,
+                    self.device_ids[device_idx],
+                ));
+            }
+            output.push((device_idx, event));
+            return GrabStatus::Continue;
+        }
+        let event_type = match evdev_event_to_rdev_event(
+            &event,
+            device,
+            &mut self.x,
+            &mut self.y,
+            self.w,
+            self.h,
+        ) {
             Some(rdev_event) => rdev_event,
             // If we can't convert event, simulate it
-            None => return (Some(event), GrabStatus::Continue),
+            None => {
+                output.push((device_idx, event));
+                return GrabStatus::Continue;
+            }
         };
-        let name = kb.add(&event_type);
-        let rdev_event = Event {
-            time: SystemTime::now(),
-            name,
+        if let EventType::LockChange { lock, on } = event_type {
+            // Only the first keyboard reporting it goes to the callback
+            if self.locks.update(lock, on).is_none() {
+                output.push((device_idx, event));
+                return GrabStatus::Continue;
+            }
+        }
+        // Before anything that could swallow it or get stuck
+        if self.escape.pressed(&event_type) {
+            self.escaped = true;
+            return GrabStatus::Stop;
+        }
+        if self.drive_gamepad(&event_type) {
+            return GrabStatus::Continue;
+        }
+        let rdev_event = kernel_event(&event, event_type, self.device_ids[device_idx]);
+        let processed = matches!(
             event_type,
-        };
-        if callback(rdev_event).is_some() {
-            (Some(event), GrabStatus::Continue)
-        } else {
-            // callback returns None, swallow the event
-            (None, GrabStatus::Continue)
+            EventType::KeyPress(_)
+                | EventType::KeyRelease(_)
+                | EventType::ButtonPress(_)
+                | EventType::ButtonRelease(_)
+        );
+        if processed && !self.pipeline.is_empty() {
+            self.last_device = device_idx;
+            let events = self.pipeline.process(rdev_event, Instant::now());
+            self.send(events, output);
+        } else if self.allowed(rdev_event) {
+            output.push((device_idx, event));
         }
-    })?;
-    Ok(())
+        GrabStatus::Continue
+    }
+
+    fn deadline(&self) -> Option<Instant> {
+        let gamepad = self
+            .gamepad
+            .as_ref()
+            .and_then(|(emulator, _)| emulator.deadline());
+        match (self.pipeline.deadline(), gamepad) {
+            (Some(pipeline), Some(gamepad)) => Some(pipeline.min(gamepad)),
+            (pipeline, gamepad) => pipeline.or(gamepad),
+        }
+    }
+
+    fn timeout(&mut self, output: &mut Vec<(usize, InputEvent)>) {
+        let now = Instant::now();
+        if let Some((emulator, device)) = &mut self.gamepad {
+            if emulator.deadline().is_some_and(|deadline| deadline <= now) {
+                write_gamepad(device, &emulator.timeout(now));
+            }
+        }
+        if self
+            .pipeline
+            .deadline()
+            .is_some_and(|deadline| deadline <= now)
+        {
+            let events = self.pipeline.timeout(now);
+            self.send(events, output);
+        }
+    }
+
+    fn owns(&self, path: &Path) -> bool {
+        self.gamepad
+            .as_ref()
+            .and_then(|(_, device)| device.devnode())
+            .is_some_and(|devnode| Path::new(devnode) == path)
+    }
+
+    fn device_added(&mut self, device_idx: usize, device_id: u32, device: &Device) {
+        debug_assert_eq!(device_idx, self.device_ids.len());
+        self.device_ids.push(device_id);
+        self.pipeline
+            .device_added(device_id, &device_identity(device));
+    }
 }
 
-pub fn filter_map_events<F>(mut func: F) -> io::Result<()>
+/// Runs `filter` on the events of every device, grabbing them until it returns
+/// `GrabStatus::Stop`, and writes what it outputs to the virtual devices picked
+/// by `options.output`. With a watchdog timeout, the devices are released from
+/// another thread if the filter takes longer than that.
+pub fn filter_map_events<F>(filter: &mut F, options: &GrabOptions) -> Result<(), GrabError>
 where
-    F: FnMut(InputEvent) -> (Option<InputEvent>, GrabStatus),
+    F: EventFilter,
 {
-    let (epoll_fd, mut devices, output_devices) = setup_devices()?;
+    let (epoll, mut devices, device_ids, mut output_devices) =
+        setup_devices(&options.output, |path| filter.owns(path))?;
+    let epoll_fd = epoll.as_raw_fd();
     let mut inotify = setup_inotify(epoll_fd, &devices)?;
+    let mut pending = HashSet::new();
+    for (device_idx, device) in devices.iter().enumerate() {
+        filter.device_added(device_idx, device_ids[device_idx], device);
+    }
 
+    wait_for_release(&devices);
     //grab devices
     devices
         .iter_mut()
         .try_for_each(|device| device.grab(evdev_rs::GrabMode::Grab))?;
+    let watchdog = options
+        .watchdog
+        .map(|timeout| Watchdog::spawn(timeout, &devices, &output_devices.devices));
 
     // create buffer for epoll to fill
     let mut epoll_buffer = [epoll::Event::new(epoll::Events::empty(), 0); 4];
     let mut inotify_buffer = vec![0_u8; 4096];
+    let mut output = Vec::new();
     'event_loop: loop {
-        let num_events = epoll::wait(epoll_fd, -1, &mut epoll_buffer)?;
+        let num_events = epoll::wait(epoll_fd, wait_timeout(&*filter), &mut epoll_buffer)?;
+
+        if filter
+            .deadline()
+            .is_some_and(|deadline| deadline <= Instant::now())
+        {
+            watched(&watchdog, || filter.timeout(&mut output))?;
+            output_devices.write(&mut output)?;
+        }
 
         //map and simulate events, dealing with
         'events: for event in &epoll_buffer[0..num_events] {
             // new device file created
             if event.data == INOTIFY_DATA {
                 for event in inotify.read_events(&mut inotify_buffer)? {
-                    assert!(
-                        event.mask.contains(inotify::EventMask::CREATE),
-                        "inotify is listening for events other than file creation"
+                    // A node that cannot be opened is skipped, like when listening,
+                    // the devices already grabbed matter more.
+                    let added = add_device_to_epoll_from_inotify_event(
+                        epoll_fd,
+                        event,
+                        &mut devices,
+                        &mut pending,
                     );
-                    add_device_to_epoll_from_inotify_event(epoll_fd, event, &mut devices)?;
+                    if let Ok(Some(device_id)) = added {
+                        let device_idx = devices.len() - 1;
+                        filter.device_added(device_idx, device_id, &devices[device_idx]);
+                    }
                 }
             } else {
                 // Input device recieved event
@@ -381,12 +507,10 @@ where
                             continue 'events;
                         }
                     };
-                    let (event, grab_status) = func(event);
-
-                    if let (Some(event), Some(out_device)) = (event, output_devices.get(device_idx))
-                    {
-                        out_device.write_event(&event)?;
-                    }
+                    let grab_status = watched(&watchdog, || {
+                        filter.filter(event, device, device_idx, &mut output)
+                    })?;
+                    output_devices.write(&mut output)?;
                     if grab_status == GrabStatus::Stop {
                         break 'event_loop;
                     }
@@ -395,135 +519,301 @@ where
         }
     }
 
+    drop(watchdog);
+    drop(output_devices);
     for device in devices.iter_mut() {
         //ungrab devices, ignore errors
         device.grab(evdev_rs::GrabMode::Ungrab).ok();
     }
-
-    epoll::close(epoll_fd)?;
     Ok(())
 }
 
-static DEV_PATH: &str = "/dev/input";
-const INOTIFY_DATA: u64 = u64::max_value();
-const EPOLLIN: epoll::Events = epoll::Events::EPOLLIN;
+#[derive(Debug, Default)]
+struct WatchdogState {
+    /// When the filter was called, if it did not return yet.
+    busy_since: Option<Instant>,
+    fired: bool,
+    /// Set once the grab is over, the devices may be closed already.
+    done: bool,
+}
 
-/// Whether to continue grabbing events or to stop
-/// Used in `filter_map_events` (and others)
-#[derive(Debug, Eq, PartialEq, Hash)]
-pub enum GrabStatus {
-    /// Stop grabbing
-    Continue,
-    /// ungrab events
-    Stop,
+/// Releases the devices from its own thread when the filter does not return in
+/// time, as the grab loop is then stuck in it with every device grabbed.
+struct Watchdog {
+    shared: Arc<(Mutex<WatchdogState>, Condvar)>,
 }
 
-fn get_device_files<T>(path: T) -> io::Result<Vec<File>>
+impl Watchdog {
+    /// Keeps the raw file descriptors, so they have to stay open until the
+    /// watchdog is dropped.
+    fn spawn(timeout: Duration, devices: &[Device], output_devices: &[UInputDevice]) -> Watchdog {
+        let shared = Arc::new((Mutex::new(WatchdogState::default()), Condvar::new()));
+        let grabbed: Vec<RawFd> = devices
+            .iter()
+            .map(|device| device.file().as_raw_fd())
+            .collect();
+        let virtual_devices: Vec<RawFd> = output_devices
+            .iter()
+            .filter_map(UInputDevice::as_fd)
+            .collect();
+        let watched = shared.clone();
+        thread::spawn(move || {
+            let (state, condvar) = &*watched;
+            let mut state = lock(state);
+            loop {
+                if state.done {
+                    return;
+                }
+                let left = match state.busy_since {
+                    Some(since) => timeout.saturating_sub(since.elapsed()),
+                    None => {
+                        state = condvar
+                            .wait(state)
+                            .unwrap_or_else(|poisoned| poisoned.into_inner());
+                        continue;
+                    }
+                };
+                if left.is_zero() {
+                    break;
+                }
+                state = condvar
+                    .wait_timeout(state, left)
+                    .map(|(state, _)| state)
+                    .unwrap_or_else(|poisoned| poisoned.into_inner().0);
+            }
+            // Destroying the virtual devices releases the keys still down on them
+            for fd in grabbed {
+                unsafe { libc::ioctl(fd, EVIOCGRAB as _, 0) };
+            }
+            for fd in virtual_devices {
+                unsafe { libc::ioctl(fd, UI_DEV_DESTROY as _) };
+            }
+            state.fired = true;
+        });
+        Watchdog { shared }
+    }
+
+    fn watch<R>(&self, f: impl FnOnce() -> R) -> Result<R, GrabError> {
+        let (state, condvar) = &*self.shared;
+        lock(state).busy_since = Some(Instant::now());
+        condvar.notify_one();
+        let result = f();
+        let mut state = lock(state);
+        state.busy_since = None;
+        if state.fired {
+            return Err(GrabError::WatchdogTimeout);
+        }
+        Ok(result)
+    }
+}
+
+impl Drop for Watchdog {
+    fn drop(&mut self) {
+        let (state, condvar) = &*self.shared;
+        lock(state).done = true;
+        condvar.notify_one();
+    }
+}
+
+fn lock(state: &Mutex<WatchdogState>) -> MutexGuard<'_, WatchdogState> {
+    state
+        .lock()
+        .unwrap_or_else(|poisoned| poisoned.into_inner())
+}
+
+fn watched<R>(watchdog: &Option<Watchdog>, f: impl FnOnce() -> R) -> Result<R, GrabError> {
+    match watchdog {
+        Some(watchdog) => watchdog.watch(f),
+        None => Ok(f()),
+    }
+}
+
+/// Milliseconds until the filter's deadline, rounded up, -1 to wait forever.
+fn wait_timeout<F>(filter: &F) -> i32
 where
-    T: AsRef<Path>,
+    F: EventFilter,
 {
-    let mut res = Vec::new();
-    for entry in read_dir(path)? {
-        let entry = entry?;
-        // /dev/input files are character devices
-        if !entry.file_type()?.is_char_device() {
-            continue;
+    match filter.deadline() {
+        Some(deadline) => {
+            let left = deadline.saturating_duration_since(Instant::now());
+            left.as_micros().div_ceil(1000).min(i32::MAX as u128) as i32
+        }
+        None => -1,
+    }
+}
+
+/// The virtual devices, which release the keys and buttons still down on them
+/// when dropped, as applications would see them held forever otherwise.
+struct OutputDevices {
+    devices: Vec<UInputDevice>,
+    /// The virtual device of each grabbed device, by index in `devices`.
+    targets: Vec<usize>,
+    /// Keys and buttons down on each virtual device.
+    down: HashSet<(usize, EV_KEY)>,
+}
+
+impl OutputDevices {
+    fn clones(devices: &[Device]) -> io::Result<OutputDevices> {
+        Ok(OutputDevices {
+            devices: devices
+                .iter()
+                .map(UInputDevice::create_from_device)
+                .collect::<io::Result<Vec<UInputDevice>>>()?,
+            targets: (0..devices.len()).collect(),
+            down: HashSet::new(),
+        })
+    }
+
+    /// One device with the keys, buttons and relative axes of every device, the
+    /// ones with absolute axes get a clone.
+    fn merged(
+        devices: &[Device],
+        name: &str,
+        vendor: u16,
+        product: u16,
+    ) -> io::Result<OutputDevices> {
+        let merged =
+            UninitDevice::new().ok_or_else(|| io::Error::other("could not create device"))?;
+        merged.set_name(name);
+        merged.set_vendor_id(vendor);
+        merged.set_product_id(product);
+        merged.set_bustype(BusType::BUS_VIRTUAL as u16);
+        let mut output_devices = vec![];
+        let mut targets = vec![];
+        for device in devices {
+            if device.has_event_type(&EvdevType::EV_ABS) {
+                targets.push(Some(output_devices.len()));
+                output_devices.push(UInputDevice::create_from_device(device)?);
+                continue;
+            }
+            targets.push(None);
+            for event_type in [EvdevType::EV_KEY, EvdevType::EV_REL, EvdevType::EV_MSC] {
+                if !device.has_event_type(&event_type) {
+                    continue;
+                }
+                for code in EventCodeIterator::new(&event_type) {
+                    if device.has_event_code(&code) {
+                        merged.enable(code)?;
+                    }
+                }
+            }
         }
+        let merged_idx = output_devices.len();
+        output_devices.push(UInputDevice::create_from_device(&merged)?);
+        Ok(OutputDevices {
+            devices: output_devices,
+            targets: targets
+                .into_iter()
+                .map(|target| target.unwrap_or(merged_idx))
+                .collect(),
+            down: HashSet::new(),
+        })
+    }
 
-        let path = entry.path();
-        let file_name_bytes = match path.file_name() {
-            Some(file_name) => file_name.as_bytes(),
-            None => continue, // file_name was "..", should be impossible
-        };
-        // skip filenames matching "mouse.* or mice".
-        // these files don't play nice with libevdev, not sure why
-        // see: https://askubuntu.com/questions/1043832/difference-between-dev-input-mouse0-and-dev-input-mice
-        if file_name_bytes == OsStr::new("mice").as_bytes()
-            || file_name_bytes
-                .get(0..=1)
-                .map(|s| s == OsStr::new("js").as_bytes())
-                .unwrap_or(false)
-            || file_name_bytes
-                .get(0..=4)
-                .map(|s| s == OsStr::new("mouse").as_bytes())
-                .unwrap_or(false)
-        {
-            continue;
+    /// Writes and empties `output`, events of devices plugged in while grabbing are dropped.
+    fn write(&mut self, output: &mut Vec<(usize, InputEvent)>) -> io::Result<()> {
+        for (device_idx, event) in output.drain(..) {
+            let target = match self.targets.get(device_idx) {
+                Some(target) => *target,
+                None => continue,
+            };
+            if let EventCode::EV_KEY(key) = event.event_code {
+                if event.value == 0 {
+                    self.down.remove(&(target, key));
+                } else {
+                    self.down.insert((target, key));
+                }
+            }
+            self.devices[target].write_event(&event)?;
         }
-        res.push(File::open(path)?);
+        Ok(())
     }
-    Ok(res)
 }
 
-fn epoll_watch_all<'a, T>(device_files: T) -> io::Result<RawFd>
-where
-    T: Iterator<Item = &'a File>,
-{
-    let epoll_fd = epoll::create(true)?;
-    // add file descriptors to epoll
-    for (file_idx, file) in device_files.enumerate() {
-        let epoll_event = epoll::Event::new(EPOLLIN, file_idx as u64);
-        epoll::ctl(epoll_fd, EPOLL_CTL_ADD, file.as_raw_fd(), epoll_event)?;
+impl Drop for OutputDevices {
+    fn drop(&mut self) {
+        let time = TimeVal::new(0, 0);
+        for (device_idx, key) in self.down.drain() {
+            let out_device = &self.devices[device_idx];
+            // Errors are ignored, the watchdog may have destroyed the devices
+            out_device
+                .write_event(&InputEvent::new(&time, &EventCode::EV_KEY(key), 0))
+                .ok();
+            out_device
+                .write_event(&InputEvent::new(
+                    &time,
+                    &EventCode::EV_SYN(EV_SYN::SYN_REPORT),
+                    0,
+                ))
+                .ok();
+        }
+    }
+}
+
+/// Waits for the keys and buttons held when grabbing starts to be released, like
+/// Enter when launching from a terminal, for up to `RELEASE_TIMEOUT`. Applications
+/// saw them pressed on the real devices, grabbing would send their releases to the
+/// virtual ones instead and leave them held. The events queued meanwhile were
+/// already seen by applications, so they are dropped.
+fn wait_for_release(devices: &[Device]) {
+    let start = Instant::now();
+    while devices.iter().any(has_keys_down) && start.elapsed() < RELEASE_TIMEOUT {
+        thread::sleep(Duration::from_millis(10));
+    }
+    for device in devices {
+        while device.has_event_pending() {
+            if device.next_event(evdev_rs::ReadFlag::NORMAL).is_err() {
+                break;
+            }
+        }
     }
-    Ok(epoll_fd)
 }
 
-fn inotify_devices() -> io::Result<Inotify> {
-    let mut inotify = Inotify::init()?;
-    inotify.add_watch(DEV_PATH, WatchMask::CREATE)?;
-    Ok(inotify)
+/// Asks the kernel, as the events telling it may not have been read yet.
+fn has_keys_down(device: &Device) -> bool {
+    let mut keys = [0_u8; KEY_BYTES];
+    let fd = device.file().as_raw_fd();
+    let read = unsafe { libc::ioctl(fd, EVIOCGKEY as _, keys.as_mut_ptr()) };
+    read > 0 && keys.iter().any(|byte| *byte != 0)
 }
 
-fn add_device_to_epoll_from_inotify_event(
-    epoll_fd: RawFd,
-    event: inotify::Event<&OsStr>,
-    devices: &mut Vec<Device>,
-) -> io::Result<()> {
-    let mut device_path = OsString::from(DEV_PATH);
-    device_path.push(OsString::from("/"));
-    device_path.push(event.name.unwrap());
-    // new plug events
-    let file = File::open(device_path)?;
-    let fd = file.as_raw_fd();
-    let device = Device::new_from_fd(file)?;
-    let event = epoll::Event::new(EPOLLIN, devices.len() as u64);
-    devices.push(device);
-    epoll::ctl(epoll_fd, EPOLL_CTL_ADD, fd, event)?;
-    Ok(())
+/// Whether to continue grabbing events or to stop
+/// Used in `filter_map_events` (and others)
+#[derive(Debug, Eq, PartialEq, Hash)]
+pub enum GrabStatus {
+    /// Stop grabbing
+    Continue,
+    /// ungrab events
+    Stop,
 }
 
-/// Returns tuple of epoll_fd, all devices, and uinput devices, where
-/// uinputdevices is the same length as devices, and each uinput device is
-/// a libevdev copy of its corresponding device.The epoll_fd is level-triggered
-/// on any available data in the original devices.
-fn setup_devices() -> io::Result<(RawFd, Vec<Device>, Vec<UInputDevice>)> {
-    let device_files = get_device_files(DEV_PATH)?;
+/// Returns tuple of the epoll instance, all devices but the `owned` ones with their
+/// node numbers, and the uinput
+/// devices picked by `output`. The epoll instance is level-triggered on any available data in the
+/// original devices.
+fn setup_devices(
+    output: &GrabOutput,
+    owned: impl Fn(&Path) -> bool,
+) -> io::Result<(OwnedFd, Vec<Device>, Vec<u32>, OutputDevices)> {
+    let (device_ids, device_files): (Vec<u32>, Vec<File>) = get_device_nodes(DEV_PATH)?
+        .into_iter()
+        .filter(|(_, path)| !owned(path))
+        .map(|(device_id, path)| Ok((device_id, File::open(path)?)))
+        .collect::<io::Result<Vec<(u32, File)>>>()?
+        .into_iter()
+        .unzip();
     let epoll_fd = epoll_watch_all(device_files.iter())?;
     let devices = device_files
         .into_iter()
         .map(Device::new_from_fd)
         .collect::<io::Result<Vec<Device>>>()?;
-    let output_devices = devices
-        .iter()
-        .map(UInputDevice::create_from_device)
-        .collect::<io::Result<Vec<UInputDevice>>>()?;
-    Ok((epoll_fd, devices, output_devices))
-}
-
-/// Creates an inotify instance looking at /dev/input and adds it to an epoll instance.
-/// Ensures devices isnt too long, which would make the epoll data ambigious.
-fn setup_inotify(epoll_fd: RawFd, devices: &[Device]) -> io::Result<Inotify> {
-    //Ensure there is space for inotify at last epoll index.
-    if devices.len() as u64 >= INOTIFY_DATA {
-        eprintln!("number of devices: {}", devices.len());
-        return Err(io::Error::new(
-            io::ErrorKind::Other,
-            "too many device files!",
-        ));
-    }
-    // Set up inotify to listen for new devices being plugged in
-    let inotify = inotify_devices()?;
-    let epoll_event = epoll::Event::new(EPOLLIN, INOTIFY_DATA);
-    epoll::ctl(epoll_fd, EPOLL_CTL_ADD, inotify.as_raw_fd(), epoll_event)?;
-    Ok(inotify)
+    let output_devices = match output {
+        GrabOutput::Clones => OutputDevices::clones(&devices)?,
+        GrabOutput::Merged {
+            name,
+            vendor,
+            product,
+        } => OutputDevices::merged(&devices, name, *vendor, *product)?,
+    };
+    Ok((epoll_fd, devices, device_ids, output_devices))
 }
@@ -1,114 +1,289 @@
 extern crate libc;
 extern crate x11;
-use crate::linux::common::{convert, FALSE, KEYBOARD};
+use crate::linux::common::{convert_event, xinput2_opcode, FALSE};
 use crate::linux::keyboard::Keyboard;
-use crate::rdev::{Event, ListenError};
-use std::convert::TryInto;
-use std::ffi::CStr;
-use std::os::raw::{c_char, c_int, c_uchar, c_uint, c_ulong};
+use crate::linux::locks::{toggled_lock, x11_lock_state, LockStates, LOCKS};
+use crate::rdev::{Event, EventType, KeyboardState, ListenError};
+use std::collections::HashMap;
+use std::os::raw::{c_int, c_uchar};
+use std::panic::{self, AssertUnwindSafe};
 use std::ptr::null;
+use std::slice;
+use std::time::SystemTime;
+use x11::xinput2;
 use x11::xlib;
-use x11::xrecord;
 
-static mut RECORD_ALL_CLIENTS: c_ulong = xrecord::XRecordAllClients;
-static mut GLOBAL_CALLBACK: Option<Box<dyn FnMut(Event)>> = None;
+/// A valuator the server reports as a scroll axis for one source device,
+/// `increment` being the valuator distance of one wheel notch.
+#[derive(Debug, Copy, Clone)]
+struct ScrollValuator {
+    number: c_int,
+    vertical: bool,
+    increment: f64,
+}
+
+type ScrollValuators = HashMap<c_int, Vec<ScrollValuator>>;
 
-pub fn listen<T>(callback: T) -> Result<(), ListenError>
+/// Listens to XInput2 raw events on the root window. Raw events are delivered
+/// whatever window has focus or grabs the devices, and they carry the source
+/// (physical) device as well as scroll valuators.
+pub fn listen<T>(mut callback: T) -> Result<(), ListenError>
 where
     T: FnMut(Event) + 'static,
 {
-    let keyboard = Keyboard::new().ok_or(ListenError::KeyboardError)?;
-
     unsafe {
-        KEYBOARD = Some(keyboard);
-        GLOBAL_CALLBACK = Some(Box::new(callback));
-        // Open displays
-        let dpy_control = xlib::XOpenDisplay(null());
-        if dpy_control.is_null() {
+        let display = xlib::XOpenDisplay(null());
+        if display.is_null() {
             return Err(ListenError::MissingDisplayError);
         }
-        let extension_name = CStr::from_bytes_with_nul(b"RECORD\0")
-            .map_err(|_| ListenError::XRecordExtensionError)?;
-        let extension = xlib::XInitExtension(dpy_control, extension_name.as_ptr());
-        if extension.is_null() {
-            return Err(ListenError::XRecordExtensionError);
-        }
-
-        // Prepare record range
-        let mut record_range: xrecord::XRecordRange = *xrecord::XRecordAllocRange();
-        record_range.device_events.first = xlib::KeyPress as c_uchar;
-        record_range.device_events.last = xlib::MotionNotify as c_uchar;
-
-        // Create context
-        let context = xrecord::XRecordCreateContext(
-            dpy_control,
-            0,
-            &mut RECORD_ALL_CLIENTS,
-            1,
-            &mut &mut record_range as *mut &mut xrecord::XRecordRange
-                as *mut *mut xrecord::XRecordRange,
-            1,
-        );
+        // However listening fails, the connection is not left open
+        let result = listen_display(display, &mut callback);
+        xlib::XCloseDisplay(display);
+        result
+    }
+}
 
-        if context == 0 {
-            return Err(ListenError::RecordContextError);
+/// Runs until setting up fails or the callback panics.
+unsafe fn listen_display<T>(
+    display: *mut xlib::Display,
+    callback: &mut T,
+) -> Result<(), ListenError>
+where
+    T: FnMut(Event),
+{
+    let mut keyboard = Keyboard::new().ok_or(ListenError::KeyboardError)?;
+    let opcode = setup_xinput(display)?;
+    let root = xlib::XDefaultRootWindow(display);
+    let mut scroll_valuators = query_scroll_valuators(display);
+    let mut locks = LockStates::default();
+    for lock in LOCKS {
+        if let Some(on) = x11_lock_state(display, lock) {
+            locks.update(lock, on);
         }
+    }
 
-        xlib::XSync(dpy_control, FALSE);
-        // Run
-        let result =
-            xrecord::XRecordEnableContext(dpy_control, context, Some(record_callback), &mut 0);
-        if result == 0 {
-            return Err(ListenError::RecordContextEnablingError);
+    let mut xevent: xlib::XEvent = std::mem::zeroed();
+    loop {
+        xlib::XNextEvent(display, &mut xevent);
+        let cookie = &mut xevent.generic_event_cookie;
+        if cookie.type_ != xlib::GenericEvent || cookie.extension != opcode {
+            continue;
+        }
+        if xlib::XGetEventData(display, cookie) == FALSE {
+            continue;
         }
+        match cookie.evtype {
+            xinput2::XI_HierarchyChanged | xinput2::XI_DeviceChanged => {
+                scroll_valuators = query_scroll_valuators(display);
+            }
+            _ => {
+                let raw = &*(cookie.data as *const xinput2::XIRawEvent);
+                let mut event_types = convert_raw(display, root, raw, &scroll_valuators);
+                if let Some(lock) = event_types.first().and_then(toggled_lock) {
+                    // The server already handled the key, the lock is up to date
+                    event_types.extend(
+                        x11_lock_state(display, lock).and_then(|on| locks.update(lock, on)),
+                    );
+                }
+                for event_type in event_types {
+                    let event = Event {
+                        time: SystemTime::now(),
+                        name: keyboard.add(&event_type),
+                        event_type,
+                        device_id: raw.sourceid.try_into().ok(),
+                    };
+                    if let Err(payload) = panic::catch_unwind(AssertUnwindSafe(|| callback(event)))
+                    {
+                        xlib::XFreeEventData(display, cookie);
+                        return Err(ListenError::CallbackPanic(payload));
+                    }
+                }
+            }
+        }
+        xlib::XFreeEventData(display, cookie);
     }
-    Ok(())
 }
 
-// No idea how to do that properly relevant doc lives here:
-// https://www.x.org/releases/X11R7.7/doc/libXtst/recordlib.html#Datum_Flags
-// https://docs.rs/xproto/1.1.5/xproto/struct._xEvent__bindgen_ty_1.html
-// 0.4.2: xproto was removed for some reason and contained the real structs
-// but we can't use it anymore.
-#[repr(C)]
-struct XRecordDatum {
-    type_: u8,
-    code: u8,
-    _rest: u64,
-    _1: bool,
-    _2: bool,
-    _3: bool,
-    root_x: i16,
-    root_y: i16,
-    event_x: i16,
-    event_y: i16,
-    state: u16,
+/// Selects the events we need on the root window, returns the XInput2 opcode.
+unsafe fn setup_xinput(display: *mut xlib::Display) -> Result<c_int, ListenError> {
+    let opcode = xinput2_opcode(display).ok_or(ListenError::XInputExtensionError)?;
+
+    let mut raw_mask = [0 as c_uchar; 4];
+    for event in &[
+        xinput2::XI_RawKeyPress,
+        xinput2::XI_RawKeyRelease,
+        xinput2::XI_RawButtonPress,
+        xinput2::XI_RawButtonRelease,
+        xinput2::XI_RawMotion,
+    ] {
+        xinput2::XISetMask(&mut raw_mask, *event);
+    }
+    // Device changes are only reported for the slaves, which is where the
+    // scroll classes we look up live.
+    let mut device_mask = [0 as c_uchar; 4];
+    xinput2::XISetMask(&mut device_mask, xinput2::XI_HierarchyChanged);
+    xinput2::XISetMask(&mut device_mask, xinput2::XI_DeviceChanged);
+    let mut masks = [
+        xinput2::XIEventMask {
+            deviceid: xinput2::XIAllMasterDevices,
+            mask_len: raw_mask.len() as c_int,
+            mask: raw_mask.as_mut_ptr(),
+        },
+        xinput2::XIEventMask {
+            deviceid: xinput2::XIAllDevices,
+            mask_len: device_mask.len() as c_int,
+            mask: device_mask.as_mut_ptr(),
+        },
+    ];
+    let root = xlib::XDefaultRootWindow(display);
+    if xinput2::XISelectEvents(display, root, masks.as_mut_ptr(), masks.len() as c_int)
+        != xlib::Success as c_int
+    {
+        return Err(ListenError::XInputExtensionError);
+    }
+    xlib::XSync(display, FALSE);
+    Ok(opcode)
 }
 
-unsafe extern "C" fn record_callback(
-    _null: *mut c_char,
-    raw_data: *mut xrecord::XRecordInterceptData,
-) {
-    let data = raw_data.as_ref().unwrap();
-    if data.category != xrecord::XRecordFromServer {
-        return;
+unsafe fn query_scroll_valuators(display: *mut xlib::Display) -> ScrollValuators {
+    let mut scroll_valuators = HashMap::new();
+    let mut count = 0;
+    let devices = xinput2::XIQueryDevice(display, xinput2::XIAllDevices, &mut count);
+    if devices.is_null() {
+        return scroll_valuators;
+    }
+    for device in slice::from_raw_parts(devices, count as usize) {
+        let classes = slice::from_raw_parts(device.classes, device.num_classes as usize);
+        let valuators: Vec<ScrollValuator> = classes
+            .iter()
+            .filter(|class| (***class)._type == xinput2::XIScrollClass)
+            .map(|class| {
+                let scroll = &*(*class as *const xinput2::XIScrollClassInfo);
+                ScrollValuator {
+                    number: scroll.number,
+                    vertical: scroll.scroll_type == xinput2::XIScrollTypeVertical,
+                    increment: scroll.increment,
+                }
+            })
+            .collect();
+        if !valuators.is_empty() {
+            scroll_valuators.insert(device.deviceid, valuators);
+        }
     }
+    xinput2::XIFreeDeviceInfo(devices);
+    scroll_valuators
+}
 
-    debug_assert!(data.data_len * 4 >= std::mem::size_of::<XRecordDatum>().try_into().unwrap());
-    // Cast binary data
-    #[allow(clippy::cast_ptr_alignment)]
-    let xdatum = (data.data as *const XRecordDatum).as_ref().unwrap();
+/// The (valuator number, value) pairs set in a raw event, values are packed
+/// in the order of the bits set in the mask.
+unsafe fn raw_valuators(raw: &xinput2::XIRawEvent) -> Vec<(c_int, f64)> {
+    let state = &raw.valuators;
+    if state.mask.is_null() || state.values.is_null() {
+        return vec![];
+    }
+    let mask = slice::from_raw_parts(state.mask, state.mask_len as usize);
+    let numbers: Vec<c_int> = (0..state.mask_len * 8)
+        .filter(|number| xinput2::XIMaskIsSet(mask, *number))
+        .collect();
+    let values = slice::from_raw_parts(state.values, numbers.len());
+    numbers.into_iter().zip(values.iter().copied()).collect()
+}
 
-    let code: c_uint = xdatum.code.into();
-    let type_: c_int = xdatum.type_.into();
+unsafe fn convert_raw(
+    display: *mut xlib::Display,
+    root: xlib::Window,
+    raw: &xinput2::XIRawEvent,
+    scroll_valuators: &ScrollValuators,
+) -> Vec<EventType> {
+    let code = raw.detail as c_uchar;
+    let converted = match raw.evtype {
+        xinput2::XI_RawKeyPress => convert_event(code, xlib::KeyPress, 0.0, 0.0),
+        xinput2::XI_RawKeyRelease => convert_event(code, xlib::KeyRelease, 0.0, 0.0),
+        xinput2::XI_RawButtonPress => convert_event(code, xlib::ButtonPress, 0.0, 0.0),
+        xinput2::XI_RawButtonRelease => convert_event(code, xlib::ButtonRelease, 0.0, 0.0),
+        xinput2::XI_RawMotion => return convert_motion(display, root, raw, scroll_valuators),
+        _ => None,
+    };
+    converted.into_iter().collect()
+}
 
-    let x = xdatum.root_x as f64;
-    let y = xdatum.root_y as f64;
+/// A raw motion can move the pointer, scroll, or both.
+unsafe fn convert_motion(
+    display: *mut xlib::Display,
+    root: xlib::Window,
+    raw: &xinput2::XIRawEvent,
+    scroll_valuators: &ScrollValuators,
+) -> Vec<EventType> {
+    let scroll_axes = scroll_valuators
+        .get(&raw.sourceid)
+        .map(Vec::as_slice)
+        .unwrap_or(&[]);
+    let mut moved = false;
+    let mut delta_x = 0.0;
+    let mut delta_y = 0.0;
+    for (number, value) in raw_valuators(raw) {
+        match scroll_axes.iter().find(|axis| axis.number == number) {
+            Some(axis) if axis.increment != 0.0 => {
+                // Valuators grow downward and rightward, `SmoothWheel` is positive
+                // upward like `Wheel`.
+                if axis.vertical {
+                    delta_y -= value / axis.increment;
+                } else {
+                    delta_x += value / axis.increment;
+                }
+            }
+            Some(_) => {}
+            None => moved |= number == 0 || number == 1,
+        }
+    }
 
-    if let Some(event) = convert(&mut KEYBOARD, code, type_, x, y) {
-        if let Some(callback) = &mut GLOBAL_CALLBACK {
-            callback(event);
+    let mut event_types = vec![];
+    if moved {
+        if let Some((x, y)) = pointer_position(display, root, raw.deviceid) {
+            event_types.push(EventType::MouseMove { x, y });
         }
     }
-    xrecord::XRecordFreeData(raw_data);
+    if delta_x != 0.0 || delta_y != 0.0 {
+        event_types.push(EventType::SmoothWheel { delta_x, delta_y });
+    }
+    event_types
+}
+
+/// Raw events are not relative to any window so they don't carry the pointer
+/// position, it has already been updated by the time they are delivered.
+unsafe fn pointer_position(
+    display: *mut xlib::Display,
+    root: xlib::Window,
+    deviceid: c_int,
+) -> Option<(f64, f64)> {
+    let mut root_return = 0;
+    let mut child_return = 0;
+    let mut root_x = 0.0;
+    let mut root_y = 0.0;
+    let mut win_x = 0.0;
+    let mut win_y = 0.0;
+    let mut buttons: xinput2::XIButtonState = std::mem::zeroed();
+    let mut modifiers: xinput2::XIModifierState = std::mem::zeroed();
+    let mut group: xinput2::XIModifierState = std::mem::zeroed();
+    let same_screen = xinput2::XIQueryPointer(
+        display,
+        deviceid,
+        root,
+        &mut root_return,
+        &mut child_return,
+        &mut root_x,
+        &mut root_y,
+        &mut win_x,
+        &mut win_y,
+        &mut buttons,
+        &mut modifiers,
+        &mut group,
+    );
+    if !buttons.mask.is_null() {
+        xlib::XFree(buttons.mask as *mut libc::c_void);
+    }
+    if same_screen == FALSE {
+        return None;
+    }
+    Some((root_x, root_y))
 }
@@ -0,0 +1,186 @@
+use crate::linux::common::{Display, FALSE, TRUE};
+#[cfg(feature = "evdev")]
+use crate::linux::evdev::{get_device_paths, DEV_PATH};
+#[cfg(feature = "evdev")]
+use crate::linux::virtual_device::Led;
+#[cfg(feature = "evdev")]
+use crate::rdev::SimulateError;
+use crate::rdev::{EventType, Key, Lock};
+#[cfg(feature = "evdev")]
+use evdev_rs::enums::EventCode;
+#[cfg(feature = "evdev")]
+use evdev_rs::{Device, DeviceWrapper, LedState};
+use std::collections::HashMap;
+use std::ffi::CStr;
+#[cfg(feature = "evdev")]
+use std::fs::{File, OpenOptions};
+use std::os::raw::{c_int, c_uint};
+use std::ptr::null_mut;
+use x11::keysym;
+use x11::xlib;
+
+pub const LOCKS: [Lock; 3] = [Lock::CapsLock, Lock::NumLock, Lock::ScrollLock];
+// Not exported by the x11 crate
+const XKB_USE_CORE_KBD: c_uint = 0x0100;
+
+/// Remembers the locks, to only report a `LockChange` when one actually changes,
+/// every keyboard reporting its LEDs.
+#[derive(Debug, Default)]
+pub struct LockStates {
+    states: HashMap<Lock, bool>,
+}
+
+impl LockStates {
+    pub fn update(&mut self, lock: Lock, on: bool) -> Option<EventType> {
+        match self.states.insert(lock, on) {
+            Some(previous) if previous == on => None,
+            _ => Some(EventType::LockChange { lock, on }),
+        }
+    }
+}
+
+/// The lock a key event may have toggled.
+pub fn toggled_lock(event_type: &EventType) -> Option<Lock> {
+    match event_type {
+        EventType::KeyPress(key) | EventType::KeyRelease(key) => match key {
+            Key::CapsLock => Some(Lock::CapsLock),
+            Key::NumLock => Some(Lock::NumLock),
+            Key::ScrollLock => Some(Lock::ScrollLock),
+            _ => None,
+        },
+        _ => None,
+    }
+}
+
+fn indicator_name(lock: Lock) -> &'static CStr {
+    let name: &'static [u8] = match lock {
+        Lock::CapsLock => b"Caps Lock\0",
+        Lock::NumLock => b"Num Lock\0",
+        Lock::ScrollLock => b"Scroll Lock\0",
+    };
+    CStr::from_bytes_with_nul(name).expect("indicator names end with a nul byte")
+}
+
+fn lock_keysym(lock: Lock) -> c_uint {
+    match lock {
+        Lock::CapsLock => keysym::XK_Caps_Lock,
+        Lock::NumLock => keysym::XK_Num_Lock,
+        Lock::ScrollLock => keysym::XK_Scroll_Lock,
+    }
+}
+
+/// The index and state of the XKB indicator showing the lock.
+unsafe fn indicator(display: *mut xlib::Display, lock: Lock) -> Option<(c_int, bool)> {
+    let atom = xlib::XInternAtom(display, indicator_name(lock).as_ptr(), TRUE);
+    if atom == 0 {
+        return None;
+    }
+    let mut index = 0;
+    let mut state = FALSE;
+    if xlib::XkbGetNamedIndicator(
+        display,
+        atom,
+        &mut index,
+        &mut state,
+        null_mut(),
+        null_mut(),
+    ) == FALSE
+    {
+        return None;
+    }
+    Some((index, state != FALSE))
+}
+
+pub unsafe fn x11_lock_state(display: *mut xlib::Display, lock: Lock) -> Option<bool> {
+    indicator(display, lock).map(|(_, on)| on)
+}
+
+/// Locks the modifier the lock key is bound to. Locks bound to none, like
+/// Scroll Lock usually is, only exist as an LED, which is set instead.
+/// Returns 0 on failure like the Xlib calls.
+pub unsafe fn x11_set_lock(display: *mut xlib::Display, lock: Lock, on: bool) -> c_int {
+    let mask = xlib::XkbKeysymToModifiers(display, lock_keysym(lock).into());
+    if mask != 0 {
+        let values = if on { mask } else { 0 };
+        return xlib::XkbLockModifiers(display, XKB_USE_CORE_KBD, mask, values);
+    }
+    let index = match indicator(display, lock) {
+        Some((index, _)) => index,
+        None => return 0,
+    };
+    let mut control: xlib::XKeyboardControl = std::mem::zeroed();
+    // Core LEDs are numbered from 1
+    control.led = index + 1;
+    control.led_mode = if on {
+        xlib::LedModeOn
+    } else {
+        xlib::LedModeOff
+    };
+    xlib::XChangeKeyboardControl(display, xlib::KBLed | xlib::KBLedMode, &mut control)
+}
+
+#[cfg(feature = "evdev")]
+fn lock_led(lock: Lock) -> Led {
+    match lock {
+        Lock::CapsLock => Led::CapsLock,
+        Lock::NumLock => Led::NumLock,
+        Lock::ScrollLock => Led::ScrollLock,
+    }
+}
+
+/// The devices with the LED, opened for writing when `write` is set.
+#[cfg(feature = "evdev")]
+fn led_devices(led: Led, write: bool) -> Vec<Device> {
+    let code = EventCode::EV_LED(led.code());
+    get_device_paths(DEV_PATH)
+        .unwrap_or_default()
+        .into_iter()
+        .filter_map(|path| {
+            let file = if write {
+                OpenOptions::new().read(true).write(true).open(path)
+            } else {
+                File::open(path)
+            };
+            Device::new_from_file(file.ok()?).ok()
+        })
+        .filter(|device| device.has(code))
+        .collect()
+}
+
+/// Compositors and the console keep the keyboard LEDs in sync with the locks.
+#[cfg(feature = "evdev")]
+pub fn evdev_lock_state(lock: Lock) -> Option<bool> {
+    let code = EventCode::EV_LED(lock_led(lock).code());
+    led_devices(lock_led(lock), false)
+        .iter()
+        .find_map(|device| device.event_value(&code))
+        .map(|value| value != 0)
+}
+
+#[cfg(not(feature = "evdev"))]
+fn evdev_lock_state(_lock: Lock) -> Option<bool> {
+    None
+}
+
+/// Asks the X server, then the keyboards.
+pub fn lock_state(lock: Lock) -> Option<bool> {
+    Display::new()
+        .and_then(|display| display.get_lock_state(lock))
+        .or_else(|| evdev_lock_state(lock))
+}
+
+/// Writes the LED to every keyboard having it, the locks are left alone.
+#[cfg(feature = "evdev")]
+pub fn set_led(led: Led, on: bool) -> Result<(), SimulateError> {
+    let code = EventCode::EV_LED(led.code());
+    let mut written = false;
+    for device in led_devices(led, true) {
+        let state = if on { LedState::On } else { LedState::Off };
+        written |= device.kernel_set_led_value(&code, state).is_ok();
+    }
+    if written {
+        Ok(())
+    } else {
+        Err(SimulateError)
+    }
+}
@@ -1,18 +1,52 @@
 extern crate libc;
 extern crate x11;
 
+mod backend;
 mod common;
+#[cfg(feature = "evdev")]
+mod devices;
 mod display;
+#[cfg(feature = "evdev")]
+mod evdev;
+#[cfg(feature = "evdev")]
+mod evdev_keycodes;
+#[cfg(feature = "evdev")]
+mod evdev_listen;
+#[cfg(feature = "evdev")]
+mod gamepads;
 #[cfg(feature = "unstable_grab")]
 mod grab;
 mod keyboard;
 mod keycodes;
 mod listen;
+mod locks;
 mod simulate;
+#[cfg(feature = "evdev")]
+mod touch;
+#[cfg(feature = "evdev")]
+mod uinput;
+#[cfg(feature = "evdev")]
+mod virtual_device;
+#[cfg(feature = "unstable_grab")]
+mod x11_grab;
 
-pub use crate::linux::display::display_size;
+pub use crate::linux::backend::{
+    available_backends, listen, listen_with, simulate, NativeSimulator,
+};
 #[cfg(feature = "unstable_grab")]
-pub use crate::linux::grab::grab;
+pub use crate::linux::backend::{grab, grab_with};
+#[cfg(feature = "evdev")]
+pub use crate::linux::devices::{devices, DeviceInfo};
+pub use crate::linux::display::display_size;
+#[cfg(feature = "evdev")]
+pub use crate::linux::evdev_listen::listen as listen_evdev;
 pub use crate::linux::keyboard::Keyboard;
-pub use crate::linux::listen::listen;
-pub use crate::linux::simulate::simulate;
+pub use crate::linux::locks::lock_state;
+#[cfg(feature = "evdev")]
+pub use crate::linux::locks::set_led;
+#[cfg(feature = "evdev")]
+pub use crate::linux::uinput::{simulate as simulate_uinput, UInputSimulator};
+#[cfg(feature = "evdev")]
+pub use crate::linux::virtual_device::{
+    AbsoluteAxis, Bus, Led, RelativeAxis, VirtualDevice, VirtualDeviceBuilder,
+};
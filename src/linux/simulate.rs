@@ -1,5 +1,6 @@
 use crate::linux::common::{FALSE, TRUE};
 use crate::linux::keycodes::code_from_key;
+use crate::linux::locks::x11_set_lock;
 use crate::rdev::{Button, EventType, SimulateError};
 use std::convert::TryInto;
 use std::os::raw::c_int;
@@ -7,6 +8,24 @@ use std::ptr::null;
 use x11::xlib;
 use x11::xtest;
 
+unsafe fn fake_wheel(display: *mut xlib::Display, delta_x: i64, delta_y: i64) -> c_int {
+    let code_x = if delta_x > 0 { 7 } else { 6 };
+    let code_y = if delta_y > 0 { 4 } else { 5 };
+
+    let mut result: c_int = 1;
+    for _ in 0..delta_x.abs() {
+        result = result
+            & xtest::XTestFakeButtonEvent(display, code_x, TRUE, 0)
+            & xtest::XTestFakeButtonEvent(display, code_x, FALSE, 0)
+    }
+    for _ in 0..delta_y.abs() {
+        result = result
+            & xtest::XTestFakeButtonEvent(display, code_y, TRUE, 0)
+            & xtest::XTestFakeButtonEvent(display, code_y, FALSE, 0)
+    }
+    result
+}
+
 unsafe fn send_native(event_type: &EventType, display: *mut xlib::Display) -> Option<()> {
     let res = match event_type {
         EventType::KeyPress(key) => {
@@ -56,23 +75,42 @@ unsafe fn send_native(event_type: &EventType, display: *mut xlib::Display) -> Op
             xtest::XTestFakeMotionEvent(display, 0, x, y, 0)
             //     xlib::XWarpPointer(display, 0, root, 0, 0, 0, 0, *x as i32, *y as i32);
         }
-        EventType::Wheel { delta_x, delta_y } => {
-            let code_x = if *delta_x > 0 { 7 } else { 6 };
-            let code_y = if *delta_y > 0 { 4 } else { 5 };
-
-            let mut result: c_int = 1;
-            for _ in 0..delta_x.abs() {
-                result = result
-                    & xtest::XTestFakeButtonEvent(display, code_x, TRUE, 0)
-                    & xtest::XTestFakeButtonEvent(display, code_x, FALSE, 0)
-            }
-            for _ in 0..delta_y.abs() {
-                result = result
-                    & xtest::XTestFakeButtonEvent(display, code_y, TRUE, 0)
-                    & xtest::XTestFakeButtonEvent(display, code_y, FALSE, 0)
-            }
-            result
+        EventType::MouseMoveRelative { delta_x, delta_y } => {
+            let dx = if delta_x.is_finite() {
+                delta_x.round() as c_int
+            } else {
+                0
+            };
+            let dy = if delta_y.is_finite() {
+                delta_y.round() as c_int
+            } else {
+                0
+            };
+            xtest::XTestFakeRelativeMotionEvent(display, 0, dx, dy, 0)
+        }
+        EventType::Wheel { delta_x, delta_y } => fake_wheel(display, *delta_x, *delta_y),
+        EventType::SmoothWheel { delta_x, delta_y } => {
+            // XTest has no smooth scrolling, only whole notches.
+            let dx = if delta_x.is_finite() {
+                delta_x.round() as i64
+            } else {
+                0
+            };
+            let dy = if delta_y.is_finite() {
+                delta_y.round() as i64
+            } else {
+                0
+            };
+            fake_wheel(display, dx, dy)
         }
+        EventType::LockChange { lock, on } => x11_set_lock(display, *lock, *on),
+        // XTest has no absolute axes besides the pointer, nor gamepads
+        EventType::Axis { .. }
+        | EventType::GamepadButtonPress(_)
+        | EventType::GamepadButtonRelease(_)
+        | EventType::GamepadAxis { .. } => return None,
+        // Touches are only ever listened to
+        EventType::Touch { .. } | EventType::Gesture(_) => return None,
     };
     if res == 0 {
         None
@@ -0,0 +1,487 @@
+use crate::rdev::{EventType, Gesture, SwipeDirection, TouchPhase};
+use evdev_rs::enums::{EventCode, EV_ABS, EV_KEY, EV_SYN};
+use evdev_rs::{Device, DeviceWrapper, InputEvent};
+use std::collections::HashMap;
+
+/// How far two fingers move together, in pixels, before they scroll.
+const SCROLL_THRESHOLD: f64 = 15.0;
+/// How much the distance between two fingers changes, in pixels, before they pinch.
+const PINCH_THRESHOLD: f64 = 30.0;
+/// How far three fingers or more move together, in pixels, to swipe.
+const SWIPE_THRESHOLD: f64 = 100.0;
+
+/// Touchscreens and touchpads, pen tablets being left out.
+pub fn is_touch_device(device: &Device) -> bool {
+    device.has(EventCode::EV_KEY(EV_KEY::BTN_TOUCH))
+        && !device.has(EventCode::EV_KEY(EV_KEY::BTN_TOOL_PEN))
+}
+
+/// The range of an axis, when the device has it.
+fn range(device: &Device, axis: EV_ABS) -> Option<(i32, i32)> {
+    device
+        .abs_info(&EventCode::EV_ABS(axis))
+        .map(|info| (info.minimum, info.maximum))
+}
+
+fn ratio(value: i32, (min, max): (i32, i32)) -> f64 {
+    if min >= max {
+        return 0.0;
+    }
+    ((f64::from(value) - f64::from(min)) / (f64::from(max) - f64::from(min))).clamp(0.0, 1.0)
+}
+
+#[derive(Debug, Clone, Copy)]
+struct Slot {
+    /// The tracking id, -1 when no finger is in the slot.
+    id: i32,
+    /// The tracking id the last events were about.
+    reported: i32,
+    x: i32,
+    y: i32,
+    pressure: i32,
+    moved: bool,
+}
+
+impl Default for Slot {
+    fn default() -> Slot {
+        Slot {
+            id: -1,
+            reported: -1,
+            x: 0,
+            y: 0,
+            pressure: 0,
+            moved: false,
+        }
+    }
+}
+
+/// The fingers on a device, updated by the events of a frame and reported at
+/// its end.
+#[derive(Debug)]
+struct TouchDevice {
+    multitouch: bool,
+    slot: usize,
+    slots: Vec<Slot>,
+    x_range: (i32, i32),
+    y_range: (i32, i32),
+    pressure_range: Option<(i32, i32)>,
+    /// Single touch devices have no tracking ids, so touches are numbered here.
+    next_id: i32,
+    gestures: Gestures,
+}
+
+impl TouchDevice {
+    fn new(device: &Device) -> TouchDevice {
+        let multitouch = device.has(EventCode::EV_ABS(EV_ABS::ABS_MT_SLOT));
+        let (x, y, pressure) = if multitouch {
+            (
+                EV_ABS::ABS_MT_POSITION_X,
+                EV_ABS::ABS_MT_POSITION_Y,
+                EV_ABS::ABS_MT_PRESSURE,
+            )
+        } else {
+            (EV_ABS::ABS_X, EV_ABS::ABS_Y, EV_ABS::ABS_PRESSURE)
+        };
+        let slots = match device.abs_info(&EventCode::EV_ABS(EV_ABS::ABS_MT_SLOT)) {
+            Some(info) if multitouch => info.maximum.max(0) as usize + 1,
+            _ => 1,
+        };
+        TouchDevice {
+            multitouch,
+            // The slot events start with, until the first ABS_MT_SLOT
+            slot: device
+                .event_value(&EventCode::EV_ABS(EV_ABS::ABS_MT_SLOT))
+                .map_or(0, |slot| slot.max(0) as usize),
+            slots: vec![Slot::default(); slots],
+            x_range: range(device, x).unwrap_or((0, 0)),
+            y_range: range(device, y).unwrap_or((0, 0)),
+            pressure_range: range(device, pressure),
+            next_id: 0,
+            gestures: Gestures::default(),
+        }
+    }
+
+    fn update(&mut self, code: &EventCode, value: i32) -> bool {
+        let multitouch = self.multitouch;
+        if let (EventCode::EV_ABS(EV_ABS::ABS_MT_SLOT), true) = (code, multitouch) {
+            self.slot = value.max(0) as usize;
+            return true;
+        }
+        if let (EventCode::EV_KEY(EV_KEY::BTN_TOUCH), false) = (code, multitouch) {
+            self.slots[0].id = if value != 0 {
+                self.next_id = self.next_id.wrapping_add(1) & i32::MAX;
+                self.next_id
+            } else {
+                -1
+            };
+            return true;
+        }
+        // Slots out of the advertised ones are ignored
+        let mut ignored = Slot::default();
+        let slot = self.slots.get_mut(self.slot).unwrap_or(&mut ignored);
+        match (code, multitouch) {
+            (EventCode::EV_ABS(EV_ABS::ABS_MT_TRACKING_ID), true) => slot.id = value,
+            (EventCode::EV_ABS(EV_ABS::ABS_MT_POSITION_X), true)
+            | (EventCode::EV_ABS(EV_ABS::ABS_X), false) => {
+                slot.x = value;
+                slot.moved = true;
+            }
+            (EventCode::EV_ABS(EV_ABS::ABS_MT_POSITION_Y), true)
+            | (EventCode::EV_ABS(EV_ABS::ABS_Y), false) => {
+                slot.y = value;
+                slot.moved = true;
+            }
+            (EventCode::EV_ABS(EV_ABS::ABS_MT_PRESSURE), true)
+            | (EventCode::EV_ABS(EV_ABS::ABS_PRESSURE), false) => {
+                slot.pressure = value;
+                slot.moved = true;
+            }
+            // The pointer emulation of multitouch devices, the finger counts and the
+            // touch shapes, which the slots already tell about
+            (EventCode::EV_ABS(EV_ABS::ABS_X), true)
+            | (EventCode::EV_ABS(EV_ABS::ABS_Y), true)
+            | (EventCode::EV_ABS(EV_ABS::ABS_PRESSURE), true)
+            | (EventCode::EV_KEY(EV_KEY::BTN_TOUCH), true) => {}
+            (EventCode::EV_ABS(axis), _) if *axis as u16 >= EV_ABS::ABS_MT_SLOT as u16 => {}
+            (EventCode::EV_KEY(key), _)
+                if (EV_KEY::BTN_TOOL_FINGER as u16..=EV_KEY::BTN_TOOL_QUADTAP as u16)
+                    .contains(&(*key as u16)) => {}
+            _ => return false,
+        }
+        true
+    }
+
+    /// The touches that changed during the frame, then the gestures they make.
+    fn frame(&mut self, width: f64, height: f64, gestures: bool) -> Vec<EventType> {
+        let mut events = vec![];
+        let mut fingers = vec![];
+        for (index, slot) in self.slots.iter_mut().enumerate() {
+            let touch = |phase, id| EventType::Touch {
+                phase,
+                slot: index as u32,
+                id,
+                x: ratio(slot.x, self.x_range) * width,
+                y: ratio(slot.y, self.y_range) * height,
+                pressure: self.pressure_range.map(|range| ratio(slot.pressure, range)),
+            };
+            if slot.reported != slot.id {
+                if slot.reported >= 0 {
+                    events.push(touch(TouchPhase::End, slot.reported));
+                }
+                if slot.id >= 0 {
+                    events.push(touch(TouchPhase::Start, slot.id));
+                }
+            } else if slot.id >= 0 && slot.moved {
+                events.push(touch(TouchPhase::Move, slot.id));
+            }
+            if slot.id >= 0 {
+                fingers.push((
+                    ratio(slot.x, self.x_range) * width,
+                    ratio(slot.y, self.y_range) * height,
+                ));
+            }
+            slot.reported = slot.id;
+            slot.moved = false;
+        }
+        if gestures {
+            events.extend(
+                self.gestures
+                    .frame(&fingers)
+                    .into_iter()
+                    .map(EventType::Gesture),
+            );
+        }
+        events
+    }
+}
+
+/// Turns the events of touchscreens and touchpads into `Touch` events, one per
+/// finger at the end of each frame, and optionally into gestures.
+#[derive(Debug)]
+pub struct TouchStates {
+    width: f64,
+    height: f64,
+    gestures: bool,
+    devices: HashMap<usize, TouchDevice>,
+}
+
+impl TouchStates {
+    pub fn new(width: f64, height: f64, gestures: bool) -> TouchStates {
+        TouchStates {
+            width,
+            height,
+            gestures,
+            devices: HashMap::new(),
+        }
+    }
+
+    /// The events of `device` at the end of a frame, none while it goes on.
+    /// `None` when the device is not a touch device or the event has nothing to do
+    /// with touches, like the buttons of touchpads, which is then converted as usual.
+    pub fn convert(
+        &mut self,
+        event: &InputEvent,
+        device: &Device,
+        device_idx: usize,
+    ) -> Option<Vec<EventType>> {
+        if !is_touch_device(device) {
+            return None;
+        }
+        let touch_device = self
+            .devices
+            .entry(device_idx)
+            .or_insert_with(|| TouchDevice::new(device));
+        if event.event_code == EventCode::EV_SYN(EV_SYN::SYN_REPORT) {
+            return Some(touch_device.frame(self.width, self.height, self.gestures));
+        }
+        if touch_device.update(&event.event_code, event.value) {
+            Some(vec![])
+        } else {
+            None
+        }
+    }
+}
+
+/// What the fingers on a device are doing, from the first one down until the
+/// last one is lifted.
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Recognized {
+    /// Nothing yet, the fingers have not moved enough.
+    Nothing,
+    Scroll,
+    Pinch,
+    /// Three fingers or more went down, which ends up as a swipe or nothing.
+    Swipe,
+    /// Already reported or given up on.
+    Done,
+}
+
+/// Recognizes gestures from the finger positions of each frame.
+#[derive(Debug)]
+struct Gestures {
+    recognized: Recognized,
+    fingers: usize,
+    /// Where the fingers started, for the ongoing gesture.
+    start_center: (f64, f64),
+    start_distance: f64,
+    last_center: (f64, f64),
+    last_distance: f64,
+    /// The most fingers down during a swipe.
+    swipe_fingers: usize,
+}
+
+impl Default for Gestures {
+    fn default() -> Gestures {
+        Gestures {
+            recognized: Recognized::Nothing,
+            fingers: 0,
+            start_center: (0.0, 0.0),
+            start_distance: 0.0,
+            last_center: (0.0, 0.0),
+            last_distance: 0.0,
+            swipe_fingers: 0,
+        }
+    }
+}
+
+fn center(fingers: &[(f64, f64)]) -> (f64, f64) {
+    let count = fingers.len().max(1) as f64;
+    let (x, y) = fingers
+        .iter()
+        .fold((0.0, 0.0), |(x, y), finger| (x + finger.0, y + finger.1));
+    (x / count, y / count)
+}
+
+fn distance((x1, y1): (f64, f64), (x2, y2): (f64, f64)) -> f64 {
+    (x2 - x1).hypot(y2 - y1)
+}
+
+impl Gestures {
+    fn frame(&mut self, fingers: &[(f64, f64)]) -> Vec<Gesture> {
+        let count = fingers.len();
+        let center = center(fingers);
+        let spread = match fingers {
+            [first, second] => distance(*first, *second),
+            _ => 0.0,
+        };
+        let mut gestures = vec![];
+        if count == 0 {
+            if self.recognized == Recognized::Swipe {
+                gestures.extend(self.swipe());
+            }
+            *self = Gestures::default();
+            return gestures;
+        }
+        if count >= 3 && self.recognized != Recognized::Done {
+            if self.recognized != Recognized::Swipe {
+                self.recognized = Recognized::Swipe;
+                self.start_center = center;
+            }
+            self.swipe_fingers = self.swipe_fingers.max(count);
+            self.last_center = center;
+        } else if self.recognized == Recognized::Swipe {
+            // The first finger lifted ends the swipe
+            gestures.extend(self.swipe());
+            self.recognized = Recognized::Done;
+        } else if count != self.fingers {
+            // Fingers coming and going start over
+            self.recognized = match self.recognized {
+                Recognized::Nothing => Recognized::Nothing,
+                _ => Recognized::Done,
+            };
+            self.start_center = center;
+            self.start_distance = spread;
+        } else if count == 2 {
+            if self.recognized == Recognized::Nothing {
+                if (spread - self.start_distance).abs() > PINCH_THRESHOLD {
+                    self.recognized = Recognized::Pinch;
+                } else if distance(center, self.start_center) > SCROLL_THRESHOLD {
+                    self.recognized = Recognized::Scroll;
+                }
+            }
+            match self.recognized {
+                Recognized::Scroll if center != self.last_center => {
+                    gestures.push(Gesture::Scroll {
+                        delta_x: center.0 - self.last_center.0,
+                        delta_y: center.1 - self.last_center.1,
+                    })
+                }
+                Recognized::Pinch if spread != self.last_distance && self.start_distance > 0.0 => {
+                    gestures.push(Gesture::Pinch {
+                        scale: spread / self.start_distance,
+                    })
+                }
+                _ => {}
+            }
+        }
+        self.fingers = count;
+        self.last_center = center;
+        self.last_distance = spread;
+        gestures
+    }
+
+    fn swipe(&self) -> Option<Gesture> {
+        let (dx, dy) = (
+            self.last_center.0 - self.start_center.0,
+            self.last_center.1 - self.start_center.1,
+        );
+        if dx.hypot(dy) < SWIPE_THRESHOLD {
+            return None;
+        }
+        let direction = if dx.abs() > dy.abs() {
+            if dx > 0.0 {
+                SwipeDirection::Right
+            } else {
+                SwipeDirection::Left
+            }
+        } else if dy > 0.0 {
+            SwipeDirection::Down
+        } else {
+            SwipeDirection::Up
+        };
+        Some(Gesture::Swipe {
+            fingers: self.swipe_fingers as u32,
+            direction,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn run(frames: &[&[(f64, f64)]]) -> Vec<Gesture> {
+        let mut gestures = Gestures::default();
+        frames
+            .iter()
+            .flat_map(|fingers| gestures.frame(fingers))
+            .collect()
+    }
+
+    #[test]
+    fn test_scroll() {
+        let gestures = run(&[
+            &[(100.0, 100.0)],
+            &[(100.0, 100.0), (200.0, 100.0)],
+            &[(100.0, 110.0), (200.0, 110.0)],
+            &[(100.0, 130.0), (200.0, 130.0)],
+            &[(100.0, 140.0), (200.0, 140.0)],
+            &[],
+        ]);
+        assert_eq!(
+            gestures,
+            vec![
+                Gesture::Scroll {
+                    delta_x: 0.0,
+                    delta_y: 20.0
+                },
+                Gesture::Scroll {
+                    delta_x: 0.0,
+                    delta_y: 10.0
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn test_pinch() {
+        let gestures = run(&[
+            &[(100.0, 100.0), (200.0, 100.0)],
+            &[(90.0, 100.0), (210.0, 100.0)],
+            &[(50.0, 100.0), (250.0, 100.0)],
+            &[(50.0, 100.0), (250.0, 100.0)],
+            &[],
+        ]);
+        assert_eq!(gestures, vec![Gesture::Pinch { scale: 2.0 }]);
+    }
+
+    #[test]
+    fn test_swipe() {
+        let gestures = run(&[
+            &[(100.0, 100.0), (150.0, 100.0), (200.0, 100.0)],
+            &[(50.0, 100.0), (100.0, 100.0), (150.0, 100.0)],
+            &[(-50.0, 100.0), (0.0, 100.0), (50.0, 100.0)],
+            // Lifting the fingers one by one does not scroll
+            &[(-50.0, 100.0), (0.0, 100.0)],
+            &[(-50.0, 200.0), (0.0, 200.0)],
+            &[],
+        ]);
+        assert_eq!(
+            gestures,
+            vec![Gesture::Swipe {
+                fingers: 3,
+                direction: SwipeDirection::Left
+            }]
+        );
+        // All the fingers lifted at once
+        let gestures = run(&[
+            &[
+                (100.0, 100.0),
+                (150.0, 100.0),
+                (200.0, 100.0),
+                (250.0, 100.0),
+            ],
+            &[
+                (100.0, 300.0),
+                (150.0, 300.0),
+                (200.0, 300.0),
+                (250.0, 300.0),
+            ],
+            &[],
+        ]);
+        assert_eq!(
+            gestures,
+            vec![Gesture::Swipe {
+                fingers: 4,
+                direction: SwipeDirection::Down
+            }]
+        );
+        // Not far enough
+        let gestures = run(&[
+            &[(100.0, 100.0), (150.0, 100.0), (200.0, 100.0)],
+            &[(100.0, 120.0), (150.0, 120.0), (200.0, 120.0)],
+            &[],
+        ]);
+        assert_eq!(gestures, vec![]);
+    }
+}
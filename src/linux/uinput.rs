@@ -0,0 +1,262 @@
+use crate::linux::evdev::{display_size_or_default, HI_RES_NOTCH};
+use crate::linux::evdev_keycodes::{rdev_button_to_evdev_key, rdev_key_to_evdev_key};
+use crate::linux::locks::evdev_lock_state;
+use crate::rdev::{EventType, SimulateError};
+use evdev_rs::enums::{int_to_ev_key, BusType, EventCode, EV_ABS, EV_KEY, EV_REL, EV_SYN};
+use evdev_rs::{
+    AbsInfo, DeviceWrapper, EnableCodeData, InputEvent, TimeVal, UInputDevice, UninitDevice,
+};
+use lazy_static::lazy_static;
+use std::convert::TryInto;
+use std::io;
+use std::sync::Mutex;
+use std::thread;
+use std::time::Duration;
+
+static DEVICE_NAME: &str = "rdev virtual input";
+static POINTER_NAME: &str = "rdev virtual pointer";
+/// Time given to udev and the compositor to pick up freshly created devices,
+/// events written before that are silently lost.
+pub const SETTLE_DELAY: Duration = Duration::from_millis(200);
+// Everything below BTN_MISC is a keyboard key
+pub const MAX_KEY_CODE: u32 = 0xff;
+
+lazy_static! {
+    static ref SIMULATOR: Mutex<Option<UInputSimulator>> = Mutex::new(None);
+}
+
+/// Simulates events through virtual uinput devices instead of XTest, so it works
+/// on Wayland, in a TTY or in a container, as long as `/dev/uinput` is writable.
+///
+/// Two devices are created: a keyboard and relative mouse, and an absolute pointer
+/// whose axes span the display so `EventType::MouseMove` lands on the right pixel.
+pub struct UInputSimulator {
+    device: UInputDevice,
+    pointer: UInputDevice,
+    width: i32,
+    height: i32,
+}
+
+impl UInputSimulator {
+    /// Creates the virtual devices, sized after the X display when there is one,
+    /// and after a 1920x1080 screen otherwise.
+    pub fn new() -> io::Result<UInputSimulator> {
+        let (width, height) = display_size_or_default();
+        UInputSimulator::with_display_size(width, height)
+    }
+
+    /// Creates the virtual devices for a display of `width`x`height` pixels.
+    pub fn with_display_size(width: u64, height: u64) -> io::Result<UInputSimulator> {
+        let invalid_size = || io::Error::new(io::ErrorKind::InvalidInput, "invalid display size");
+        let width: i32 = width.try_into().map_err(|_| invalid_size())?;
+        let height: i32 = height.try_into().map_err(|_| invalid_size())?;
+        if width <= 0 || height <= 0 {
+            return Err(invalid_size());
+        }
+
+        let device = new_device(DEVICE_NAME)?;
+        for code in 1..=MAX_KEY_CODE {
+            if let Some(key) = int_to_ev_key(code) {
+                device.enable(EventCode::EV_KEY(key))?;
+            }
+        }
+        for button in &[
+            EV_KEY::BTN_LEFT,
+            EV_KEY::BTN_RIGHT,
+            EV_KEY::BTN_MIDDLE,
+            EV_KEY::BTN_SIDE,
+            EV_KEY::BTN_EXTRA,
+        ] {
+            device.enable(EventCode::EV_KEY(*button))?;
+        }
+        for axis in &[
+            EV_REL::REL_X,
+            EV_REL::REL_Y,
+            EV_REL::REL_WHEEL,
+            EV_REL::REL_HWHEEL,
+            EV_REL::REL_WHEEL_HI_RES,
+            EV_REL::REL_HWHEEL_HI_RES,
+        ] {
+            device.enable(EventCode::EV_REL(*axis))?;
+        }
+
+        // A device with both relative and absolute axes confuses libinput,
+        // so absolute motion goes through a tablet-like pointer of its own.
+        let pointer = new_device(POINTER_NAME)?;
+        for (axis, size) in &[(EV_ABS::ABS_X, width), (EV_ABS::ABS_Y, height)] {
+            let info = AbsInfo {
+                value: 0,
+                minimum: 0,
+                maximum: size - 1,
+                fuzz: 0,
+                flat: 0,
+                resolution: 0,
+            };
+            pointer.enable_event_code(
+                &EventCode::EV_ABS(*axis),
+                Some(EnableCodeData::AbsInfo(info)),
+            )?;
+        }
+        pointer.enable(EventCode::EV_KEY(EV_KEY::BTN_LEFT))?;
+
+        let simulator = UInputSimulator {
+            device: UInputDevice::create_from_device(&device)?,
+            pointer: UInputDevice::create_from_device(&pointer)?,
+            width,
+            height,
+        };
+        thread::sleep(SETTLE_DELAY);
+        Ok(simulator)
+    }
+
+    pub fn simulate(&self, event_type: &EventType) -> Result<(), SimulateError> {
+        match event_type {
+            EventType::KeyPress(key) => {
+                let key = rdev_key_to_evdev_key(key).ok_or(SimulateError)?;
+                write_frame(&self.device, &[(EventCode::EV_KEY(key), 1)])
+            }
+            EventType::KeyRelease(key) => {
+                let key = rdev_key_to_evdev_key(key).ok_or(SimulateError)?;
+                write_frame(&self.device, &[(EventCode::EV_KEY(key), 0)])
+            }
+            EventType::ButtonPress(button) => {
+                let button = rdev_button_to_evdev_key(button).ok_or(SimulateError)?;
+                write_frame(&self.device, &[(EventCode::EV_KEY(button), 1)])
+            }
+            EventType::ButtonRelease(button) => {
+                let button = rdev_button_to_evdev_key(button).ok_or(SimulateError)?;
+                write_frame(&self.device, &[(EventCode::EV_KEY(button), 0)])
+            }
+            EventType::MouseMove { x, y } => {
+                let x = clamp_coordinate(*x, self.width)?;
+                let y = clamp_coordinate(*y, self.height)?;
+                write_frame(
+                    &self.pointer,
+                    &[
+                        (EventCode::EV_ABS(EV_ABS::ABS_X), x),
+                        (EventCode::EV_ABS(EV_ABS::ABS_Y), y),
+                    ],
+                )
+            }
+            EventType::MouseMoveRelative { delta_x, delta_y } => {
+                let dx = to_value(*delta_x)?;
+                let dy = to_value(*delta_y)?;
+                write_frame(
+                    &self.device,
+                    &[
+                        (EventCode::EV_REL(EV_REL::REL_X), dx),
+                        (EventCode::EV_REL(EV_REL::REL_Y), dy),
+                    ],
+                )
+            }
+            EventType::Wheel { delta_x, delta_y } => {
+                let dx: i32 = (*delta_x).try_into().map_err(|_| SimulateError)?;
+                let dy: i32 = (*delta_y).try_into().map_err(|_| SimulateError)?;
+                write_wheel(&self.device, dx, dy, f64::from(dx), f64::from(dy))
+            }
+            EventType::SmoothWheel { delta_x, delta_y } => {
+                let dx = to_value(*delta_x)?;
+                let dy = to_value(*delta_y)?;
+                write_wheel(&self.device, dx, dy, *delta_x, *delta_y)
+            }
+            EventType::LockChange { lock, on } => {
+                // Taps the lock key when the keyboard LEDs say it is needed
+                if evdev_lock_state(*lock).ok_or(SimulateError)? == *on {
+                    return Ok(());
+                }
+                let key = rdev_key_to_evdev_key(&lock.key()).ok_or(SimulateError)?;
+                write_frame(&self.device, &[(EventCode::EV_KEY(key), 1)])?;
+                write_frame(&self.device, &[(EventCode::EV_KEY(key), 0)])
+            }
+            // Needs a device with the axis or the button, see `VirtualDevice`
+            EventType::Axis { .. }
+            | EventType::GamepadButtonPress(_)
+            | EventType::GamepadButtonRelease(_)
+            | EventType::GamepadAxis { .. } => Err(SimulateError),
+            // Touches are only ever listened to
+            EventType::Touch { .. } | EventType::Gesture(_) => Err(SimulateError),
+        }
+    }
+}
+
+/// The devices advertise high resolution wheels, which libinput prefers over
+/// the notch ones, so both are always written.
+pub fn write_wheel(
+    device: &UInputDevice,
+    notches_x: i32,
+    notches_y: i32,
+    delta_x: f64,
+    delta_y: f64,
+) -> Result<(), SimulateError> {
+    write_frame(
+        device,
+        &[
+            (EventCode::EV_REL(EV_REL::REL_HWHEEL), notches_x),
+            (EventCode::EV_REL(EV_REL::REL_WHEEL), notches_y),
+            (
+                EventCode::EV_REL(EV_REL::REL_HWHEEL_HI_RES),
+                to_value(delta_x * HI_RES_NOTCH)?,
+            ),
+            (
+                EventCode::EV_REL(EV_REL::REL_WHEEL_HI_RES),
+                to_value(delta_y * HI_RES_NOTCH)?,
+            ),
+        ],
+    )
+}
+
+pub fn new_device(name: &str) -> io::Result<UninitDevice> {
+    let device = UninitDevice::new().ok_or_else(|| io::Error::other("could not create device"))?;
+    device.set_name(name);
+    device.set_bustype(BusType::BUS_VIRTUAL as u16);
+    Ok(device)
+}
+
+fn clamp_coordinate(value: f64, size: i32) -> Result<i32, SimulateError> {
+    if !value.is_finite() {
+        return Err(SimulateError);
+    }
+    Ok(value.round().clamp(0.0, (size - 1).into()) as i32)
+}
+
+pub fn to_value(value: f64) -> Result<i32, SimulateError> {
+    if !value.is_finite() {
+        return Err(SimulateError);
+    }
+    Ok(value.round().clamp(i32::MIN.into(), i32::MAX.into()) as i32)
+}
+
+/// Writes the events followed by a `SYN_REPORT`, skipping zero relative values
+/// so a wheel event on one axis doesn't report a stationary other axis.
+pub fn write_frame(
+    device: &UInputDevice,
+    events: &[(EventCode, i32)],
+) -> Result<(), SimulateError> {
+    let time = TimeVal::new(0, 0);
+    for (code, value) in events {
+        if let (EventCode::EV_REL(_), 0) = (code, value) {
+            continue;
+        }
+        device
+            .write_event(&InputEvent::new(&time, code, *value))
+            .map_err(|_| SimulateError)?;
+    }
+    device
+        .write_event(&InputEvent::new(
+            &time,
+            &EventCode::EV_SYN(EV_SYN::SYN_REPORT),
+            0,
+        ))
+        .map_err(|_| SimulateError)
+}
+
+pub fn simulate(event_type: &EventType) -> Result<(), SimulateError> {
+    let mut simulator = SIMULATOR.lock().map_err(|_| SimulateError)?;
+    if simulator.is_none() {
+        *simulator = Some(UInputSimulator::new().map_err(|_| SimulateError)?);
+    }
+    match &*simulator {
+        Some(simulator) => simulator.simulate(event_type),
+        None => Err(SimulateError),
+    }
+}
@@ -0,0 +1,654 @@
+use crate::linux::evdev_keycodes::{
+    rdev_button_to_evdev_key, rdev_gamepad_axis_to_evdev_abs, rdev_gamepad_button_to_evdev_key,
+    rdev_key_to_evdev_key,
+};
+use crate::linux::uinput::{
+    new_device, to_value, write_frame, write_wheel, MAX_KEY_CODE, SETTLE_DELAY,
+};
+use crate::rdev::{Button, EventType, GamepadAxis, GamepadButton, Key, SimulateError};
+use evdev_rs::enums::{
+    int_to_bus_type, int_to_ev_abs, int_to_ev_key, BusType, EventCode, EV_ABS, EV_KEY, EV_LED,
+    EV_REL,
+};
+use evdev_rs::{AbsInfo, DeviceWrapper, EnableCodeData, UInputDevice};
+use std::collections::{HashMap, HashSet};
+use std::convert::TryInto;
+use std::thread;
+
+static DEFAULT_NAME: &str = "rdev virtual device";
+static GAMEPAD_BUTTONS: [GamepadButton; 15] = [
+    GamepadButton::South,
+    GamepadButton::East,
+    GamepadButton::North,
+    GamepadButton::West,
+    GamepadButton::LeftBumper,
+    GamepadButton::RightBumper,
+    GamepadButton::Select,
+    GamepadButton::Start,
+    GamepadButton::Mode,
+    GamepadButton::LeftThumb,
+    GamepadButton::RightThumb,
+    GamepadButton::DpadUp,
+    GamepadButton::DpadDown,
+    GamepadButton::DpadLeft,
+    GamepadButton::DpadRight,
+];
+static GAMEPAD_AXES: [GamepadAxis; 6] = [
+    GamepadAxis::LeftStickX,
+    GamepadAxis::LeftStickY,
+    GamepadAxis::RightStickX,
+    GamepadAxis::RightStickY,
+    GamepadAxis::LeftTrigger,
+    GamepadAxis::RightTrigger,
+];
+
+/// The bus a device is plugged into.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash, Default)]
+#[non_exhaustive]
+pub enum Bus {
+    Usb,
+    Bluetooth,
+    /// The PS/2 controller of built-in keyboards and touchpads.
+    Ps2,
+    I2c,
+    #[default]
+    Virtual,
+    /// Any other `BUS_*` code of `linux/input.h`.
+    Other(u16),
+}
+
+impl Bus {
+    pub(crate) fn from_code(code: u16) -> Bus {
+        match int_to_bus_type(code.into()) {
+            Some(BusType::BUS_USB) => Bus::Usb,
+            Some(BusType::BUS_BLUETOOTH) => Bus::Bluetooth,
+            Some(BusType::BUS_I8042) => Bus::Ps2,
+            Some(BusType::BUS_I2C) => Bus::I2c,
+            Some(BusType::BUS_VIRTUAL) => Bus::Virtual,
+            _ => Bus::Other(code),
+        }
+    }
+
+    fn code(self) -> u16 {
+        match self {
+            Bus::Usb => BusType::BUS_USB as u16,
+            Bus::Bluetooth => BusType::BUS_BLUETOOTH as u16,
+            Bus::Ps2 => BusType::BUS_I8042 as u16,
+            Bus::I2c => BusType::BUS_I2C as u16,
+            Bus::Virtual => BusType::BUS_VIRTUAL as u16,
+            Bus::Other(code) => code,
+        }
+    }
+}
+
+/// An axis reporting movements, like the ones of mice.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
+#[non_exhaustive]
+pub enum RelativeAxis {
+    X,
+    Y,
+    /// Also reports high resolution scrolling.
+    Wheel,
+    /// Also reports high resolution scrolling.
+    HorizontalWheel,
+    Dial,
+}
+
+impl RelativeAxis {
+    pub(crate) const ALL: &'static [RelativeAxis] = &[
+        RelativeAxis::X,
+        RelativeAxis::Y,
+        RelativeAxis::Wheel,
+        RelativeAxis::HorizontalWheel,
+        RelativeAxis::Dial,
+    ];
+
+    /// The first code is the one every device with the axis has.
+    pub(crate) fn codes(self) -> &'static [EV_REL] {
+        match self {
+            RelativeAxis::X => &[EV_REL::REL_X],
+            RelativeAxis::Y => &[EV_REL::REL_Y],
+            RelativeAxis::Wheel => &[EV_REL::REL_WHEEL, EV_REL::REL_WHEEL_HI_RES],
+            RelativeAxis::HorizontalWheel => &[EV_REL::REL_HWHEEL, EV_REL::REL_HWHEEL_HI_RES],
+            RelativeAxis::Dial => &[EV_REL::REL_DIAL],
+        }
+    }
+}
+
+/// An axis reporting positions, like the ones of tablets, joysticks and triggers.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
+#[non_exhaustive]
+pub enum AbsoluteAxis {
+    X,
+    Y,
+    Z,
+    RotationX,
+    RotationY,
+    RotationZ,
+    Throttle,
+    Rudder,
+    Wheel,
+    Gas,
+    Brake,
+    HatX,
+    HatY,
+    Pressure,
+    Distance,
+}
+
+impl AbsoluteAxis {
+    pub(crate) const ALL: &'static [AbsoluteAxis] = &[
+        AbsoluteAxis::X,
+        AbsoluteAxis::Y,
+        AbsoluteAxis::Z,
+        AbsoluteAxis::RotationX,
+        AbsoluteAxis::RotationY,
+        AbsoluteAxis::RotationZ,
+        AbsoluteAxis::Throttle,
+        AbsoluteAxis::Rudder,
+        AbsoluteAxis::Wheel,
+        AbsoluteAxis::Gas,
+        AbsoluteAxis::Brake,
+        AbsoluteAxis::HatX,
+        AbsoluteAxis::HatY,
+        AbsoluteAxis::Pressure,
+        AbsoluteAxis::Distance,
+    ];
+
+    /// The axis with this `ABS_*` code, as found in `EventType::Axis`.
+    pub fn from_code(code: u16) -> Option<AbsoluteAxis> {
+        AbsoluteAxis::ALL
+            .iter()
+            .copied()
+            .find(|axis| axis.code() as u16 == code)
+    }
+
+    pub(crate) fn code(self) -> EV_ABS {
+        match self {
+            AbsoluteAxis::X => EV_ABS::ABS_X,
+            AbsoluteAxis::Y => EV_ABS::ABS_Y,
+            AbsoluteAxis::Z => EV_ABS::ABS_Z,
+            AbsoluteAxis::RotationX => EV_ABS::ABS_RX,
+            AbsoluteAxis::RotationY => EV_ABS::ABS_RY,
+            AbsoluteAxis::RotationZ => EV_ABS::ABS_RZ,
+            AbsoluteAxis::Throttle => EV_ABS::ABS_THROTTLE,
+            AbsoluteAxis::Rudder => EV_ABS::ABS_RUDDER,
+            AbsoluteAxis::Wheel => EV_ABS::ABS_WHEEL,
+            AbsoluteAxis::Gas => EV_ABS::ABS_GAS,
+            AbsoluteAxis::Brake => EV_ABS::ABS_BRAKE,
+            AbsoluteAxis::HatX => EV_ABS::ABS_HAT0X,
+            AbsoluteAxis::HatY => EV_ABS::ABS_HAT0Y,
+            AbsoluteAxis::Pressure => EV_ABS::ABS_PRESSURE,
+            AbsoluteAxis::Distance => EV_ABS::ABS_DISTANCE,
+        }
+    }
+}
+
+/// A keyboard LED.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
+#[non_exhaustive]
+pub enum Led {
+    NumLock,
+    CapsLock,
+    ScrollLock,
+    Compose,
+    Kana,
+}
+
+impl Led {
+    pub(crate) const ALL: &'static [Led] = &[
+        Led::NumLock,
+        Led::CapsLock,
+        Led::ScrollLock,
+        Led::Compose,
+        Led::Kana,
+    ];
+
+    pub(crate) fn code(self) -> EV_LED {
+        match self {
+            Led::NumLock => EV_LED::LED_NUML,
+            Led::CapsLock => EV_LED::LED_CAPSL,
+            Led::ScrollLock => EV_LED::LED_SCROLLL,
+            Led::Compose => EV_LED::LED_COMPOSE,
+            Led::Kana => EV_LED::LED_KANA,
+        }
+    }
+}
+
+/// Builds a `VirtualDevice`, see `VirtualDevice::builder`.
+#[derive(Debug, Clone)]
+pub struct VirtualDeviceBuilder {
+    name: String,
+    bus: Bus,
+    vendor: u16,
+    product: u16,
+    version: u16,
+    keys: HashSet<EV_KEY>,
+    relative_axes: HashSet<EV_REL>,
+    absolute_axes: HashMap<EV_ABS, (i32, i32)>,
+    leds: HashSet<EV_LED>,
+}
+
+impl Default for VirtualDeviceBuilder {
+    fn default() -> VirtualDeviceBuilder {
+        VirtualDeviceBuilder {
+            name: DEFAULT_NAME.to_string(),
+            bus: Bus::default(),
+            vendor: 0,
+            product: 0,
+            version: 0,
+            keys: HashSet::new(),
+            relative_axes: HashSet::new(),
+            absolute_axes: HashMap::new(),
+            leds: HashSet::new(),
+        }
+    }
+}
+
+impl VirtualDeviceBuilder {
+    /// "rdev virtual device" by default.
+    pub fn name(mut self, name: &str) -> VirtualDeviceBuilder {
+        self.name = name.to_string();
+        self
+    }
+
+    /// `Bus::Virtual` by default.
+    pub fn bus(mut self, bus: Bus) -> VirtualDeviceBuilder {
+        self.bus = bus;
+        self
+    }
+
+    /// The vendor, product and version ids, all 0 by default.
+    pub fn ids(mut self, vendor: u16, product: u16, version: u16) -> VirtualDeviceBuilder {
+        self.vendor = vendor;
+        self.product = product;
+        self.version = version;
+        self
+    }
+
+    /// Keys without an evdev code, like `Key::Unknown`, are left out.
+    pub fn key(mut self, key: Key) -> VirtualDeviceBuilder {
+        self.keys.extend(rdev_key_to_evdev_key(&key));
+        self
+    }
+
+    /// Every keyboard key.
+    pub fn keyboard(mut self) -> VirtualDeviceBuilder {
+        self.keys
+            .extend((1..=MAX_KEY_CODE).filter_map(int_to_ev_key));
+        self
+    }
+
+    /// Buttons without an evdev code are left out.
+    pub fn button(mut self, button: Button) -> VirtualDeviceBuilder {
+        self.keys.extend(rdev_button_to_evdev_key(&button));
+        self
+    }
+
+    /// The buttons and axes of a wheel mouse.
+    pub fn mouse(self) -> VirtualDeviceBuilder {
+        self.button(Button::Left)
+            .button(Button::Right)
+            .button(Button::Middle)
+            .relative_axis(RelativeAxis::X)
+            .relative_axis(RelativeAxis::Y)
+            .relative_axis(RelativeAxis::Wheel)
+            .relative_axis(RelativeAxis::HorizontalWheel)
+    }
+
+    /// The buttons, sticks and triggers of an Xbox style controller, which games
+    /// handle through SDL without any setup. Sticks go from -32768 to 32767 and
+    /// triggers from 0 to 255, like on Xbox controllers.
+    pub fn gamepad(mut self) -> VirtualDeviceBuilder {
+        self.keys.extend(
+            GAMEPAD_BUTTONS
+                .iter()
+                .filter_map(rdev_gamepad_button_to_evdev_key),
+        );
+        for axis in &GAMEPAD_AXES {
+            let range = if axis.min() < 0.0 {
+                (-32768, 32767)
+            } else {
+                (0, 255)
+            };
+            self.absolute_axes
+                .insert(rdev_gamepad_axis_to_evdev_abs(axis), range);
+        }
+        self
+    }
+
+    pub fn relative_axis(mut self, axis: RelativeAxis) -> VirtualDeviceBuilder {
+        self.relative_axes.extend(axis.codes());
+        self
+    }
+
+    /// An axis going from `min` to `max`, both included.
+    pub fn absolute_axis(mut self, axis: AbsoluteAxis, min: i32, max: i32) -> VirtualDeviceBuilder {
+        self.absolute_axes.insert(axis.code(), (min, max));
+        self
+    }
+
+    /// An axis by its `ABS_*` code, for the ones `AbsoluteAxis` lacks, like the
+    /// per-key axes of analog keyboards. Unknown codes are left out.
+    pub fn absolute_axis_code(mut self, code: u16, min: i32, max: i32) -> VirtualDeviceBuilder {
+        self.absolute_axes
+            .extend(int_to_ev_abs(code.into()).map(|axis| (axis, (min, max))));
+        self
+    }
+
+    pub fn led(mut self, led: Led) -> VirtualDeviceBuilder {
+        self.leds.insert(led.code());
+        self
+    }
+
+    /// Creates the device and gives udev and the compositor time to pick it up.
+    /// Fails when `/dev/uinput` cannot be written to.
+    pub fn build(self) -> Result<VirtualDevice, SimulateError> {
+        let device = new_device(&self.name).map_err(|_| SimulateError)?;
+        device.set_bustype(self.bus.code());
+        device.set_vendor_id(self.vendor);
+        device.set_product_id(self.product);
+        device.set_version(self.version);
+        let codes = self
+            .keys
+            .iter()
+            .map(|key| EventCode::EV_KEY(*key))
+            .chain(
+                self.relative_axes
+                    .iter()
+                    .map(|axis| EventCode::EV_REL(*axis)),
+            )
+            .chain(self.leds.iter().map(|led| EventCode::EV_LED(*led)));
+        for code in codes {
+            device.enable(code).map_err(|_| SimulateError)?;
+        }
+        for (axis, (min, max)) in &self.absolute_axes {
+            let info = AbsInfo {
+                value: initial_value((*min, *max)),
+                minimum: *min,
+                maximum: *max,
+                fuzz: 0,
+                flat: 0,
+                resolution: 0,
+            };
+            device
+                .enable_event_code(
+                    &EventCode::EV_ABS(*axis),
+                    Some(EnableCodeData::AbsInfo(info)),
+                )
+                .map_err(|_| SimulateError)?;
+        }
+        let virtual_device = VirtualDevice {
+            device: UInputDevice::create_from_device(&device).map_err(|_| SimulateError)?,
+            keys: self.keys,
+            relative_axes: self.relative_axes,
+            absolute_axes: self.absolute_axes,
+        };
+        thread::sleep(SETTLE_DELAY);
+        Ok(virtual_device)
+    }
+}
+
+/// A uinput device with the name, ids and capabilities of your choice, for
+/// instance to give test suites keyboards and mice with known identities.
+/// It goes away when dropped. (Linux only, requires the `evdev` feature)
+///
+/// ```no_run
+/// use rdev::{AbsoluteAxis, EventType, Key, VirtualDevice};
+///
+/// let keyboard = VirtualDevice::builder()
+///     .name("test keyboard")
+///     .ids(0x1234, 0x5678, 1)
+///     .keyboard()
+///     .build()
+///     .unwrap();
+/// keyboard.simulate(&EventType::KeyPress(Key::KeyA)).unwrap();
+/// keyboard.simulate(&EventType::KeyRelease(Key::KeyA)).unwrap();
+///
+/// let pedal = VirtualDevice::builder()
+///     .absolute_axis(AbsoluteAxis::Gas, 0, 1023)
+///     .build()
+///     .unwrap();
+/// pedal.set_axis(AbsoluteAxis::Gas, 512).unwrap();
+/// ```
+pub struct VirtualDevice {
+    device: UInputDevice,
+    keys: HashSet<EV_KEY>,
+    relative_axes: HashSet<EV_REL>,
+    absolute_axes: HashMap<EV_ABS, (i32, i32)>,
+}
+
+impl VirtualDevice {
+    pub fn builder() -> VirtualDeviceBuilder {
+        VirtualDeviceBuilder::default()
+    }
+
+    /// The `/dev/input/event*` node of the device.
+    pub fn devnode(&self) -> Option<&str> {
+        self.device.devnode()
+    }
+
+    /// Writes the event, which fails when the device lacks the key, button or
+    /// axes it needs. `MouseMove` sets the `X` and `Y` absolute axes, clamped
+    /// to their ranges. `Axis` values are moved from the range of the event to the
+    /// range of the axis, gamepad axes as with `set_gamepad_axis`. `LockChange`,
+    /// `Touch` and `Gesture` always fail.
+    pub fn simulate(&self, event_type: &EventType) -> Result<(), SimulateError> {
+        match event_type {
+            EventType::KeyPress(key) => self.write_key(rdev_key_to_evdev_key(key), 1),
+            EventType::KeyRelease(key) => self.write_key(rdev_key_to_evdev_key(key), 0),
+            EventType::ButtonPress(button) => self.write_key(rdev_button_to_evdev_key(button), 1),
+            EventType::ButtonRelease(button) => self.write_key(rdev_button_to_evdev_key(button), 0),
+            EventType::MouseMove { x, y } => write_frame(
+                &self.device,
+                &[
+                    self.axis_value(AbsoluteAxis::X, to_value(*x)?)?,
+                    self.axis_value(AbsoluteAxis::Y, to_value(*y)?)?,
+                ],
+            ),
+            EventType::MouseMoveRelative { delta_x, delta_y } => {
+                self.require(&[EV_REL::REL_X, EV_REL::REL_Y])?;
+                write_frame(
+                    &self.device,
+                    &[
+                        (EventCode::EV_REL(EV_REL::REL_X), to_value(*delta_x)?),
+                        (EventCode::EV_REL(EV_REL::REL_Y), to_value(*delta_y)?),
+                    ],
+                )
+            }
+            EventType::Wheel { delta_x, delta_y } => {
+                let dx: i32 = (*delta_x).try_into().map_err(|_| SimulateError)?;
+                let dy: i32 = (*delta_y).try_into().map_err(|_| SimulateError)?;
+                self.require_wheels(dx, dy)?;
+                write_wheel(&self.device, dx, dy, f64::from(dx), f64::from(dy))
+            }
+            EventType::SmoothWheel { delta_x, delta_y } => {
+                let dx = to_value(*delta_x)?;
+                let dy = to_value(*delta_y)?;
+                self.require_wheels(dx, dy)?;
+                write_wheel(&self.device, dx, dy, *delta_x, *delta_y)
+            }
+            // The locks belong to the compositor or the console, not to devices
+            EventType::LockChange { .. } => Err(SimulateError),
+            EventType::Axis {
+                code,
+                value,
+                min,
+                max,
+            } => {
+                let axis = int_to_ev_abs((*code).into()).ok_or(SimulateError)?;
+                let range = self.absolute_axes.get(&axis).ok_or(SimulateError)?;
+                write_frame(
+                    &self.device,
+                    &[(
+                        EventCode::EV_ABS(axis),
+                        rescale(*value, (*min, *max), *range),
+                    )],
+                )
+            }
+            EventType::GamepadButtonPress(button) => self.set_gamepad_button(*button, true),
+            EventType::GamepadButtonRelease(button) => self.set_gamepad_button(*button, false),
+            EventType::GamepadAxis { axis, value } => self.set_gamepad_axis(*axis, *value),
+            EventType::Touch { .. } | EventType::Gesture(_) => Err(SimulateError),
+        }
+    }
+
+    /// Moves an absolute axis, the value is clamped to its range.
+    pub fn set_axis(&self, axis: AbsoluteAxis, value: i32) -> Result<(), SimulateError> {
+        write_frame(&self.device, &[self.axis_value(axis, value)?])
+    }
+
+    pub fn set_gamepad_button(
+        &self,
+        button: GamepadButton,
+        pressed: bool,
+    ) -> Result<(), SimulateError> {
+        self.write_key(rdev_gamepad_button_to_evdev_key(&button), pressed as i32)
+    }
+
+    /// Moves a stick or a trigger, from `GamepadAxis::min` to 1.0 across the range
+    /// of the axis. Fails when the device lacks the axis.
+    pub fn set_gamepad_axis(&self, axis: GamepadAxis, position: f64) -> Result<(), SimulateError> {
+        let code = rdev_gamepad_axis_to_evdev_abs(&axis);
+        let range = *self.absolute_axes.get(&code).ok_or(SimulateError)?;
+        write_frame(
+            &self.device,
+            &[(
+                EventCode::EV_ABS(code),
+                gamepad_axis_value(axis, position, range),
+            )],
+        )
+    }
+
+    fn write_key(&self, key: Option<EV_KEY>, value: i32) -> Result<(), SimulateError> {
+        match key {
+            Some(key) if self.keys.contains(&key) => {
+                write_frame(&self.device, &[(EventCode::EV_KEY(key), value)])
+            }
+            _ => Err(SimulateError),
+        }
+    }
+
+    fn axis_value(
+        &self,
+        axis: AbsoluteAxis,
+        value: i32,
+    ) -> Result<(EventCode, i32), SimulateError> {
+        let code = axis.code();
+        let (min, max) = self.absolute_axes.get(&code).ok_or(SimulateError)?;
+        Ok((EventCode::EV_ABS(code), value.clamp(*min, *max)))
+    }
+
+    fn require(&self, axes: &[EV_REL]) -> Result<(), SimulateError> {
+        if axes.iter().all(|axis| self.relative_axes.contains(axis)) {
+            Ok(())
+        } else {
+            Err(SimulateError)
+        }
+    }
+
+    /// Only the wheels that move are needed.
+    fn require_wheels(&self, delta_x: i32, delta_y: i32) -> Result<(), SimulateError> {
+        if delta_x != 0 {
+            self.require(RelativeAxis::HorizontalWheel.codes())?;
+        }
+        if delta_y != 0 {
+            self.require(RelativeAxis::Wheel.codes())?;
+        }
+        Ok(())
+    }
+}
+
+/// Maps `value` from one range onto the other, keeping it within the latter.
+fn rescale(value: i32, (from_min, from_max): (i32, i32), (to_min, to_max): (i32, i32)) -> i32 {
+    if from_min >= from_max {
+        return value.clamp(to_min, to_max);
+    }
+    let ratio =
+        (f64::from(value) - f64::from(from_min)) / (f64::from(from_max) - f64::from(from_min));
+    let scaled = f64::from(to_min) + ratio * (f64::from(to_max) - f64::from(to_min));
+    (scaled.round() as i32).clamp(to_min, to_max)
+}
+
+/// Where an axis rests until first written, which programs reading the device
+/// state see: centered for sticks, at the bottom for triggers and pedals.
+fn initial_value((min, max): (i32, i32)) -> i32 {
+    if min <= 0 && 0 <= max {
+        0
+    } else {
+        min
+    }
+}
+
+/// Maps a position from `GamepadAxis::min` to 1.0 onto the range of the axis.
+fn gamepad_axis_value(axis: GamepadAxis, position: f64, (min, max): (i32, i32)) -> i32 {
+    let ratio = ((position - axis.min()) / (1.0 - axis.min())).clamp(0.0, 1.0);
+    let value = f64::from(min) + ratio * (f64::from(max) - f64::from(min));
+    value.round() as i32
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_rescale() {
+        assert_eq!(rescale(0, (0, 100), (0, 1000)), 0);
+        assert_eq!(rescale(50, (0, 100), (0, 1000)), 500);
+        assert_eq!(rescale(100, (0, 100), (-1000, 1000)), 1000);
+        assert_eq!(rescale(1, (0, 3), (0, 10)), 3);
+        // Out of the range
+        assert_eq!(rescale(-20, (0, 100), (0, 1000)), 0);
+        assert_eq!(rescale(150, (0, 100), (0, 1000)), 1000);
+        // Without a range to map from, only clamped
+        assert_eq!(rescale(7, (5, 5), (0, 10)), 7);
+        assert_eq!(rescale(20, (5, 5), (0, 10)), 10);
+        assert_eq!(rescale(20, (10, 0), (0, 10)), 10);
+    }
+
+    #[test]
+    fn test_gamepad_axis_value() {
+        let stick = (-32768, 32767);
+        assert_eq!(
+            gamepad_axis_value(GamepadAxis::LeftStickX, -1.0, stick),
+            -32768
+        );
+        assert_eq!(
+            gamepad_axis_value(GamepadAxis::LeftStickX, 1.0, stick),
+            32767
+        );
+        // The center falls between two values
+        assert!(gamepad_axis_value(GamepadAxis::LeftStickX, 0.0, stick).abs() <= 1);
+        assert_eq!(
+            gamepad_axis_value(GamepadAxis::LeftStickX, 2.0, stick),
+            32767
+        );
+        let trigger = (0, 255);
+        assert_eq!(
+            gamepad_axis_value(GamepadAxis::RightTrigger, 0.0, trigger),
+            0
+        );
+        assert_eq!(
+            gamepad_axis_value(GamepadAxis::RightTrigger, 0.5, trigger),
+            128
+        );
+        assert_eq!(
+            gamepad_axis_value(GamepadAxis::RightTrigger, -1.0, trigger),
+            0
+        );
+        // An empty range always gives its only value
+        assert_eq!(gamepad_axis_value(GamepadAxis::LeftStickY, 0.3, (4, 4)), 4);
+    }
+
+    #[test]
+    fn test_initial_value() {
+        let builder = VirtualDevice::builder().gamepad();
+        let stick = rdev_gamepad_axis_to_evdev_abs(&GamepadAxis::LeftStickX);
+        let trigger = rdev_gamepad_axis_to_evdev_abs(&GamepadAxis::LeftTrigger);
+        // A new stick reads centered, not pushed up-left
+        assert_eq!(initial_value(builder.absolute_axes[&stick]), 0);
+        assert_eq!(initial_value(builder.absolute_axes[&trigger]), 0);
+        assert_eq!(initial_value((100, 1023)), 100);
+        assert_eq!(initial_value((-10, -5)), -10);
+    }
+}
@@ -0,0 +1,218 @@
+use crate::backend::GrabOptions;
+use crate::linux::common::{convert_event, xinput2_opcode, FALSE, TRUE};
+use crate::linux::grab::{EscapeCombo, RELEASE_TIMEOUT};
+use crate::linux::keyboard::Keyboard;
+use crate::rdev::{Event, GrabError, KeyboardState};
+use std::collections::HashSet;
+use std::ffi::CStr;
+use std::os::raw::{c_char, c_int, c_uchar, c_uint};
+use std::panic::{self, AssertUnwindSafe};
+use std::ptr::null;
+use std::slice;
+use std::thread;
+use std::time::{Duration, Instant, SystemTime};
+use x11::xinput2;
+use x11::xlib;
+use x11::xtest;
+
+/// Grabs every physical keyboard with XInput2 and replays the key events the
+/// callback lets through with XTest. A grabbed slave device stops feeding its
+/// master device, so replayed events reach the focused window through the XTest
+/// keyboard instead of coming back to us.
+///
+/// This only needs access to the X display, but only keyboards are grabbed:
+/// mouse events are neither reported nor blocked.
+pub fn grab<T>(options: GrabOptions, callback: T) -> Result<(), GrabError>
+where
+    T: Fn(Event) -> Option<Event> + 'static,
+{
+    unsafe {
+        let display = xlib::XOpenDisplay(null());
+        if display.is_null() {
+            return Err(GrabError::MissingDisplayError);
+        }
+        let mut replayed = HashSet::new();
+        let result = grab_display(display, &options, &callback, &mut replayed);
+        // However the grab ends, applications must not be left with keys held
+        for code in replayed {
+            xtest::XTestFakeKeyEvent(display, c_uint::from(code), FALSE, xlib::CurrentTime);
+        }
+        // Closing the connection releases its grabs
+        xlib::XCloseDisplay(display);
+        result
+    }
+}
+
+/// Runs the grab until it fails or ends, `replayed` being the keys replayed as
+/// pressed and not released yet.
+unsafe fn grab_display<T>(
+    display: *mut xlib::Display,
+    options: &GrabOptions,
+    callback: &T,
+    replayed: &mut HashSet<c_uchar>,
+) -> Result<(), GrabError>
+where
+    T: Fn(Event) -> Option<Event>,
+{
+    let mut keyboard = Keyboard::new().ok_or(GrabError::KeyboardError)?;
+    let opcode = xinput2_opcode(display).ok_or(GrabError::XInputExtensionError)?;
+    let root = xlib::XDefaultRootWindow(display);
+    select_hierarchy_events(display, root)?;
+    wait_for_release(display);
+    let mut grabbed = HashSet::new();
+    grab_keyboards(display, root, &mut grabbed);
+    let mut escape = EscapeCombo::new(&options.escape_combo);
+
+    let mut xevent: xlib::XEvent = std::mem::zeroed();
+    loop {
+        xlib::XNextEvent(display, &mut xevent);
+        let cookie = &mut xevent.generic_event_cookie;
+        if cookie.type_ != xlib::GenericEvent || cookie.extension != opcode {
+            continue;
+        }
+        if xlib::XGetEventData(display, cookie) == FALSE {
+            continue;
+        }
+        match cookie.evtype {
+            xinput2::XI_HierarchyChanged => grab_keyboards(display, root, &mut grabbed),
+            xinput2::XI_KeyPress | xinput2::XI_KeyRelease => {
+                let device_event = &*(cookie.data as *const xinput2::XIDeviceEvent);
+                let press = device_event.evtype == xinput2::XI_KeyPress;
+                let code = device_event.detail as c_uchar;
+                let type_ = if press {
+                    xlib::KeyPress
+                } else {
+                    xlib::KeyRelease
+                };
+                if let Some(event_type) = convert_event(code, type_, 0.0, 0.0) {
+                    if escape.pressed(&event_type) {
+                        xlib::XFreeEventData(display, cookie);
+                        return Err(GrabError::EscapeCombo);
+                    }
+                    let event = Event {
+                        time: SystemTime::now(),
+                        name: keyboard.add(&event_type),
+                        event_type,
+                        device_id: device_event.sourceid.try_into().ok(),
+                    };
+                    // The XTest keyboard repeats replayed keys on its own.
+                    let repeat = device_event.flags & xinput2::XIKeyRepeat != 0;
+                    let allowed = match panic::catch_unwind(AssertUnwindSafe(|| callback(event))) {
+                        Ok(allowed) => allowed.is_some(),
+                        Err(payload) => {
+                            xlib::XFreeEventData(display, cookie);
+                            return Err(GrabError::CallbackPanic(payload));
+                        }
+                    };
+                    if allowed && !repeat {
+                        let is_press = if press {
+                            replayed.insert(code);
+                            TRUE
+                        } else {
+                            replayed.remove(&code);
+                            FALSE
+                        };
+                        xtest::XTestFakeKeyEvent(
+                            display,
+                            c_uint::from(code),
+                            is_press,
+                            xlib::CurrentTime,
+                        );
+                        xlib::XFlush(display);
+                    }
+                }
+            }
+            _ => {}
+        }
+        xlib::XFreeEventData(display, cookie);
+    }
+}
+
+/// Waits for the keys held when grabbing starts to be released, like Enter when
+/// launching from a terminal, for up to `RELEASE_TIMEOUT`. Their releases would
+/// come to us instead of the applications that saw them pressed.
+unsafe fn wait_for_release(display: *mut xlib::Display) {
+    let start = Instant::now();
+    let mut keys = [0 as c_char; 32];
+    while start.elapsed() < RELEASE_TIMEOUT {
+        xlib::XQueryKeymap(display, keys.as_mut_ptr());
+        if keys.iter().all(|byte| *byte == 0) {
+            return;
+        }
+        thread::sleep(Duration::from_millis(10));
+    }
+}
+
+/// Keyboards plugged in while grabbing get grabbed too.
+unsafe fn select_hierarchy_events(
+    display: *mut xlib::Display,
+    root: xlib::Window,
+) -> Result<(), GrabError> {
+    let mut mask = [0 as c_uchar; 4];
+    xinput2::XISetMask(&mut mask, xinput2::XI_HierarchyChanged);
+    let mut event_mask = xinput2::XIEventMask {
+        deviceid: xinput2::XIAllDevices,
+        mask_len: mask.len() as c_int,
+        mask: mask.as_mut_ptr(),
+    };
+    if xinput2::XISelectEvents(display, root, &mut event_mask, 1) != xlib::Success as c_int {
+        return Err(GrabError::XInputExtensionError);
+    }
+    Ok(())
+}
+
+/// Grabs the enabled slave keyboards that aren't grabbed yet, except the XTest
+/// ones we replay events through. `grabbed` is updated to the devices still
+/// present, as ids get reused once a device is unplugged.
+unsafe fn grab_keyboards(
+    display: *mut xlib::Display,
+    root: xlib::Window,
+    grabbed: &mut HashSet<c_int>,
+) {
+    let mut count = 0;
+    let devices = xinput2::XIQueryDevice(display, xinput2::XIAllDevices, &mut count);
+    if devices.is_null() {
+        return;
+    }
+    let mut mask = [0 as c_uchar; 4];
+    xinput2::XISetMask(&mut mask, xinput2::XI_KeyPress);
+    xinput2::XISetMask(&mut mask, xinput2::XI_KeyRelease);
+
+    let mut present = HashSet::new();
+    for device in slice::from_raw_parts(devices, count as usize) {
+        if device._use != xinput2::XISlaveKeyboard || device.enabled == FALSE {
+            continue;
+        }
+        let name = CStr::from_ptr(device.name).to_string_lossy();
+        if name.contains("XTEST") {
+            continue;
+        }
+        if grabbed.contains(&device.deviceid) {
+            present.insert(device.deviceid);
+            continue;
+        }
+        let mut event_mask = xinput2::XIEventMask {
+            deviceid: device.deviceid,
+            mask_len: mask.len() as c_int,
+            mask: mask.as_mut_ptr(),
+        };
+        let status = xinput2::XIGrabDevice(
+            display,
+            device.deviceid,
+            root,
+            xlib::CurrentTime,
+            0,
+            xlib::GrabModeAsync,
+            xlib::GrabModeAsync,
+            FALSE,
+            &mut event_mask,
+        );
+        // Another client may hold a grab on this device already, we get
+        // the others anyway.
+        if status == xlib::GrabSuccess {
+            present.insert(device.deviceid);
+        }
+    }
+    xinput2::XIFreeDeviceInfo(devices);
+    *grabbed = present;
+}
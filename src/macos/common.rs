@@ -75,6 +75,7 @@ extern "C" {
     pub fn CFRunLoopGetCurrent() -> CFRunLoopRef;
     pub fn CGEventTapEnable(tap: CFMachPortRef, enable: bool);
     pub fn CFRunLoopRun();
+    pub fn CFRunLoopStop(rl: CFRunLoopRef);
 
     pub static kCFRunLoopCommonModes: CFRunLoopMode;
 
@@ -151,6 +152,7 @@ pub unsafe fn convert(
             event_type,
             time: SystemTime::now(),
             name,
+            device_id: None,
         });
     }
     None
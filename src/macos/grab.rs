@@ -4,10 +4,16 @@ use crate::rdev::{Event, GrabError};
 use cocoa::base::nil;
 use cocoa::foundation::NSAutoreleasePool;
 use core_graphics::event::{CGEventTapLocation, CGEventType};
+use std::any::Any;
 use std::os::raw::c_void;
+use std::panic::{self, AssertUnwindSafe};
 
 static mut GLOBAL_CALLBACK: Option<Box<dyn FnMut(Event) -> Option<Event>>> = None;
 
+/// Set when the callback panicked, unwinding out of the tap callback is undefined
+/// behavior so the run loop is stopped instead.
+static mut PANIC: Option<Box<dyn Any + Send>> = None;
+
 #[link(name = "Cocoa", kind = "framework")]
 extern "C" {}
 
@@ -23,8 +29,13 @@ unsafe extern "C" fn raw_callback(
     if let Ok(mut keyboard) = opt {
         if let Some(event) = convert(_type, &cg_event, &mut keyboard) {
             if let Some(callback) = &mut GLOBAL_CALLBACK {
-                if callback(event).is_none() {
-                    cg_event.set_type(CGEventType::Null);
+                match panic::catch_unwind(AssertUnwindSafe(|| callback(event))) {
+                    Ok(None) => cg_event.set_type(CGEventType::Null),
+                    Ok(Some(_)) => {}
+                    Err(payload) => {
+                        PANIC = Some(payload);
+                        CFRunLoopStop(CFRunLoopGetCurrent());
+                    }
                 }
             }
         }
@@ -60,6 +71,10 @@ where
 
         CGEventTapEnable(tap, true);
         CFRunLoopRun();
+        if let Some(payload) = PANIC.take() {
+            CGEventTapEnable(tap, false);
+            return Err(GrabError::CallbackPanic(payload));
+        }
     }
     Ok(())
 }
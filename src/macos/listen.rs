@@ -4,10 +4,16 @@ use crate::rdev::{Event, ListenError};
 use cocoa::base::nil;
 use cocoa::foundation::NSAutoreleasePool;
 use core_graphics::event::{CGEventTapLocation, CGEventType};
+use std::any::Any;
 use std::os::raw::c_void;
+use std::panic::{self, AssertUnwindSafe};
 
 static mut GLOBAL_CALLBACK: Option<Box<dyn FnMut(Event)>> = None;
 
+/// Set when the callback panicked, unwinding out of the tap callback is undefined
+/// behavior so the run loop is stopped instead.
+static mut PANIC: Option<Box<dyn Any + Send>> = None;
+
 #[link(name = "Cocoa", kind = "framework")]
 extern "C" {}
 
@@ -23,7 +29,10 @@ unsafe extern "C" fn raw_callback(
     if let Ok(mut keyboard) = opt {
         if let Some(event) = convert(_type, &cg_event, &mut keyboard) {
             if let Some(callback) = &mut GLOBAL_CALLBACK {
-                callback(event);
+                if let Err(payload) = panic::catch_unwind(AssertUnwindSafe(|| callback(event))) {
+                    PANIC = Some(payload);
+                    CFRunLoopStop(CFRunLoopGetCurrent());
+                }
             }
         }
     }
@@ -60,6 +69,10 @@ where
 
         CGEventTapEnable(tap, true);
         CFRunLoopRun();
+        if let Some(payload) = PANIC.take() {
+            CGEventTapEnable(tap, false);
+            return Err(ListenError::CallbackPanic(payload));
+        }
     }
     Ok(())
 }
@@ -56,6 +56,15 @@ unsafe fn convert_native_with_source(
             CGEvent::new_mouse_event(source, CGEventType::MouseMoved, point, CGMouseButton::Left)
                 .ok()
         }
+        EventType::MouseMoveRelative { delta_x, delta_y } => {
+            let current = get_current_mouse_location()?;
+            let point = CGPoint {
+                x: current.x + delta_x,
+                y: current.y + delta_y,
+            };
+            CGEvent::new_mouse_event(source, CGEventType::MouseMoved, point, CGMouseButton::Left)
+                .ok()
+        }
         EventType::Wheel { delta_x, delta_y } => {
             let wheel_count = 2;
             CGEvent::new_scroll_event(
@@ -68,6 +77,29 @@ unsafe fn convert_native_with_source(
             )
             .ok()
         }
+        EventType::SmoothWheel { delta_x, delta_y } => {
+            if !delta_x.is_finite() || !delta_y.is_finite() {
+                return None;
+            }
+            let wheel_count = 2;
+            CGEvent::new_scroll_event(
+                source,
+                ScrollEventUnit::PIXEL,
+                wheel_count,
+                delta_y.round() as i32,
+                delta_x.round() as i32,
+                0,
+            )
+            .ok()
+        }
+        // Synthetic lock key presses don't toggle the locks on macOS.
+        EventType::LockChange { .. } => None,
+        EventType::Axis { .. }
+        | EventType::GamepadButtonPress(_)
+        | EventType::GamepadButtonRelease(_)
+        | EventType::GamepadAxis { .. } => None,
+        // Touches are only ever listened to
+        EventType::Touch { .. } | EventType::Gesture(_) => None,
     }
 }
 
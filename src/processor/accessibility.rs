@@ -0,0 +1,556 @@
+use std::collections::{HashMap, HashSet};
+use std::sync::{Arc, Mutex, MutexGuard};
+use std::time::{Duration, Instant, SystemTime};
+
+use crate::hotkey::is_modifier;
+use crate::processor::{with_type, EventProcessor};
+use crate::rdev::{Event, EventType, Key};
+
+const DEFAULT_SLOW_KEYS_DELAY: Duration = Duration::from_millis(300);
+const DEFAULT_BOUNCE_KEYS_DELAY: Duration = Duration::from_millis(300);
+
+fn lock<T>(state: &Mutex<T>) -> MutexGuard<'_, T> {
+    state
+        .lock()
+        .unwrap_or_else(|poisoned| poisoned.into_inner())
+}
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+enum Latch {
+    Latched,
+    Locked,
+}
+
+#[derive(Debug)]
+struct StickyState {
+    locking: bool,
+    /// Modifiers kept down for applications after they were released.
+    latches: HashMap<Key, Latch>,
+    /// Modifiers that are physically down, and whether another key was
+    /// pressed while they were.
+    held: HashMap<Key, bool>,
+    /// Keys pressed while modifiers were latched, the modifiers are released
+    /// along with them.
+    releasing: HashMap<Key, Vec<Key>>,
+}
+
+impl Default for StickyState {
+    fn default() -> StickyState {
+        StickyState {
+            locking: true,
+            latches: HashMap::new(),
+            held: HashMap::new(),
+            releasing: HashMap::new(),
+        }
+    }
+}
+
+impl StickyState {
+    fn with(&self, latch: Latch) -> Vec<Key> {
+        let mut keys: Vec<Key> = self
+            .latches
+            .iter()
+            .filter(|(_, state)| **state == latch)
+            .map(|(key, _)| *key)
+            .collect();
+        keys.sort();
+        keys
+    }
+}
+
+/// Sticky keys: a modifier pressed and released on its own latches, and stays
+/// down until the next key is pressed and released. Tapping it again while it
+/// is latched locks it, until it is tapped once more. Modifiers held while
+/// pressing other keys work as usual.
+///
+/// Like the other accessibility filters, it is cheap to clone and clones share
+/// their state, so a clone kept aside can be configured and queried while grabbing.
+///
+/// ```no_run
+/// use rdev::StickyKeys;
+/// #[cfg(feature = "unstable_grab")]
+/// use rdev::{Event, Grabber};
+///
+/// let sticky_keys = StickyKeys::new();
+/// #[cfg(feature = "unstable_grab")]
+/// let grabber = Grabber::builder().processor(sticky_keys.clone()).build();
+/// // This will block.
+/// #[cfg(feature = "unstable_grab")]
+/// if let Err(error) = grabber.grab(|event: Event| Some(event)) {
+///     println!("Error: {:?}", error)
+/// }
+/// println!("Still latched: {:?}", sticky_keys.latched());
+/// ```
+#[derive(Debug, Clone, Default)]
+pub struct StickyKeys {
+    state: Arc<Mutex<StickyState>>,
+}
+
+impl StickyKeys {
+    pub fn new() -> StickyKeys {
+        StickyKeys::default()
+    }
+
+    /// Whether tapping a latched modifier locks it, on by default.
+    pub fn set_locking(&self, locking: bool) {
+        lock(&self.state).locking = locking;
+    }
+
+    /// Modifiers latched until the next key.
+    pub fn latched(&self) -> Vec<Key> {
+        lock(&self.state).with(Latch::Latched)
+    }
+
+    /// Modifiers locked until they are tapped again.
+    pub fn locked(&self) -> Vec<Key> {
+        lock(&self.state).with(Latch::Locked)
+    }
+}
+
+impl EventProcessor for StickyKeys {
+    fn process(&mut self, event: Event, _now: Instant, output: &mut Vec<Event>) {
+        let mut state = lock(&self.state);
+        match event.event_type {
+            EventType::KeyPress(key) if is_modifier(key) => {
+                let repeat = state.held.contains_key(&key);
+                state.held.values_mut().for_each(|used| *used = true);
+                state.held.entry(key).or_insert(repeat);
+                // Already down for applications when latched
+                if !state.latches.contains_key(&key) {
+                    output.push(event);
+                }
+            }
+            EventType::KeyRelease(key) if is_modifier(key) => {
+                let used = match state.held.remove(&key) {
+                    Some(used) => used,
+                    // Pressed before the grab started
+                    None => {
+                        output.push(event);
+                        return;
+                    }
+                };
+                let latch = match (state.latches.get(&key), used) {
+                    (None, false) => Some(Latch::Latched),
+                    (Some(Latch::Latched), false) if state.locking => Some(Latch::Locked),
+                    _ => None,
+                };
+                match latch {
+                    Some(latch) => {
+                        state.latches.insert(key, latch);
+                    }
+                    None => {
+                        state.latches.remove(&key);
+                        output.push(event);
+                    }
+                }
+            }
+            EventType::KeyPress(key) => {
+                state.held.values_mut().for_each(|used| *used = true);
+                if !state.releasing.contains_key(&key) {
+                    let latched = state.with(Latch::Latched);
+                    for modifier in &latched {
+                        state.latches.remove(modifier);
+                    }
+                    state.releasing.insert(key, latched);
+                }
+                output.push(event);
+            }
+            EventType::KeyRelease(key) => {
+                let modifiers = state.releasing.remove(&key).unwrap_or_default();
+                let releases: Vec<Event> = modifiers
+                    .into_iter()
+                    .map(|modifier| with_type(&event, EventType::KeyRelease(modifier)))
+                    .collect();
+                output.push(event);
+                output.extend(releases);
+            }
+            _ => output.push(event),
+        }
+    }
+}
+
+#[derive(Debug)]
+struct SlowState {
+    delay: Duration,
+    /// Presses held back, with when they register.
+    pending: Vec<(Event, Instant)>,
+    /// Keys that registered and are still down.
+    accepted: HashSet<Key>,
+}
+
+/// Slow keys: a key has to be held for the delay before its press is sent,
+/// keys released sooner are swallowed. This keeps brushing against keys from
+/// typing anything.
+///
+/// ```no_run
+/// use rdev::SlowKeys;
+/// #[cfg(feature = "unstable_grab")]
+/// use rdev::{Event, Grabber};
+/// use std::time::Duration;
+///
+/// let slow_keys = SlowKeys::new();
+/// slow_keys.set_delay(Duration::from_millis(500));
+/// #[cfg(feature = "unstable_grab")]
+/// let grabber = Grabber::builder().processor(slow_keys).build();
+/// // This will block.
+/// #[cfg(feature = "unstable_grab")]
+/// if let Err(error) = grabber.grab(|event: Event| Some(event)) {
+///     println!("Error: {:?}", error)
+/// }
+/// ```
+#[derive(Debug, Clone)]
+pub struct SlowKeys {
+    state: Arc<Mutex<SlowState>>,
+}
+
+impl Default for SlowKeys {
+    fn default() -> SlowKeys {
+        SlowKeys {
+            state: Arc::new(Mutex::new(SlowState {
+                delay: DEFAULT_SLOW_KEYS_DELAY,
+                pending: vec![],
+                accepted: HashSet::new(),
+            })),
+        }
+    }
+}
+
+impl SlowKeys {
+    /// The delay is 300ms by default.
+    pub fn new() -> SlowKeys {
+        SlowKeys::default()
+    }
+
+    /// Applies to the keys pressed from now on.
+    pub fn set_delay(&self, delay: Duration) {
+        lock(&self.state).delay = delay;
+    }
+
+    pub fn delay(&self) -> Duration {
+        lock(&self.state).delay
+    }
+
+    /// Keys held that did not register yet.
+    pub fn pending(&self) -> Vec<Key> {
+        lock(&self.state)
+            .pending
+            .iter()
+            .filter_map(|(event, _)| match event.event_type {
+                EventType::KeyPress(key) => Some(key),
+                _ => None,
+            })
+            .collect()
+    }
+}
+
+impl EventProcessor for SlowKeys {
+    fn process(&mut self, event: Event, now: Instant, output: &mut Vec<Event>) {
+        let mut state = lock(&self.state);
+        let is_pending = |state: &SlowState, key: Key| {
+            state
+                .pending
+                .iter()
+                .position(|(pending, _)| pending.event_type == EventType::KeyPress(key))
+        };
+        match event.event_type {
+            EventType::KeyPress(key) => {
+                if state.accepted.contains(&key) {
+                    // Key repeat
+                    output.push(event);
+                } else if is_pending(&state, key).is_none() {
+                    let deadline = now + state.delay;
+                    state.pending.push((event, deadline));
+                }
+            }
+            EventType::KeyRelease(key) => {
+                if let Some(index) = is_pending(&state, key) {
+                    state.pending.remove(index);
+                } else {
+                    state.accepted.remove(&key);
+                    output.push(event);
+                }
+            }
+            _ => output.push(event),
+        }
+    }
+
+    fn deadline(&self) -> Option<Instant> {
+        lock(&self.state)
+            .pending
+            .iter()
+            .map(|(_, deadline)| *deadline)
+            .min()
+    }
+
+    fn timeout(&mut self, now: Instant, output: &mut Vec<Event>) {
+        let mut state = lock(&self.state);
+        let (expired, pending) = std::mem::take(&mut state.pending)
+            .into_iter()
+            .partition(|(_, deadline)| *deadline <= now);
+        state.pending = pending;
+        for (event, _) in expired {
+            if let EventType::KeyPress(key) = event.event_type {
+                state.accepted.insert(key);
+            }
+            output.push(event);
+        }
+    }
+}
+
+#[derive(Debug)]
+struct BounceState {
+    delay: Duration,
+    /// When each key was last released, according to the kernel timestamps.
+    released: HashMap<Key, SystemTime>,
+    /// Keys whose press was ignored, their release is too.
+    ignored: HashSet<Key>,
+    bounced: u64,
+}
+
+/// Bounce keys: a key pressed again within the delay after it was released
+/// is ignored, for hands that tremble or press keys twice.
+///
+/// ```no_run
+/// use rdev::BounceKeys;
+/// #[cfg(feature = "unstable_grab")]
+/// use rdev::{Event, Grabber};
+///
+/// let bounce_keys = BounceKeys::new();
+/// #[cfg(feature = "unstable_grab")]
+/// let grabber = Grabber::builder().processor(bounce_keys.clone()).build();
+/// // This will block.
+/// #[cfg(feature = "unstable_grab")]
+/// if let Err(error) = grabber.grab(|event: Event| Some(event)) {
+///     println!("Error: {:?}", error)
+/// }
+/// println!("Ignored {} presses", bounce_keys.bounced());
+/// ```
+#[derive(Debug, Clone)]
+pub struct BounceKeys {
+    state: Arc<Mutex<BounceState>>,
+}
+
+impl Default for BounceKeys {
+    fn default() -> BounceKeys {
+        BounceKeys {
+            state: Arc::new(Mutex::new(BounceState {
+                delay: DEFAULT_BOUNCE_KEYS_DELAY,
+                released: HashMap::new(),
+                ignored: HashSet::new(),
+                bounced: 0,
+            })),
+        }
+    }
+}
+
+impl BounceKeys {
+    /// The delay is 300ms by default.
+    pub fn new() -> BounceKeys {
+        BounceKeys::default()
+    }
+
+    pub fn set_delay(&self, delay: Duration) {
+        lock(&self.state).delay = delay;
+    }
+
+    pub fn delay(&self) -> Duration {
+        lock(&self.state).delay
+    }
+
+    /// Presses ignored so far.
+    pub fn bounced(&self) -> u64 {
+        lock(&self.state).bounced
+    }
+}
+
+impl EventProcessor for BounceKeys {
+    fn process(&mut self, event: Event, _now: Instant, output: &mut Vec<Event>) {
+        let mut state = lock(&self.state);
+        match event.event_type {
+            EventType::KeyPress(key) => {
+                if state.ignored.contains(&key) {
+                    // Key repeat
+                    return;
+                }
+                let bounced = state.released.get(&key).is_some_and(|released| {
+                    event
+                        .time
+                        .duration_since(*released)
+                        .is_ok_and(|elapsed| elapsed < state.delay)
+                });
+                if bounced {
+                    state.ignored.insert(key);
+                    state.bounced += 1;
+                } else {
+                    output.push(event);
+                }
+            }
+            EventType::KeyRelease(key) => {
+                if !state.ignored.remove(&key) {
+                    state.released.insert(key, event.time);
+                    output.push(event);
+                }
+            }
+            _ => output.push(event),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use EventType::{KeyPress as Press, KeyRelease as Release};
+
+    fn run<P: EventProcessor>(processor: &mut P, events: &[(EventType, u64)]) -> Vec<EventType> {
+        let start = Instant::now();
+        let time = SystemTime::now();
+        let mut output = vec![];
+        for (event_type, at) in events {
+            let now = start + Duration::from_millis(*at);
+            if processor.deadline().is_some_and(|deadline| deadline <= now) {
+                processor.timeout(now, &mut output);
+            }
+            let event = Event {
+                time: time + Duration::from_millis(*at),
+                name: None,
+                event_type: *event_type,
+                device_id: None,
+            };
+            processor.process(event, now, &mut output);
+        }
+        output.into_iter().map(|event| event.event_type).collect()
+    }
+
+    #[test]
+    fn test_sticky_keys_latch() {
+        let mut sticky_keys = StickyKeys::new();
+        let output = run(
+            &mut sticky_keys,
+            &[
+                (Press(Key::ShiftLeft), 0),
+                (Release(Key::ShiftLeft), 10),
+                (Press(Key::KeyA), 20),
+                (Release(Key::KeyA), 30),
+                (Press(Key::KeyB), 40),
+                (Release(Key::KeyB), 50),
+            ],
+        );
+        assert_eq!(
+            output,
+            vec![
+                Press(Key::ShiftLeft),
+                Press(Key::KeyA),
+                Release(Key::KeyA),
+                Release(Key::ShiftLeft),
+                Press(Key::KeyB),
+                Release(Key::KeyB),
+            ]
+        );
+        assert!(sticky_keys.latched().is_empty());
+    }
+
+    #[test]
+    fn test_sticky_keys_lock() {
+        let mut sticky_keys = StickyKeys::new();
+        let tap = |at| {
+            [
+                (Press(Key::ControlLeft), at),
+                (Release(Key::ControlLeft), at + 5),
+            ]
+        };
+        let output = run(&mut sticky_keys, &[tap(0), tap(10)].concat());
+        assert_eq!(output, vec![Press(Key::ControlLeft)]);
+        assert_eq!(sticky_keys.locked(), vec![Key::ControlLeft]);
+
+        let output = run(
+            &mut sticky_keys,
+            &[
+                (Press(Key::KeyC), 0),
+                (Release(Key::KeyC), 5),
+                (Press(Key::ControlLeft), 10),
+                (Release(Key::ControlLeft), 15),
+            ],
+        );
+        assert_eq!(
+            output,
+            vec![
+                Press(Key::KeyC),
+                Release(Key::KeyC),
+                Release(Key::ControlLeft)
+            ]
+        );
+        assert!(sticky_keys.locked().is_empty());
+    }
+
+    #[test]
+    fn test_sticky_keys_held() {
+        // Modifiers used as usual do not latch
+        let mut sticky_keys = StickyKeys::new();
+        let output = run(
+            &mut sticky_keys,
+            &[
+                (Press(Key::ShiftLeft), 0),
+                (Press(Key::KeyA), 10),
+                (Release(Key::KeyA), 20),
+                (Release(Key::ShiftLeft), 30),
+            ],
+        );
+        assert_eq!(
+            output,
+            vec![
+                Press(Key::ShiftLeft),
+                Press(Key::KeyA),
+                Release(Key::KeyA),
+                Release(Key::ShiftLeft),
+            ]
+        );
+        assert!(sticky_keys.latched().is_empty());
+    }
+
+    #[test]
+    fn test_slow_keys() {
+        let mut slow_keys = SlowKeys::new();
+        let output = run(
+            &mut slow_keys,
+            &[
+                (Press(Key::KeyA), 0),
+                (Release(Key::KeyA), 100),
+                (Press(Key::KeyB), 200),
+                (Press(Key::KeyB), 400),
+                (Press(Key::KeyB), 600),
+                (Release(Key::KeyB), 700),
+            ],
+        );
+        assert_eq!(
+            output,
+            vec![Press(Key::KeyB), Press(Key::KeyB), Release(Key::KeyB)]
+        );
+        assert!(slow_keys.pending().is_empty());
+    }
+
+    #[test]
+    fn test_bounce_keys() {
+        let mut bounce_keys = BounceKeys::new();
+        let output = run(
+            &mut bounce_keys,
+            &[
+                (Press(Key::KeyA), 0),
+                (Release(Key::KeyA), 50),
+                (Press(Key::KeyA), 100),
+                (Release(Key::KeyA), 150),
+                (Press(Key::KeyA), 500),
+                (Release(Key::KeyA), 550),
+            ],
+        );
+        assert_eq!(
+            output,
+            vec![
+                Press(Key::KeyA),
+                Release(Key::KeyA),
+                Press(Key::KeyA),
+                Release(Key::KeyA),
+            ]
+        );
+        assert_eq!(bounce_keys.bounced(), 1);
+    }
+}
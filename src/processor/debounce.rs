@@ -0,0 +1,384 @@
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex, MutexGuard};
+use std::time::{Duration, Instant, SystemTime};
+
+use crate::processor::{with_type, EventProcessor};
+use crate::rdev::{Event, EventType, Key};
+
+const DEFAULT_WINDOW: Duration = Duration::from_millis(10);
+
+/// When `Debounce` lets a key change through.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash, Default)]
+pub enum DebounceMode {
+    /// Right away, then the key is ignored for the window. Adds no latency.
+    #[default]
+    Eager,
+    /// Once the key stayed the same for the window. Also filters out noise on
+    /// keys that are not pressed, at the cost of the window in latency.
+    Deferred,
+}
+
+/// What `ChatterStats` knows about a key.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Default)]
+pub struct KeyChatter {
+    /// Presses let through.
+    pub presses: u64,
+    /// Presses and releases swallowed as chatter.
+    pub suppressed: u64,
+    /// The shortest time seen between two changes of the key, chatter included,
+    /// according to the kernel timestamps.
+    pub shortest_change: Option<Duration>,
+}
+
+/// Chatter statistics of a `Debounce`, that can be read while grabbing.
+#[derive(Debug, Clone, Default)]
+pub struct ChatterStats {
+    keys: Arc<Mutex<HashMap<Key, KeyChatter>>>,
+}
+
+impl ChatterStats {
+    pub fn get(&self, key: Key) -> KeyChatter {
+        self.lock().get(&key).copied().unwrap_or_default()
+    }
+
+    /// Every key seen so far.
+    pub fn all(&self) -> HashMap<Key, KeyChatter> {
+        self.lock().clone()
+    }
+
+    pub fn reset(&self) {
+        self.lock().clear();
+    }
+
+    fn lock(&self) -> MutexGuard<'_, HashMap<Key, KeyChatter>> {
+        self.keys
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner())
+    }
+}
+
+#[derive(Debug)]
+struct KeyState {
+    mode: DebounceMode,
+    window: Duration,
+    /// Whether the key is physically down.
+    down: bool,
+    /// Whether the key is down for applications.
+    sent: bool,
+    /// The last event seen, to send when the window ends.
+    last: Event,
+    /// The kernel time of the last change that got through.
+    sent_at: Option<SystemTime>,
+    /// When to send a change still held back if no other event comes first.
+    deadline: Option<Instant>,
+}
+
+impl KeyState {
+    /// How much of the window is left at `time`, by the kernel clock so that
+    /// delays in reading the events do not change what is chatter. Eager keys
+    /// ignore changes for the window after one gets through, deferred ones need
+    /// to stay the same for the window after the last change.
+    fn remaining(&self, time: SystemTime) -> Duration {
+        let start = match self.mode {
+            DebounceMode::Eager => self.sent_at,
+            DebounceMode::Deferred => Some(self.last.time),
+        };
+        let elapsed = start.map_or(self.window, |start| {
+            time.duration_since(start).unwrap_or_default()
+        });
+        self.window.saturating_sub(elapsed)
+    }
+
+    /// Every change is counted as suppressed when it comes in, until it gets through.
+    fn send(&mut self, key: Key, stats: &mut KeyChatter, output: &mut Vec<Event>) {
+        self.sent = self.down;
+        self.sent_at = Some(self.last.time);
+        stats.suppressed = stats.suppressed.saturating_sub(1);
+        let event_type = if self.down {
+            stats.presses += 1;
+            EventType::KeyPress(key)
+        } else {
+            EventType::KeyRelease(key)
+        };
+        output.push(with_type(&self.last, event_type));
+    }
+}
+
+/// Filters key chatter: switches that wear out can register a single press as
+/// several presses and releases a few milliseconds apart. Changes of a key within
+/// its debounce window are swallowed, and counted in the `ChatterStats`.
+///
+/// ```no_run
+/// use rdev::{Debounce, DebounceMode, Key};
+/// #[cfg(feature = "unstable_grab")]
+/// use rdev::{Event, Grabber};
+/// use std::time::Duration;
+///
+/// let debounce = Debounce::new(DebounceMode::Eager, Duration::from_millis(8))
+///     .key(Key::KeyE, DebounceMode::Deferred, Duration::from_millis(30));
+/// let stats = debounce.stats();
+/// #[cfg(feature = "unstable_grab")]
+/// let grabber = Grabber::builder().processor(debounce).build();
+/// // This will block.
+/// #[cfg(feature = "unstable_grab")]
+/// if let Err(error) = grabber.grab(|event: Event| Some(event)) {
+///     println!("Error: {:?}", error)
+/// }
+/// println!("E chattered {} times", stats.get(Key::KeyE).suppressed);
+/// ```
+#[derive(Debug)]
+pub struct Debounce {
+    mode: DebounceMode,
+    window: Duration,
+    per_key: HashMap<Key, (DebounceMode, Duration)>,
+    keys: HashMap<Key, KeyState>,
+    stats: ChatterStats,
+}
+
+impl Default for Debounce {
+    fn default() -> Debounce {
+        Debounce::new(DebounceMode::default(), DEFAULT_WINDOW)
+    }
+}
+
+impl Debounce {
+    /// Debounces every key with this mode and window, the default is eager with
+    /// a 10ms window.
+    pub fn new(mode: DebounceMode, window: Duration) -> Debounce {
+        Debounce {
+            mode,
+            window,
+            per_key: HashMap::new(),
+            keys: HashMap::new(),
+            stats: ChatterStats::default(),
+        }
+    }
+
+    /// Uses another mode and window for this key.
+    pub fn key(mut self, key: Key, mode: DebounceMode, window: Duration) -> Debounce {
+        self.per_key.insert(key, (mode, window));
+        self
+    }
+
+    /// Keeps counting while grabbing.
+    pub fn stats(&self) -> ChatterStats {
+        self.stats.clone()
+    }
+}
+
+impl EventProcessor for Debounce {
+    fn process(&mut self, event: Event, now: Instant, output: &mut Vec<Event>) {
+        let (key, pressed) = match event.event_type {
+            EventType::KeyPress(key) => (key, true),
+            EventType::KeyRelease(key) => (key, false),
+            _ => {
+                output.push(event);
+                return;
+            }
+        };
+        if !pressed && !self.keys.contains_key(&key) {
+            // Pressed before the grab started
+            output.push(event);
+            return;
+        }
+        let (mode, window) = self
+            .per_key
+            .get(&key)
+            .copied()
+            .unwrap_or((self.mode, self.window));
+        let state = self.keys.entry(key).or_insert_with(|| KeyState {
+            mode,
+            window,
+            down: false,
+            sent: false,
+            last: event.clone(),
+            sent_at: None,
+            deadline: None,
+        });
+        let mut stats = self.stats.lock();
+        let stats = stats.entry(key).or_default();
+        if state.down == pressed {
+            // Key repeat
+            if pressed && state.sent {
+                output.push(event);
+            }
+            return;
+        }
+        if let Ok(elapsed) = event.time.duration_since(state.last.time) {
+            // Except on the first press, where the last event is this one
+            if state.last.event_type != event.event_type
+                && stats
+                    .shortest_change
+                    .map_or(true, |shortest| elapsed < shortest)
+            {
+                stats.shortest_change = Some(elapsed);
+            }
+        }
+        // A change held back whose window ended before this one, even if the
+        // deadline has not been noticed yet
+        if state.down != state.sent && state.remaining(event.time).is_zero() {
+            state.send(key, stats, output);
+        }
+        state.down = pressed;
+        state.last = event;
+        stats.suppressed += 1;
+        state.deadline = None;
+        if state.down == state.sent {
+            return;
+        }
+        let remaining = state.remaining(state.last.time);
+        if remaining.is_zero() {
+            state.send(key, stats, output);
+        } else {
+            state.deadline = Some(now + remaining);
+        }
+    }
+
+    fn deadline(&self) -> Option<Instant> {
+        self.keys.values().filter_map(|state| state.deadline).min()
+    }
+
+    fn timeout(&mut self, now: Instant, output: &mut Vec<Event>) {
+        let mut stats = self.stats.lock();
+        let mut expired: Vec<(&Key, &mut KeyState)> = self
+            .keys
+            .iter_mut()
+            .filter(|(_, state)| state.deadline.is_some_and(|deadline| deadline <= now))
+            .collect();
+        // In the order the keys changed
+        expired.sort_by_key(|(_, state)| state.last.time);
+        for (key, state) in expired {
+            state.deadline = None;
+            if state.down != state.sent {
+                state.send(*key, stats.entry(*key).or_default(), output);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::SystemTime;
+    use EventType::{KeyPress as Press, KeyRelease as Release};
+
+    fn run(debounce: &mut Debounce, events: &[(EventType, u64)]) -> Vec<EventType> {
+        let start = Instant::now();
+        let time = SystemTime::now();
+        let mut output = vec![];
+        for (event_type, at) in events {
+            let now = start + Duration::from_millis(*at);
+            if debounce.deadline().is_some_and(|deadline| deadline <= now) {
+                debounce.timeout(now, &mut output);
+            }
+            let event = Event {
+                time: time + Duration::from_millis(*at),
+                name: None,
+                event_type: *event_type,
+                device_id: None,
+            };
+            debounce.process(event, now, &mut output);
+        }
+        output.into_iter().map(|event| event.event_type).collect()
+    }
+
+    const CHATTER: &[(EventType, u64)] = &[
+        (Press(Key::KeyE), 0),
+        (Release(Key::KeyE), 3),
+        (Press(Key::KeyE), 5),
+        (Release(Key::KeyE), 100),
+        (Press(Key::KeyA), 200),
+    ];
+
+    #[test]
+    fn test_eager() {
+        let mut debounce = Debounce::default();
+        let stats = debounce.stats();
+        assert_eq!(
+            run(&mut debounce, CHATTER),
+            vec![Press(Key::KeyE), Release(Key::KeyE), Press(Key::KeyA)]
+        );
+        let chatter = stats.get(Key::KeyE);
+        assert_eq!(chatter.presses, 1);
+        assert_eq!(chatter.suppressed, 2);
+        assert_eq!(chatter.shortest_change, Some(Duration::from_millis(2)));
+    }
+
+    #[test]
+    fn test_eager_late_release() {
+        // A tap shorter than the window is not lost
+        let mut debounce = Debounce::default();
+        assert_eq!(
+            run(
+                &mut debounce,
+                &[
+                    (Press(Key::KeyE), 0),
+                    (Release(Key::KeyE), 5),
+                    (Press(Key::KeyA), 50)
+                ]
+            ),
+            vec![Press(Key::KeyE), Release(Key::KeyE), Press(Key::KeyA)]
+        );
+    }
+
+    #[test]
+    fn test_eager_burst() {
+        // Events read late, all at once, are judged on their kernel times
+        let mut debounce = Debounce::default();
+        let now = Instant::now();
+        let time = SystemTime::now();
+        let mut output = vec![];
+        for (event_type, at) in [
+            (Press(Key::KeyE), 0),
+            (Release(Key::KeyE), 3),
+            (Press(Key::KeyE), 20),
+            (Release(Key::KeyE), 22),
+        ] {
+            let event = Event {
+                time: time + Duration::from_millis(at),
+                name: None,
+                event_type,
+                device_id: None,
+            };
+            debounce.process(event, now, &mut output);
+        }
+        let output: Vec<EventType> = output.into_iter().map(|event| event.event_type).collect();
+        assert_eq!(
+            output,
+            vec![Press(Key::KeyE), Release(Key::KeyE), Press(Key::KeyE)]
+        );
+        assert_eq!(debounce.stats().get(Key::KeyE).suppressed, 1);
+    }
+
+    #[test]
+    fn test_deferred() {
+        let mut debounce =
+            Debounce::default().key(Key::KeyE, DebounceMode::Deferred, Duration::from_millis(10));
+        let stats = debounce.stats();
+        assert_eq!(
+            run(&mut debounce, CHATTER),
+            vec![Press(Key::KeyE), Release(Key::KeyE), Press(Key::KeyA)]
+        );
+        assert_eq!(stats.get(Key::KeyE).suppressed, 2);
+    }
+
+    #[test]
+    fn test_deferred_blip() {
+        // A blip shorter than the window never gets through
+        let mut debounce = Debounce::new(DebounceMode::Deferred, Duration::from_millis(10));
+        let stats = debounce.stats();
+        assert_eq!(
+            run(
+                &mut debounce,
+                &[
+                    (Press(Key::KeyE), 0),
+                    (Release(Key::KeyE), 4),
+                    (Press(Key::KeyA), 50)
+                ]
+            ),
+            vec![]
+        );
+        assert_eq!(stats.get(Key::KeyE).suppressed, 2);
+        assert_eq!(stats.get(Key::KeyE).presses, 0);
+    }
+}
@@ -0,0 +1,328 @@
+use std::collections::{HashMap, HashSet};
+use std::time::Instant;
+
+use crate::processor::{with_type, EventProcessor};
+use crate::rdev::{Event, EventType, Key};
+
+/// What a key does on a `Layer`.
+#[derive(Debug, Clone, PartialEq)]
+pub enum LayerAction {
+    /// Sends this key instead.
+    Key(Key),
+    /// Sends these events when the key is pressed, and nothing on release.
+    /// Only key and button events can be sent.
+    Macro(Vec<EventType>),
+    /// Does what the next active layer below says, the key itself below the
+    /// base layer. Keys missing from a layer are transparent.
+    Transparent,
+    /// Swallows the key.
+    Block,
+    /// Turns the layer on while the key is held.
+    Momentary(usize),
+    /// Turns the layer on or off on each press.
+    Toggle(usize),
+    /// Turns the layer on for the next key press only.
+    OneShot(usize),
+}
+
+/// A remap table for `Layers`.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Layer {
+    bindings: HashMap<Key, LayerAction>,
+}
+
+impl Layer {
+    pub fn new() -> Layer {
+        Layer::default()
+    }
+
+    /// Binding a key that already is replaces it.
+    pub fn bind(mut self, key: Key, action: LayerAction) -> Layer {
+        self.bindings.insert(key, action);
+        self
+    }
+
+    /// Sends `to` instead of `key`.
+    pub fn remap(self, key: Key, to: Key) -> Layer {
+        self.bind(key, LayerAction::Key(to))
+    }
+}
+
+/// Keyboard layers, like keyboard firmwares have them: a stack of remap tables
+/// where the first layer is always on and the others are turned on and off by
+/// keys. A key does what the highest active layer binding it says.
+///
+/// Layers are switched when keys are pressed, a key that is held keeps doing what
+/// it did when it was pressed.
+///
+/// ```no_run
+/// use rdev::{EventType, Key, Layer, LayerAction, Layers};
+/// #[cfg(feature = "unstable_grab")]
+/// use rdev::{Event, Grabber};
+///
+/// const NAVIGATION: usize = 1;
+/// let base = Layer::new()
+///     .bind(Key::CapsLock, LayerAction::Momentary(NAVIGATION))
+///     .bind(Key::ScrollLock, LayerAction::Toggle(NAVIGATION));
+/// let navigation = Layer::new()
+///     .remap(Key::KeyH, Key::LeftArrow)
+///     .remap(Key::KeyJ, Key::DownArrow)
+///     .remap(Key::KeyK, Key::UpArrow)
+///     .remap(Key::KeyL, Key::RightArrow)
+///     .bind(
+///         Key::KeyS,
+///         LayerAction::Macro(vec![
+///             EventType::KeyPress(Key::ControlLeft),
+///             EventType::KeyPress(Key::KeyS),
+///             EventType::KeyRelease(Key::KeyS),
+///             EventType::KeyRelease(Key::ControlLeft),
+///         ]),
+///     );
+/// let layers = Layers::new(vec![base, navigation]);
+/// #[cfg(feature = "unstable_grab")]
+/// let grabber = Grabber::builder().processor(layers).build();
+/// // This will block.
+/// #[cfg(feature = "unstable_grab")]
+/// if let Err(error) = grabber.grab(|event: Event| Some(event)) {
+///     println!("Error: {:?}", error)
+/// }
+/// ```
+#[derive(Debug, Clone, Default)]
+pub struct Layers {
+    layers: Vec<Layer>,
+    toggled: HashSet<usize>,
+    one_shot: Option<usize>,
+    /// Keys that are down, with what they did when pressed.
+    pressed: HashMap<Key, LayerAction>,
+}
+
+impl Layers {
+    /// The first layer is the base layer, the others are referred to by their
+    /// index in `layers`.
+    pub fn new(layers: Vec<Layer>) -> Layers {
+        Layers {
+            layers,
+            ..Layers::default()
+        }
+    }
+
+    /// Swaps the remap tables, turning the layers off. Keys that are down are
+    /// still released as they were pressed.
+    #[cfg(feature = "serialize")]
+    pub(crate) fn set_layers(&mut self, layers: Vec<Layer>) {
+        self.layers = layers;
+        self.toggled.clear();
+        self.one_shot = None;
+    }
+
+    /// Whether the layer is on, the base layer always is.
+    pub fn is_active(&self, layer: usize) -> bool {
+        layer == 0
+            || self.toggled.contains(&layer)
+            || self.one_shot == Some(layer)
+            || self
+                .pressed
+                .values()
+                .any(|action| *action == LayerAction::Momentary(layer))
+    }
+
+    fn lookup(&self, key: Key) -> LayerAction {
+        (0..self.layers.len())
+            .rev()
+            .filter(|layer| self.is_active(*layer))
+            .filter_map(|layer| self.layers[layer].bindings.get(&key))
+            .find(|action| **action != LayerAction::Transparent)
+            .cloned()
+            .unwrap_or(LayerAction::Key(key))
+    }
+}
+
+impl EventProcessor for Layers {
+    fn process(&mut self, event: Event, _now: Instant, output: &mut Vec<Event>) {
+        match event.event_type {
+            EventType::KeyPress(key) => {
+                if let Some(action) = self.pressed.get(&key) {
+                    // Key repeat
+                    if let LayerAction::Key(to) = action {
+                        output.push(with_type(&event, EventType::KeyPress(*to)));
+                    }
+                    return;
+                }
+                let action = self.lookup(key);
+                match &action {
+                    LayerAction::Key(to) => {
+                        output.push(with_type(&event, EventType::KeyPress(*to)));
+                    }
+                    LayerAction::Macro(events) => output.extend(
+                        events
+                            .iter()
+                            .map(|event_type| with_type(&event, *event_type)),
+                    ),
+                    LayerAction::Toggle(layer) => {
+                        if !self.toggled.remove(layer) {
+                            self.toggled.insert(*layer);
+                        }
+                    }
+                    LayerAction::OneShot(layer) => self.one_shot = Some(*layer),
+                    LayerAction::Transparent | LayerAction::Block | LayerAction::Momentary(_) => {}
+                }
+                if let LayerAction::Key(_) | LayerAction::Macro(_) | LayerAction::Block = action {
+                    self.one_shot = None;
+                }
+                self.pressed.insert(key, action);
+            }
+            EventType::KeyRelease(key) => match self.pressed.remove(&key) {
+                Some(LayerAction::Key(to)) => {
+                    output.push(with_type(&event, EventType::KeyRelease(to)));
+                }
+                Some(_) => {}
+                // Pressed before the grab started
+                None => output.push(event),
+            },
+            _ => output.push(event),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::SystemTime;
+    use EventType::{KeyPress as Press, KeyRelease as Release};
+
+    fn run(layers: &mut Layers, events: &[EventType]) -> Vec<EventType> {
+        let mut output = vec![];
+        for event_type in events {
+            let event = Event {
+                time: SystemTime::now(),
+                name: None,
+                event_type: *event_type,
+                device_id: None,
+            };
+            layers.process(event, Instant::now(), &mut output);
+        }
+        output.into_iter().map(|event| event.event_type).collect()
+    }
+
+    fn layers() -> Layers {
+        let base = Layer::new()
+            .bind(Key::CapsLock, LayerAction::Momentary(1))
+            .bind(Key::ScrollLock, LayerAction::Toggle(1))
+            .bind(Key::Pause, LayerAction::OneShot(1))
+            .bind(Key::Insert, LayerAction::Block);
+        let navigation = Layer::new()
+            .remap(Key::KeyH, Key::LeftArrow)
+            .bind(Key::KeyJ, LayerAction::Transparent)
+            .bind(
+                Key::KeyS,
+                LayerAction::Macro(vec![
+                    Press(Key::ControlLeft),
+                    Press(Key::KeyS),
+                    Release(Key::KeyS),
+                    Release(Key::ControlLeft),
+                ]),
+            );
+        Layers::new(vec![base, navigation])
+    }
+
+    #[test]
+    fn test_base_layer() {
+        let output = run(
+            &mut layers(),
+            &[
+                Press(Key::KeyH),
+                Release(Key::KeyH),
+                Press(Key::Insert),
+                Release(Key::Insert),
+            ],
+        );
+        assert_eq!(output, vec![Press(Key::KeyH), Release(Key::KeyH)]);
+    }
+
+    #[test]
+    fn test_momentary() {
+        let mut layers = layers();
+        let output = run(
+            &mut layers,
+            &[
+                Press(Key::CapsLock),
+                Press(Key::KeyH),
+                Press(Key::KeyH),
+                Press(Key::KeyJ),
+                Release(Key::KeyJ),
+            ],
+        );
+        assert_eq!(
+            output,
+            vec![
+                Press(Key::LeftArrow),
+                Press(Key::LeftArrow),
+                Press(Key::KeyJ),
+                Release(Key::KeyJ),
+            ]
+        );
+        assert!(layers.is_active(1));
+
+        // Keys held when the layer goes away are released as they were pressed
+        let output = run(
+            &mut layers,
+            &[Release(Key::CapsLock), Release(Key::KeyH), Press(Key::KeyH)],
+        );
+        assert_eq!(output, vec![Release(Key::LeftArrow), Press(Key::KeyH)]);
+        assert!(!layers.is_active(1));
+    }
+
+    #[test]
+    fn test_toggle_and_macro() {
+        let mut layers = layers();
+        let output = run(
+            &mut layers,
+            &[
+                Press(Key::ScrollLock),
+                Release(Key::ScrollLock),
+                Press(Key::KeyS),
+                Release(Key::KeyS),
+            ],
+        );
+        assert_eq!(
+            output,
+            vec![
+                Press(Key::ControlLeft),
+                Press(Key::KeyS),
+                Release(Key::KeyS),
+                Release(Key::ControlLeft),
+            ]
+        );
+        run(
+            &mut layers,
+            &[Press(Key::ScrollLock), Release(Key::ScrollLock)],
+        );
+        assert!(!layers.is_active(1));
+    }
+
+    #[test]
+    fn test_one_shot() {
+        let mut layers = layers();
+        let output = run(
+            &mut layers,
+            &[
+                Press(Key::Pause),
+                Release(Key::Pause),
+                Press(Key::KeyH),
+                Press(Key::KeyL),
+                Release(Key::KeyH),
+                Release(Key::KeyL),
+            ],
+        );
+        assert_eq!(
+            output,
+            vec![
+                Press(Key::LeftArrow),
+                Press(Key::KeyL),
+                Release(Key::LeftArrow),
+                Release(Key::KeyL),
+            ]
+        );
+        assert!(!layers.is_active(1));
+    }
+}
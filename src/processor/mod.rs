@@ -0,0 +1,113 @@
+mod accessibility;
+mod debounce;
+mod layers;
+#[cfg(feature = "serialize")]
+mod remap;
+mod socd;
+mod tap_hold;
+
+pub use crate::processor::accessibility::{BounceKeys, SlowKeys, StickyKeys};
+pub use crate::processor::debounce::{ChatterStats, Debounce, DebounceMode, KeyChatter};
+pub use crate::processor::layers::{Layer, LayerAction, Layers};
+#[cfg(feature = "serialize")]
+pub use crate::processor::remap::{
+    BindingConfig, DeviceConfig, LayerConfig, RemapConfig, Remapper,
+};
+pub use crate::processor::socd::{Socd, SocdMode};
+pub use crate::processor::tap_hold::TapHold;
+
+use crate::rdev::{DeviceIdentity, Event, EventType};
+use std::time::Instant;
+
+/// A stage of the grab pipeline, rewriting key and button events before they
+/// reach the grab callback and the virtual output devices. Register them with
+/// `GrabberBuilder::processor`, they run in registration order, each one
+/// getting the output of the previous one. (Linux evdev grab only)
+///
+/// Only `KeyPress`, `KeyRelease`, `ButtonPress` and `ButtonRelease` go through
+/// processors, other events are forwarded as is. Processors get events without
+/// `Event.name`, it is filled in once they are done.
+pub trait EventProcessor: Send {
+    /// Handles an event received at `now`, pushing the events to send on to
+    /// `output`. Pushing nothing swallows the event, pushing it later (from
+    /// `timeout` or another call) delays it.
+    fn process(&mut self, event: Event, now: Instant, output: &mut Vec<Event>);
+
+    /// When `timeout` should be called, if the processor is waiting on time.
+    fn deadline(&self) -> Option<Instant> {
+        None
+    }
+
+    /// Called once `deadline` is reached, events pushed to `output` are sent
+    /// right away.
+    fn timeout(&mut self, _now: Instant, _output: &mut Vec<Event>) {}
+
+    /// Called when the grab opens a device, before any of its events, with the
+    /// `Event.device_id` they will have.
+    fn device_added(&mut self, _device_id: u32, _identity: &DeviceIdentity) {}
+}
+
+/// The same event, standing for another key or button.
+fn with_type(event: &Event, event_type: EventType) -> Event {
+    Event {
+        event_type,
+        ..event.clone()
+    }
+}
+
+/// Runs processors one after the other.
+#[cfg(all(target_os = "linux", feature = "unstable_grab"))]
+#[derive(Default)]
+pub(crate) struct Pipeline {
+    stages: Vec<Box<dyn EventProcessor>>,
+}
+
+#[cfg(all(target_os = "linux", feature = "unstable_grab"))]
+impl Pipeline {
+    pub(crate) fn new(stages: Vec<Box<dyn EventProcessor>>) -> Pipeline {
+        Pipeline { stages }
+    }
+
+    pub(crate) fn is_empty(&self) -> bool {
+        self.stages.is_empty()
+    }
+
+    pub(crate) fn process(&mut self, event: Event, now: Instant) -> Vec<Event> {
+        self.stages.iter_mut().fold(vec![event], |events, stage| {
+            let mut output = Vec::new();
+            for event in events {
+                stage.process(event, now, &mut output);
+            }
+            output
+        })
+    }
+
+    /// Runs the timeouts that are due, the events they release go through the
+    /// following stages.
+    pub(crate) fn timeout(&mut self, now: Instant) -> Vec<Event> {
+        self.stages.iter_mut().fold(Vec::new(), |released, stage| {
+            let mut output = Vec::new();
+            if stage.deadline().is_some_and(|deadline| deadline <= now) {
+                stage.timeout(now, &mut output);
+            }
+            // Then what earlier stages just released
+            for event in released {
+                stage.process(event, now, &mut output);
+            }
+            output
+        })
+    }
+
+    pub(crate) fn device_added(&mut self, device_id: u32, identity: &DeviceIdentity) {
+        for stage in &mut self.stages {
+            stage.device_added(device_id, identity);
+        }
+    }
+
+    pub(crate) fn deadline(&self) -> Option<Instant> {
+        self.stages
+            .iter()
+            .filter_map(|stage| stage.deadline())
+            .min()
+    }
+}
@@ -0,0 +1,495 @@
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashMap};
+use std::str::FromStr;
+use std::sync::{Arc, Mutex, MutexGuard};
+use std::time::Instant;
+
+use crate::hotkey::KeyCombo;
+use crate::processor::{with_type, EventProcessor, Layer, LayerAction, Layers};
+use crate::rdev::{Button, ConfigError, DeviceIdentity, Event, EventType, Key, ParseKeyError};
+
+/// A remap profile, meant to be read from a file with any serde format. Keys and
+/// buttons are written with their names, see `Key::from_str` and `Button::from_str`.
+///
+/// Simple remaps and swaps happen first, then the layer bindings apply to the
+/// keys they produce. Every field can be left out.
+///
+/// ```toml
+/// # CapsLock sends Escape, Escape sends CapsLock
+/// swap = [["CapsLock", "Escape"]]
+///
+/// [remap]
+/// MouseBack = "Alt"
+///
+/// # Bindings of the base layer
+/// [bindings]
+/// RightAlt = { momentary = "navigation" }
+///
+/// [[layers]]
+/// name = "navigation"
+/// bindings = { H = { key = "Left" }, L = { key = "Right" }, S = { macro = ["Ctrl+S"] } }
+///
+/// # Rules for some devices only, the ones matching the fields given
+/// [[devices]]
+/// vendor = 0x046d
+/// product = 0xc52b
+/// swap = [["Alt", "Meta"]]
+/// ```
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct RemapConfig {
+    /// Keys or buttons sending another key or button.
+    pub remap: BTreeMap<String, String>,
+    /// Pairs of keys or buttons sending each other, like modifier swaps.
+    pub swap: Vec<(String, String)>,
+    /// Bindings of the base layer, the one that is always on.
+    pub bindings: BTreeMap<String, BindingConfig>,
+    pub layers: Vec<LayerConfig>,
+    /// Remaps and swaps replacing the ones above on some devices.
+    pub devices: Vec<DeviceConfig>,
+}
+
+/// A layer of a `RemapConfig`, referred to by its name.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct LayerConfig {
+    pub name: String,
+    pub bindings: BTreeMap<String, BindingConfig>,
+}
+
+/// Remaps and swaps for the devices matching every field given of their
+/// `DeviceIdentity`, the first matching `DeviceConfig` applying. Fields left out
+/// match any device.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct DeviceConfig {
+    pub name: Option<String>,
+    pub vendor: Option<u16>,
+    pub product: Option<u16>,
+    pub phys: Option<String>,
+    pub remap: BTreeMap<String, String>,
+    pub swap: Vec<(String, String)>,
+}
+
+impl DeviceConfig {
+    fn matches(&self, identity: &DeviceIdentity) -> bool {
+        self.name
+            .as_ref()
+            .map_or(true, |name| *name == identity.name)
+            && self.vendor.map_or(true, |vendor| vendor == identity.vendor)
+            && self
+                .product
+                .map_or(true, |product| product == identity.product)
+            && self
+                .phys
+                .as_ref()
+                .map_or(true, |phys| Some(phys) == identity.phys.as_ref())
+    }
+}
+
+/// A `LayerAction` in a `RemapConfig`, with layers referred to by name.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum BindingConfig {
+    Key(String),
+    /// Key combinations typed one after the other, like `["Ctrl+C", "Ctrl+V"]`.
+    Macro(Vec<String>),
+    Transparent,
+    Block,
+    Momentary(String),
+    Toggle(String),
+    OneShot(String),
+}
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
+enum Input {
+    Key(Key),
+    Button(Button),
+}
+
+impl Input {
+    fn event_type(self, pressed: bool) -> EventType {
+        match (self, pressed) {
+            (Input::Key(key), true) => EventType::KeyPress(key),
+            (Input::Key(key), false) => EventType::KeyRelease(key),
+            (Input::Button(button), true) => EventType::ButtonPress(button),
+            (Input::Button(button), false) => EventType::ButtonRelease(button),
+        }
+    }
+}
+
+impl FromStr for Input {
+    type Err = ParseKeyError;
+
+    fn from_str(s: &str) -> Result<Input, ParseKeyError> {
+        s.parse()
+            .map(Input::Key)
+            .or_else(|_| s.parse().map(Input::Button))
+    }
+}
+
+type Remaps = HashMap<Input, Input>;
+
+fn remaps(
+    remap: &BTreeMap<String, String>,
+    swap: &[(String, String)],
+) -> Result<Remaps, ConfigError> {
+    let mut remaps = HashMap::new();
+    for (from, to) in remap {
+        remaps.insert(from.parse()?, to.parse()?);
+    }
+    for (first, second) in swap {
+        let (first, second) = (first.parse()?, second.parse()?);
+        remaps.insert(first, second);
+        remaps.insert(second, first);
+    }
+    Ok(remaps)
+}
+
+/// Presses the modifiers, taps the key and releases the modifiers, for each combo.
+fn macro_events(combos: &[String]) -> Result<Vec<EventType>, ConfigError> {
+    let mut events = vec![];
+    for combo in combos {
+        let combo: KeyCombo = combo.parse()?;
+        events.extend(combo.modifiers.iter().copied().map(EventType::KeyPress));
+        events.push(EventType::KeyPress(combo.key));
+        events.push(EventType::KeyRelease(combo.key));
+        events.extend(
+            combo
+                .modifiers
+                .iter()
+                .rev()
+                .copied()
+                .map(EventType::KeyRelease),
+        );
+    }
+    Ok(events)
+}
+
+fn layer(
+    bindings: &BTreeMap<String, BindingConfig>,
+    names: &HashMap<&str, usize>,
+) -> Result<Layer, ConfigError> {
+    let index = |name: &String| {
+        names
+            .get(name.as_str())
+            .copied()
+            .ok_or_else(|| ConfigError::UnknownLayer(name.clone()))
+    };
+    bindings
+        .iter()
+        .try_fold(Layer::new(), |layer, (key, binding)| {
+            let action = match binding {
+                BindingConfig::Key(to) => LayerAction::Key(to.parse()?),
+                BindingConfig::Macro(combos) => LayerAction::Macro(macro_events(combos)?),
+                BindingConfig::Transparent => LayerAction::Transparent,
+                BindingConfig::Block => LayerAction::Block,
+                BindingConfig::Momentary(name) => LayerAction::Momentary(index(name)?),
+                BindingConfig::Toggle(name) => LayerAction::Toggle(index(name)?),
+                BindingConfig::OneShot(name) => LayerAction::OneShot(index(name)?),
+            };
+            Ok(layer.bind(key.parse()?, action))
+        })
+}
+
+#[derive(Default)]
+struct State {
+    remaps: Remaps,
+    /// The device rules of the configuration, in order.
+    device_rules: Vec<(DeviceConfig, Remaps)>,
+    /// The identity of the devices the grab opened, by `Event.device_id`.
+    identities: HashMap<u32, DeviceIdentity>,
+    /// The rules of each device with matching ones.
+    devices: HashMap<u32, Remaps>,
+    layers: Layers,
+    /// Keys and buttons that are down, with the device they are on and what they
+    /// were remapped to.
+    pressed: HashMap<(Option<u32>, Input), Input>,
+}
+
+impl State {
+    fn resolve(&mut self, device_id: u32) {
+        let rules = self.identities.get(&device_id).and_then(|identity| {
+            self.device_rules
+                .iter()
+                .find(|(config, _)| config.matches(identity))
+        });
+        match rules {
+            Some((_, remaps)) => {
+                let remaps = remaps.clone();
+                self.devices.insert(device_id, remaps);
+            }
+            None => {
+                self.devices.remove(&device_id);
+            }
+        }
+    }
+
+    fn remap(&self, device_id: Option<u32>, input: Input) -> Input {
+        let remaps = device_id
+            .and_then(|device_id| self.devices.get(&device_id))
+            .unwrap_or(&self.remaps);
+        remaps.get(&input).copied().unwrap_or(input)
+    }
+}
+
+/// Runs a `RemapConfig` in the grab pipeline. Like `HotkeyManager`, the remapper
+/// is cheap to clone and clones share their state, so a clone kept aside can load
+/// another configuration while grabbing. Keys that are down when it does are
+/// still released as they were pressed.
+///
+/// ```no_run
+/// use rdev::{RemapConfig, Remapper};
+/// #[cfg(feature = "unstable_grab")]
+/// use rdev::{Event, Grabber};
+///
+/// let config: RemapConfig =
+///     serde_json::from_str(r#"{"swap": [["CapsLock", "Escape"]]}"#).unwrap();
+/// let remapper = Remapper::new();
+/// remapper.load(&config).unwrap();
+/// #[cfg(feature = "unstable_grab")]
+/// let grabber = Grabber::builder().processor(remapper.clone()).build();
+/// // This will block.
+/// #[cfg(feature = "unstable_grab")]
+/// if let Err(error) = grabber.grab(|event: Event| Some(event)) {
+///     println!("Error: {:?}", error)
+/// }
+/// ```
+#[derive(Clone, Default)]
+pub struct Remapper {
+    state: Arc<Mutex<State>>,
+}
+
+impl Remapper {
+    /// A remapper leaving every key as is until a configuration is loaded.
+    pub fn new() -> Remapper {
+        Remapper::default()
+    }
+
+    /// Replaces the running configuration, or keeps it when this one has errors.
+    pub fn load(&self, config: &RemapConfig) -> Result<(), ConfigError> {
+        let mut names = HashMap::new();
+        for (index, layer) in config.layers.iter().enumerate() {
+            names.insert(layer.name.as_str(), index + 1);
+        }
+        let mut layers = vec![layer(&config.bindings, &names)?];
+        for config in &config.layers {
+            layers.push(layer(&config.bindings, &names)?);
+        }
+        let mut device_rules = vec![];
+        for device in &config.devices {
+            device_rules.push((device.clone(), remaps(&device.remap, &device.swap)?));
+        }
+        let remaps = remaps(&config.remap, &config.swap)?;
+
+        let mut state = self.lock();
+        state.remaps = remaps;
+        state.device_rules = device_rules;
+        let device_ids: Vec<u32> = state.identities.keys().copied().collect();
+        for device_id in device_ids {
+            state.resolve(device_id);
+        }
+        state.layers.set_layers(layers);
+        Ok(())
+    }
+
+    fn lock(&self) -> MutexGuard<'_, State> {
+        self.state
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner())
+    }
+}
+
+impl EventProcessor for Remapper {
+    fn process(&mut self, event: Event, now: Instant, output: &mut Vec<Event>) {
+        let (input, pressed) = match event.event_type {
+            EventType::KeyPress(key) => (Input::Key(key), true),
+            EventType::KeyRelease(key) => (Input::Key(key), false),
+            EventType::ButtonPress(button) => (Input::Button(button), true),
+            EventType::ButtonRelease(button) => (Input::Button(button), false),
+            _ => {
+                output.push(event);
+                return;
+            }
+        };
+        let mut state = self.lock();
+        let held = (event.device_id, input);
+        let to = if pressed {
+            let to = state
+                .pressed
+                .get(&held)
+                .copied()
+                .unwrap_or_else(|| state.remap(event.device_id, input));
+            state.pressed.insert(held, to);
+            to
+        } else {
+            state.pressed.remove(&held).unwrap_or(input)
+        };
+        let event = with_type(&event, to.event_type(pressed));
+        state.layers.process(event, now, output);
+    }
+
+    fn device_added(&mut self, device_id: u32, identity: &DeviceIdentity) {
+        let mut state = self.lock();
+        state.identities.insert(device_id, identity.clone());
+        state.resolve(device_id);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::SystemTime;
+    use EventType::{KeyPress as Press, KeyRelease as Release};
+
+    fn run(remapper: &mut Remapper, events: &[(EventType, Option<u32>)]) -> Vec<EventType> {
+        let mut output = vec![];
+        for (event_type, device_id) in events {
+            let event = Event {
+                time: SystemTime::now(),
+                name: None,
+                event_type: *event_type,
+                device_id: *device_id,
+            };
+            remapper.process(event, Instant::now(), &mut output);
+        }
+        output.into_iter().map(|event| event.event_type).collect()
+    }
+
+    fn config() -> RemapConfig {
+        serde_json::from_str(
+            r#"{
+                "remap": {"MouseBack": "Alt"},
+                "swap": [["CapsLock", "Esc"]],
+                "bindings": {"RightAlt": {"momentary": "navigation"}},
+                "layers": [{
+                    "name": "navigation",
+                    "bindings": {"H": {"key": "Left"}, "S": {"macro": ["Ctrl+S"]}}
+                }],
+                "devices": [{"vendor": 1133, "swap": [["Alt", "Meta"]]}]
+            }"#,
+        )
+        .unwrap()
+    }
+
+    fn identity(vendor: u16) -> DeviceIdentity {
+        DeviceIdentity {
+            name: "Keyboard".to_string(),
+            vendor,
+            product: 1,
+            phys: None,
+        }
+    }
+
+    #[test]
+    fn test_remaps_and_swaps() {
+        let mut remapper = Remapper::new();
+        remapper.load(&config()).unwrap();
+        remapper.device_added(3, &identity(1133));
+        let output = run(
+            &mut remapper,
+            &[
+                (Press(Key::CapsLock), None),
+                (Release(Key::CapsLock), None),
+                (Press(Key::Escape), None),
+                (EventType::ButtonPress(Button::Backward), None),
+                (Press(Key::Alt), Some(3)),
+            ],
+        );
+        assert_eq!(
+            output,
+            vec![
+                Press(Key::Escape),
+                Release(Key::Escape),
+                Press(Key::CapsLock),
+                Press(Key::Alt),
+                Press(Key::MetaLeft),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_layers() {
+        let mut remapper = Remapper::new();
+        remapper.load(&config()).unwrap();
+        let output = run(
+            &mut remapper,
+            &[
+                (Press(Key::AltGr), None),
+                (Press(Key::KeyH), None),
+                (Release(Key::KeyH), None),
+                (Press(Key::KeyS), None),
+                (Release(Key::KeyS), None),
+                (Release(Key::AltGr), None),
+                (Press(Key::KeyH), None),
+            ],
+        );
+        assert_eq!(
+            output,
+            vec![
+                Press(Key::LeftArrow),
+                Release(Key::LeftArrow),
+                Press(Key::ControlLeft),
+                Press(Key::KeyS),
+                Release(Key::KeyS),
+                Release(Key::ControlLeft),
+                Press(Key::KeyH),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_device_rules() {
+        let mut remapper = Remapper::new();
+        // Devices opened before loading get their rules too
+        remapper.device_added(3, &identity(1133));
+        remapper.device_added(4, &identity(1));
+        remapper.load(&config()).unwrap();
+        let output = run(
+            &mut remapper,
+            &[(Press(Key::Alt), Some(3)), (Press(Key::Alt), Some(4))],
+        );
+        assert_eq!(output, vec![Press(Key::MetaLeft), Press(Key::Alt)]);
+
+        let mut config = config();
+        config.devices[0].name = Some("Mouse".to_string());
+        remapper.load(&config).unwrap();
+        let output = run(&mut remapper, &[(Press(Key::MetaLeft), Some(3))]);
+        assert_eq!(output, vec![Press(Key::MetaLeft)]);
+    }
+
+    #[test]
+    fn test_reload_while_held() {
+        let mut remapper = Remapper::new();
+        remapper.load(&config()).unwrap();
+        let output = run(&mut remapper, &[(Press(Key::CapsLock), None)]);
+        assert_eq!(output, vec![Press(Key::Escape)]);
+
+        remapper.clone().load(&RemapConfig::default()).unwrap();
+        let output = run(
+            &mut remapper,
+            &[(Release(Key::CapsLock), None), (Press(Key::CapsLock), None)],
+        );
+        assert_eq!(output, vec![Release(Key::Escape), Press(Key::CapsLock)]);
+    }
+
+    #[test]
+    fn test_errors() {
+        let remapper = Remapper::new();
+        let mut config = config();
+        config.remap.insert("Nope".to_string(), "A".to_string());
+        assert_eq!(
+            remapper.load(&config),
+            Err(ConfigError::Key(ParseKeyError::new("Nope")))
+        );
+        let mut config = RemapConfig::default();
+        config.bindings.insert(
+            "CapsLock".to_string(),
+            BindingConfig::Toggle("missing".to_string()),
+        );
+        assert_eq!(
+            remapper.load(&config),
+            Err(ConfigError::UnknownLayer("missing".to_string()))
+        );
+    }
+}
@@ -0,0 +1,223 @@
+use std::collections::HashMap;
+use std::time::Instant;
+
+use crate::processor::{with_type, EventProcessor};
+use crate::rdev::{Event, EventType, Key};
+
+/// How `Socd` resolves both keys of a pair being held.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash, Default)]
+pub enum SocdMode {
+    /// The key pressed last wins, the other one comes back when it is released.
+    /// This is what Snap Tap does.
+    #[default]
+    LastInput,
+    /// The key pressed first wins until it is released.
+    FirstInput,
+    /// Neither key is held.
+    Neutral,
+}
+
+#[derive(Debug)]
+struct Pair {
+    keys: [Key; 2],
+    /// Whether each key is physically down.
+    down: [bool; 2],
+    /// Whether each key is down for applications.
+    sent: [bool; 2],
+    /// The key pressed last.
+    last: usize,
+}
+
+impl Pair {
+    fn wanted(&self, mode: SocdMode) -> [bool; 2] {
+        match (self.down, mode) {
+            ([true, true], SocdMode::LastInput) => [self.last == 0, self.last == 1],
+            ([true, true], SocdMode::FirstInput) => [self.last == 1, self.last == 0],
+            ([true, true], SocdMode::Neutral) => [false, false],
+            (down, _) => down,
+        }
+    }
+}
+
+/// Cleans Simultaneous Opposing Cardinal Directions: keys paired like A/D or W/S
+/// are never held together, the mode decides which one is held instead. The
+/// other one is released, and pressed again once the winner is released if it
+/// is still held.
+///
+/// ```no_run
+/// use rdev::{Key, Socd, SocdMode};
+/// #[cfg(feature = "unstable_grab")]
+/// use rdev::{Event, Grabber};
+///
+/// let socd = Socd::new(SocdMode::LastInput)
+///     .pair(Key::KeyA, Key::KeyD)
+///     .pair(Key::KeyW, Key::KeyS);
+/// #[cfg(feature = "unstable_grab")]
+/// let grabber = Grabber::builder().processor(socd).build();
+/// // This will block.
+/// #[cfg(feature = "unstable_grab")]
+/// if let Err(error) = grabber.grab(|event: Event| Some(event)) {
+///     println!("Error: {:?}", error)
+/// }
+/// ```
+#[derive(Debug, Default)]
+pub struct Socd {
+    mode: SocdMode,
+    pairs: Vec<Pair>,
+    /// The pair of each key, and its place in the pair.
+    keys: HashMap<Key, (usize, usize)>,
+}
+
+impl Socd {
+    pub fn new(mode: SocdMode) -> Socd {
+        Socd {
+            mode,
+            ..Socd::default()
+        }
+    }
+
+    /// Pairs two opposing keys. A key is only in one pair, pairing it again
+    /// moves it out of its previous pair.
+    pub fn pair(mut self, first: Key, second: Key) -> Socd {
+        let index = self.pairs.len();
+        self.pairs.push(Pair {
+            keys: [first, second],
+            down: [false, false],
+            sent: [false, false],
+            last: 0,
+        });
+        self.keys.insert(first, (index, 0));
+        self.keys.insert(second, (index, 1));
+        self
+    }
+}
+
+impl EventProcessor for Socd {
+    fn process(&mut self, event: Event, _now: Instant, output: &mut Vec<Event>) {
+        let (key, pressed) = match event.event_type {
+            EventType::KeyPress(key) => (key, true),
+            EventType::KeyRelease(key) => (key, false),
+            _ => {
+                output.push(event);
+                return;
+            }
+        };
+        let (pair, side) = match self.keys.get(&key) {
+            Some(place) => *place,
+            None => {
+                output.push(event);
+                return;
+            }
+        };
+        let pair = &mut self.pairs[pair];
+        if pressed && pair.down[side] {
+            // Key repeat
+            if pair.sent[side] {
+                output.push(event);
+            }
+            return;
+        }
+        pair.down[side] = pressed;
+        if pressed {
+            pair.last = side;
+        }
+        let wanted = pair.wanted(self.mode);
+        // Releases first, so opposing keys are never seen held together
+        for pressed in [false, true] {
+            for side in [0, 1] {
+                if wanted[side] == pressed && pair.sent[side] != pressed {
+                    pair.sent[side] = pressed;
+                    let event_type = if pressed {
+                        EventType::KeyPress(pair.keys[side])
+                    } else {
+                        EventType::KeyRelease(pair.keys[side])
+                    };
+                    output.push(with_type(&event, event_type));
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::SystemTime;
+    use EventType::{KeyPress as Press, KeyRelease as Release};
+
+    fn run(socd: &mut Socd, events: &[EventType]) -> Vec<EventType> {
+        let mut output = vec![];
+        for event_type in events {
+            let event = Event {
+                time: SystemTime::now(),
+                name: None,
+                event_type: *event_type,
+                device_id: None,
+            };
+            socd.process(event, Instant::now(), &mut output);
+        }
+        output.into_iter().map(|event| event.event_type).collect()
+    }
+
+    const STRAFE: &[EventType] = &[
+        Press(Key::KeyA),
+        Press(Key::KeyD),
+        Press(Key::KeyD),
+        Release(Key::KeyD),
+        Release(Key::KeyA),
+    ];
+
+    #[test]
+    fn test_last_input() {
+        let mut socd = Socd::new(SocdMode::LastInput).pair(Key::KeyA, Key::KeyD);
+        assert_eq!(
+            run(&mut socd, STRAFE),
+            vec![
+                Press(Key::KeyA),
+                Release(Key::KeyA),
+                Press(Key::KeyD),
+                Press(Key::KeyD),
+                Release(Key::KeyD),
+                Press(Key::KeyA),
+                Release(Key::KeyA),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_first_input() {
+        let mut socd = Socd::new(SocdMode::FirstInput).pair(Key::KeyA, Key::KeyD);
+        assert_eq!(
+            run(&mut socd, STRAFE),
+            vec![Press(Key::KeyA), Release(Key::KeyA)]
+        );
+    }
+
+    #[test]
+    fn test_neutral() {
+        let mut socd = Socd::new(SocdMode::Neutral).pair(Key::KeyA, Key::KeyD);
+        assert_eq!(
+            run(&mut socd, STRAFE),
+            vec![
+                Press(Key::KeyA),
+                Release(Key::KeyA),
+                Press(Key::KeyA),
+                Release(Key::KeyA),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_unpaired_keys() {
+        let mut socd = Socd::default()
+            .pair(Key::KeyA, Key::KeyD)
+            .pair(Key::KeyA, Key::KeyW);
+        assert_eq!(
+            run(
+                &mut socd,
+                &[Press(Key::KeyD), Press(Key::KeyA), Press(Key::KeyS)]
+            ),
+            vec![Press(Key::KeyD), Press(Key::KeyA), Press(Key::KeyS)]
+        );
+    }
+}
@@ -0,0 +1,429 @@
+use std::collections::HashMap;
+use std::time::{Duration, Instant};
+
+use crate::processor::{with_type, EventProcessor};
+use crate::rdev::{Event, EventType, Key};
+
+const DEFAULT_TAPPING_TERM: Duration = Duration::from_millis(200);
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+struct Binding {
+    tap: Key,
+    hold: Key,
+}
+
+/// A dual-role key that was pressed but is not yet known to be a tap or a hold.
+struct Pending {
+    key: Key,
+    press: Event,
+    deadline: Instant,
+    /// Events that came in since, with when they came in.
+    buffered: Vec<(Event, Instant)>,
+}
+
+/// Dual-role keys: a key acts as one key when tapped and as another one when
+/// held, like CapsLock sending Escape on tap and Control on hold, or home row
+/// modifiers.
+///
+/// A key held for longer than the tapping term is a hold, a key released before
+/// is a tap. Events coming in while it is undecided are held back, and sent on
+/// in their original order once it is. Pressing another key before the tapping
+/// term expires does not make it a hold by default, so fast typing is not
+/// mistaken for a shortcut, see `permissive_hold` and `hold_on_other_key_press`
+/// to decide sooner.
+///
+/// ```no_run
+/// use rdev::{Key, TapHold};
+/// #[cfg(feature = "unstable_grab")]
+/// use rdev::{Event, Grabber};
+/// use std::time::Duration;
+///
+/// let tap_hold = TapHold::new()
+///     .tapping_term(Duration::from_millis(180))
+///     .permissive_hold(true)
+///     .bind(Key::CapsLock, Key::Escape, Key::ControlLeft)
+///     .bind(Key::KeyF, Key::KeyF, Key::ShiftLeft);
+/// #[cfg(feature = "unstable_grab")]
+/// let grabber = Grabber::builder().processor(tap_hold).build();
+/// // This will block.
+/// #[cfg(feature = "unstable_grab")]
+/// if let Err(error) = grabber.grab(|event: Event| Some(event)) {
+///     println!("Error: {:?}", error)
+/// }
+/// ```
+pub struct TapHold {
+    bindings: HashMap<Key, Binding>,
+    tapping_term: Duration,
+    permissive_hold: bool,
+    hold_on_other_key_press: bool,
+    pending: Option<Pending>,
+    /// Dual-role keys decided as holds and still down, with the key they hold.
+    held: HashMap<Key, Key>,
+}
+
+impl Default for TapHold {
+    fn default() -> TapHold {
+        TapHold {
+            bindings: HashMap::new(),
+            tapping_term: DEFAULT_TAPPING_TERM,
+            permissive_hold: false,
+            hold_on_other_key_press: false,
+            pending: None,
+            held: HashMap::new(),
+        }
+    }
+}
+
+impl TapHold {
+    pub fn new() -> TapHold {
+        TapHold::default()
+    }
+
+    /// Makes `key` send `tap` when tapped and `hold` when held. Binding a key
+    /// that already is replaces it.
+    pub fn bind(mut self, key: Key, tap: Key, hold: Key) -> TapHold {
+        self.bindings.insert(key, Binding { tap, hold });
+        self
+    }
+
+    /// How long a key must be held to be a hold, 200ms by default.
+    pub fn tapping_term(mut self, tapping_term: Duration) -> TapHold {
+        self.tapping_term = tapping_term;
+        self
+    }
+
+    /// A hold as soon as another key is pressed and released while the dual-role
+    /// key is down, even within the tapping term.
+    pub fn permissive_hold(mut self, permissive_hold: bool) -> TapHold {
+        self.permissive_hold = permissive_hold;
+        self
+    }
+
+    /// A hold as soon as another key is pressed while the dual-role key is down,
+    /// even within the tapping term. Best for keys that are never rolled over
+    /// while typing, like CapsLock.
+    pub fn hold_on_other_key_press(mut self, hold_on_other_key_press: bool) -> TapHold {
+        self.hold_on_other_key_press = hold_on_other_key_press;
+        self
+    }
+
+    /// Sends the dual-role key as a hold, then the events held back behind it.
+    fn resolve_hold(&mut self, output: &mut Vec<Event>) {
+        if let Some(pending) = self.pending.take() {
+            let hold = self.bindings[&pending.key].hold;
+            self.held.insert(pending.key, hold);
+            output.push(with_type(&pending.press, EventType::KeyPress(hold)));
+            self.replay(pending.buffered, output);
+        }
+    }
+
+    /// Sends the dual-role key as a tap, then the events held back behind it,
+    /// then the release of the tap.
+    fn resolve_tap(&mut self, release: &Event, output: &mut Vec<Event>) {
+        if let Some(pending) = self.pending.take() {
+            let tap = self.bindings[&pending.key].tap;
+            output.push(with_type(&pending.press, EventType::KeyPress(tap)));
+            self.replay(pending.buffered, output);
+            output.push(with_type(release, EventType::KeyRelease(tap)));
+        }
+    }
+
+    /// Held back events can be dual-role keys themselves, so they go through
+    /// the processor again.
+    fn replay(&mut self, events: Vec<(Event, Instant)>, output: &mut Vec<Event>) {
+        for (event, now) in events {
+            self.process(event, now, output);
+        }
+    }
+
+    fn process_pending(&mut self, event: Event, now: Instant, output: &mut Vec<Event>) {
+        let pending = match &mut self.pending {
+            Some(pending) => pending,
+            None => return,
+        };
+        let hold = match event.event_type {
+            // Key repeat
+            EventType::KeyPress(key) if key == pending.key => return,
+            EventType::KeyRelease(key) if key == pending.key => {
+                self.resolve_tap(&event, output);
+                return;
+            }
+            EventType::KeyPress(_) | EventType::ButtonPress(_) => self.hold_on_other_key_press,
+            EventType::KeyRelease(key) => {
+                self.permissive_hold && pending.was_pressed(EventType::KeyPress(key))
+            }
+            EventType::ButtonRelease(button) => {
+                self.permissive_hold && pending.was_pressed(EventType::ButtonPress(button))
+            }
+            _ => false,
+        };
+        pending.buffered.push((event, now));
+        if hold {
+            self.resolve_hold(output);
+        }
+    }
+}
+
+impl Pending {
+    /// Whether `press` was held back, the other key was pressed after the
+    /// dual-role key then.
+    fn was_pressed(&self, press: EventType) -> bool {
+        self.buffered
+            .iter()
+            .any(|(event, _)| event.event_type == press)
+    }
+}
+
+impl EventProcessor for TapHold {
+    fn process(&mut self, event: Event, now: Instant, output: &mut Vec<Event>) {
+        if self.pending.is_some() {
+            self.process_pending(event, now, output);
+            return;
+        }
+        match event.event_type {
+            EventType::KeyPress(key) => {
+                if let Some(hold) = self.held.get(&key) {
+                    // Key repeat
+                    output.push(with_type(&event, EventType::KeyPress(*hold)));
+                } else if self.bindings.contains_key(&key) {
+                    self.pending = Some(Pending {
+                        key,
+                        press: event,
+                        deadline: now + self.tapping_term,
+                        buffered: vec![],
+                    });
+                } else {
+                    output.push(event);
+                }
+            }
+            EventType::KeyRelease(key) => match self.held.remove(&key) {
+                Some(hold) => output.push(with_type(&event, EventType::KeyRelease(hold))),
+                None => output.push(event),
+            },
+            _ => output.push(event),
+        }
+    }
+
+    fn deadline(&self) -> Option<Instant> {
+        self.pending.as_ref().map(|pending| pending.deadline)
+    }
+
+    fn timeout(&mut self, now: Instant, output: &mut Vec<Event>) {
+        // Replaying can leave another dual-role key pending, already past its term
+        while self.deadline().is_some_and(|deadline| deadline <= now) {
+            self.resolve_hold(output);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::rdev::Button;
+    use std::time::SystemTime;
+
+    fn event(event_type: EventType) -> Event {
+        Event {
+            time: SystemTime::now(),
+            name: None,
+            event_type,
+            device_id: None,
+        }
+    }
+
+    fn run(tap_hold: &mut TapHold, events: &[(EventType, Duration)]) -> Vec<EventType> {
+        let start = Instant::now();
+        let mut output = vec![];
+        for (event_type, at) in events {
+            let now = start + *at;
+            if tap_hold.deadline().is_some_and(|deadline| deadline <= now) {
+                tap_hold.timeout(now, &mut output);
+            }
+            tap_hold.process(event(*event_type), now, &mut output);
+        }
+        output.into_iter().map(|event| event.event_type).collect()
+    }
+
+    fn ms(millis: u64) -> Duration {
+        Duration::from_millis(millis)
+    }
+
+    fn caps() -> TapHold {
+        TapHold::new().bind(Key::CapsLock, Key::Escape, Key::ControlLeft)
+    }
+
+    use EventType::{KeyPress as Press, KeyRelease as Release};
+
+    #[test]
+    fn test_tap() {
+        let output = run(
+            &mut caps(),
+            &[
+                (Press(Key::CapsLock), ms(0)),
+                (Release(Key::CapsLock), ms(50)),
+            ],
+        );
+        assert_eq!(output, vec![Press(Key::Escape), Release(Key::Escape)]);
+    }
+
+    #[test]
+    fn test_hold_after_tapping_term() {
+        let mut tap_hold = caps();
+        let output = run(
+            &mut tap_hold,
+            &[
+                (Press(Key::CapsLock), ms(0)),
+                (Press(Key::CapsLock), ms(100)),
+                (Press(Key::KeyC), ms(300)),
+                (Release(Key::KeyC), ms(320)),
+                (Release(Key::CapsLock), ms(400)),
+            ],
+        );
+        assert_eq!(
+            output,
+            vec![
+                Press(Key::ControlLeft),
+                Press(Key::KeyC),
+                Release(Key::KeyC),
+                Release(Key::ControlLeft),
+            ]
+        );
+        assert_eq!(tap_hold.deadline(), None);
+    }
+
+    #[test]
+    fn test_nested_tap_keeps_order() {
+        let output = run(
+            &mut caps(),
+            &[
+                (Press(Key::CapsLock), ms(0)),
+                (Press(Key::KeyA), ms(20)),
+                (Release(Key::KeyA), ms(40)),
+                (Release(Key::CapsLock), ms(60)),
+            ],
+        );
+        assert_eq!(
+            output,
+            vec![
+                Press(Key::Escape),
+                Press(Key::KeyA),
+                Release(Key::KeyA),
+                Release(Key::Escape),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_permissive_hold() {
+        let output = run(
+            &mut caps().permissive_hold(true),
+            &[
+                (Press(Key::CapsLock), ms(0)),
+                (Press(Key::KeyA), ms(20)),
+                (Release(Key::KeyA), ms(40)),
+                (Release(Key::CapsLock), ms(60)),
+            ],
+        );
+        assert_eq!(
+            output,
+            vec![
+                Press(Key::ControlLeft),
+                Press(Key::KeyA),
+                Release(Key::KeyA),
+                Release(Key::ControlLeft),
+            ]
+        );
+
+        // Rolling over to the next key is still a tap
+        let output = run(
+            &mut caps().permissive_hold(true),
+            &[
+                (Press(Key::CapsLock), ms(0)),
+                (Press(Key::KeyA), ms(20)),
+                (Release(Key::CapsLock), ms(40)),
+                (Release(Key::KeyA), ms(60)),
+            ],
+        );
+        assert_eq!(
+            output,
+            vec![
+                Press(Key::Escape),
+                Press(Key::KeyA),
+                Release(Key::Escape),
+                Release(Key::KeyA),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_hold_on_other_key_press() {
+        let output = run(
+            &mut caps().hold_on_other_key_press(true),
+            &[
+                (Press(Key::CapsLock), ms(0)),
+                (EventType::ButtonPress(Button::Left), ms(20)),
+                (Release(Key::CapsLock), ms(40)),
+                (EventType::ButtonRelease(Button::Left), ms(60)),
+            ],
+        );
+        assert_eq!(
+            output,
+            vec![
+                Press(Key::ControlLeft),
+                EventType::ButtonPress(Button::Left),
+                Release(Key::ControlLeft),
+                EventType::ButtonRelease(Button::Left),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_home_row_mods() {
+        let mut tap_hold = TapHold::new()
+            .bind(Key::KeyF, Key::KeyF, Key::ShiftLeft)
+            .bind(Key::KeyD, Key::KeyD, Key::ControlLeft);
+        // Both held past the tapping term
+        let output = run(
+            &mut tap_hold,
+            &[
+                (Press(Key::KeyF), ms(0)),
+                (Press(Key::KeyD), ms(50)),
+                (Press(Key::KeyJ), ms(300)),
+                (Release(Key::KeyJ), ms(310)),
+                (Release(Key::KeyD), ms(320)),
+                (Release(Key::KeyF), ms(330)),
+            ],
+        );
+        assert_eq!(
+            output,
+            vec![
+                Press(Key::ShiftLeft),
+                Press(Key::ControlLeft),
+                Press(Key::KeyJ),
+                Release(Key::KeyJ),
+                Release(Key::ControlLeft),
+                Release(Key::ShiftLeft),
+            ]
+        );
+
+        // Typing "fd" quickly
+        let output = run(
+            &mut tap_hold,
+            &[
+                (Press(Key::KeyF), ms(0)),
+                (Press(Key::KeyD), ms(30)),
+                (Release(Key::KeyF), ms(60)),
+                (Release(Key::KeyD), ms(90)),
+            ],
+        );
+        assert_eq!(
+            output,
+            vec![
+                Press(Key::KeyF),
+                Release(Key::KeyF),
+                Press(Key::KeyD),
+                Release(Key::KeyD),
+            ]
+        );
+    }
+}
@@ -1,5 +1,6 @@
 #[cfg(feature = "serialize")]
 use serde::{Deserialize, Serialize};
+use std::any::Any;
 use std::time::SystemTime;
 use std::{fmt, fmt::Display};
 
@@ -29,10 +30,19 @@ pub enum ListenError {
     RecordContextError,
     /// Linux
     XRecordExtensionError,
+    /// Linux, the X server lacks XInput 2.1
+    XInputExtensionError,
     /// Windows
     KeyHookError(u32),
     /// Windows
     MouseHookError(u32),
+    /// Linux
+    IoError(std::io::Error),
+    /// All, the requested `Backend` is not available on this platform or not compiled in
+    UnsupportedBackend,
+    /// All, the callback panicked and listening stopped. The payload can be passed
+    /// on to `std::panic::resume_unwind`.
+    CallbackPanic(Box<dyn Any + Send>),
 }
 
 /// Errors that occur when trying to grab OS events.
@@ -49,6 +59,8 @@ pub enum GrabError {
     MissingDisplayError,
     /// Linux
     KeyboardError,
+    /// Linux, the X server lacks XInput 2.1
+    XInputExtensionError,
     /// Windows
     KeyHookError(u32),
     /// Windows
@@ -56,6 +68,16 @@ pub enum GrabError {
     /// All
     SimulateError,
     IoError(std::io::Error),
+    /// All, the requested `Backend` is not available on this platform or not compiled in
+    UnsupportedBackend,
+    /// Linux, the escape combo was pressed and every device released
+    EscapeCombo,
+    /// Linux, handling an event took longer than the watchdog allows and every
+    /// device was released
+    WatchdogTimeout,
+    /// All, the callback (or a processor) panicked and every device was released.
+    /// The payload can be passed on to `std::panic::resume_unwind`.
+    CallbackPanic(Box<dyn Any + Send>),
 }
 /// Errors that occur when trying to get display size.
 #[non_exhaustive]
@@ -71,6 +93,12 @@ impl From<SimulateError> for GrabError {
     }
 }
 
+impl From<std::io::Error> for ListenError {
+    fn from(err: std::io::Error) -> ListenError {
+        ListenError::IoError(err)
+    }
+}
+
 impl From<std::io::Error> for GrabError {
     fn from(err: std::io::Error) -> GrabError {
         GrabError::IoError(err)
@@ -89,6 +117,59 @@ impl Display for SimulateError {
 
 impl std::error::Error for SimulateError {}
 
+/// Marking an error when a `Key`, `Button` or `KeyCombo` could not be parsed
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ParseKeyError {
+    input: String,
+}
+
+impl ParseKeyError {
+    pub(crate) fn new(input: &str) -> ParseKeyError {
+        ParseKeyError {
+            input: input.to_string(),
+        }
+    }
+}
+
+impl Display for ParseKeyError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "Could not parse key {:?}", self.input)
+    }
+}
+
+impl std::error::Error for ParseKeyError {}
+
+/// Errors in a `RemapConfig`, see `Remapper::load`.
+#[cfg(feature = "serialize")]
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum ConfigError {
+    /// A key, button or combination name could not be parsed
+    Key(ParseKeyError),
+    /// A binding switches to a layer missing from the configuration
+    UnknownLayer(String),
+}
+
+#[cfg(feature = "serialize")]
+impl Display for ConfigError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            ConfigError::Key(error) => write!(f, "{}", error),
+            ConfigError::UnknownLayer(name) => write!(f, "Unknown layer {:?}", name),
+        }
+    }
+}
+
+#[cfg(feature = "serialize")]
+impl std::error::Error for ConfigError {}
+
+#[cfg(feature = "serialize")]
+impl From<ParseKeyError> for ConfigError {
+    fn from(error: ParseKeyError) -> ConfigError {
+        ConfigError::Key(error)
+    }
+}
+
 /// Key names based on physical location on the device
 /// Merge Option(MacOS) and Alt(Windows, Linux) into Alt
 /// Merge Windows (Windows), Meta(Linux), Command(MacOS) into Meta
@@ -240,6 +321,112 @@ pub enum Button {
     Unknown(u8),
 }
 
+/// The locks toggled by `Key::CapsLock`, `Key::NumLock` and `Key::ScrollLock`,
+/// usually shown by keyboard LEDs.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serialize", derive(Serialize, Deserialize))]
+pub enum Lock {
+    CapsLock,
+    NumLock,
+    ScrollLock,
+}
+
+impl Lock {
+    /// The key toggling the lock.
+    pub fn key(self) -> Key {
+        match self {
+            Lock::CapsLock => Key::CapsLock,
+            Lock::NumLock => Key::NumLock,
+            Lock::ScrollLock => Key::ScrollLock,
+        }
+    }
+}
+
+/// Gamepad buttons, named after their place on Xbox style controllers: `South`
+/// is A there, and cross on PlayStation controllers.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serialize", derive(Serialize, Deserialize))]
+pub enum GamepadButton {
+    South,
+    East,
+    North,
+    West,
+    LeftBumper,
+    RightBumper,
+    Select,
+    Start,
+    /// The logo button in the middle.
+    Mode,
+    /// Pressing the left stick.
+    LeftThumb,
+    RightThumb,
+    DpadUp,
+    DpadDown,
+    DpadLeft,
+    DpadRight,
+    /// A button outside the standard layout, like the ones of joysticks, by its
+    /// `BTN_*` code of `linux/input.h`.
+    Other(u16),
+}
+
+/// Gamepad sticks and triggers.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serialize", derive(Serialize, Deserialize))]
+pub enum GamepadAxis {
+    LeftStickX,
+    LeftStickY,
+    RightStickX,
+    RightStickY,
+    LeftTrigger,
+    RightTrigger,
+}
+
+impl GamepadAxis {
+    /// Sticks go from -1.0 to 1.0, rightward and downward, triggers go from 0.0
+    /// when released to 1.0.
+    pub fn min(self) -> f64 {
+        match self {
+            GamepadAxis::LeftTrigger | GamepadAxis::RightTrigger => 0.0,
+            _ => -1.0,
+        }
+    }
+}
+
+/// Where a touch is in its life, see `EventType::Touch`.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serialize", derive(Serialize, Deserialize))]
+pub enum TouchPhase {
+    Start,
+    Move,
+    End,
+}
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serialize", derive(Serialize, Deserialize))]
+pub enum SwipeDirection {
+    Up,
+    Down,
+    Left,
+    Right,
+}
+
+/// Gestures made of several touches, see `ListenerBuilder::gestures`.
+#[derive(Debug, Copy, Clone, PartialEq)]
+#[cfg_attr(feature = "serialize", derive(Serialize, Deserialize))]
+pub enum Gesture {
+    /// Two fingers moving together, in pixels since the previous one, positive
+    /// rightward and downward.
+    Scroll { delta_x: f64, delta_y: f64 },
+    /// Two fingers moving apart or closer, `scale` being their distance over the
+    /// one they had when the pinch started.
+    Pinch { scale: f64 },
+    /// Three fingers or more moved together, reported once they are lifted.
+    Swipe {
+        fingers: u32,
+        direction: SwipeDirection,
+    },
+}
+
 /// In order to manage different OSs, the current EventType choices are a mix and
 /// match to account for all possible events.
 #[derive(Debug, Copy, Clone, PartialEq)]
@@ -258,6 +445,12 @@ pub enum EventType {
         x: f64,
         y: f64,
     },
+    /// Values in pixels, relative to the current pointer position. This is only
+    /// ever simulated, listening reports the resulting `MouseMove` instead.
+    MouseMoveRelative {
+        delta_x: f64,
+        delta_y: f64,
+    },
     /// `delta_y` represents vertical scroll and `delta_x` represents horizontal scroll.
     /// Positive values correspond to scrolling up or right and negative values
     /// correspond to scrolling down or left
@@ -265,6 +458,56 @@ pub enum EventType {
         delta_x: i64,
         delta_y: i64,
     },
+    /// High resolution scrolling in wheel notches, with the same signs as `Wheel`.
+    /// Listening reports it for devices that scroll finely (X11 scroll valuators,
+    /// evdev high resolution wheels), along with the usual `Wheel` for every full notch.
+    /// Simulating it rounds to whole notches where the OS has no finer scrolling.
+    SmoothWheel {
+        delta_x: f64,
+        delta_y: f64,
+    },
+    /// A lock turned on or off. Listening reports it on Linux only, after the key
+    /// event that toggled it on X11, and when the keyboard LEDs change with evdev.
+    /// Simulating it sets the lock, doing nothing when it already is in that state.
+    LockChange {
+        lock: Lock,
+        on: bool,
+    },
+    /// An absolute axis of an evdev device moved, like the travel of an analog key,
+    /// a joystick or a pedal. `code` is the `ABS_*` code of `linux/input.h` and
+    /// `value` goes from `min` to `max`, the range the device advertises. The
+    /// device is the `Event::device_id`. Only the evdev backend reports it, and
+    /// only `VirtualDevice` simulates it.
+    Axis {
+        code: u16,
+        value: i32,
+        min: i32,
+        max: i32,
+    },
+    /// Gamepad and joystick buttons, d-pads reporting as hats included. Only the
+    /// evdev backend reports them, and only `VirtualDevice` simulates them.
+    GamepadButtonPress(GamepadButton),
+    GamepadButtonRelease(GamepadButton),
+    /// A stick or a trigger moved, see `GamepadAxis::min` for the range of `value`.
+    /// Joystick axes outside the standard layout are reported as `Axis`.
+    GamepadAxis {
+        axis: GamepadAxis,
+        value: f64,
+    },
+    /// A finger on a touchscreen or a touchpad. `slot` is the multitouch slot the
+    /// kernel tracks it in, and `id` tells it apart from the previous touches of the
+    /// slot. `x` and `y` are in pixels like `MouseMove`, touchpads being mapped onto
+    /// the whole display. `pressure` goes from 0.0 to 1.0, for devices measuring it.
+    /// Only the evdev backend reports it, and it cannot be simulated.
+    Touch {
+        phase: TouchPhase,
+        slot: u32,
+        id: i32,
+        x: f64,
+        y: f64,
+        pressure: Option<f64>,
+    },
+    Gesture(Gesture),
 }
 
 /// When events arrive from the OS they get some additional information added from
@@ -276,9 +519,27 @@ pub enum EventType {
 #[derive(Debug, Clone, PartialEq)]
 #[cfg_attr(feature = "serialize", derive(Serialize, Deserialize))]
 pub struct Event {
+    /// When the event was received, or when the kernel saw it with the evdev grab.
     pub time: SystemTime,
     pub name: Option<String>,
     pub event_type: EventType,
+    /// The physical device the event comes from, when the backend knows it.
+    /// On X11 this is the XInput2 source device id, with evdev it is the N of the
+    /// `/dev/input/eventN` node, as in `DeviceInfo.id`. It is `None` elsewhere.
+    #[cfg_attr(feature = "serialize", serde(default))]
+    pub device_id: Option<u32>,
+}
+
+/// What tells a device apart from the others whenever and wherever it is plugged,
+/// unlike `Event.device_id` which only holds while it stays plugged.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serialize", derive(Serialize, Deserialize))]
+pub struct DeviceIdentity {
+    pub name: String,
+    pub vendor: u16,
+    pub product: u16,
+    /// Where the device is plugged, like `usb-0000:00:14.0-2/input0`.
+    pub phys: Option<String>,
 }
 
 /// We can define a dummy Keyboard, that we will use to detect
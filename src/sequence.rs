@@ -0,0 +1,526 @@
+use std::collections::{HashMap, HashSet};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex, MutexGuard, Weak};
+use std::thread;
+use std::time::{Duration, Instant};
+
+use crate::hotkey::{is_modifier, left_side, KeyCombo};
+#[cfg(feature = "unstable_grab")]
+use crate::rdev::GrabError;
+use crate::rdev::{Event, EventType, Key, ListenError};
+
+const DEFAULT_TIMEOUT: Duration = Duration::from_secs(1);
+/// How often `SequenceManager::listen` and `SequenceManager::grab` check for timeouts.
+const POLL_INTERVAL: Duration = Duration::from_millis(10);
+
+/// Returned by `SequenceManager::register`, to unregister the sequence later on.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
+pub struct SequenceId(u64);
+
+type SequenceCallback = Arc<Mutex<dyn FnMut() + Send>>;
+type ProgressCallback = Arc<Mutex<dyn FnMut(&[KeyCombo]) + Send>>;
+
+#[derive(Default)]
+struct Node {
+    children: HashMap<KeyCombo, Node>,
+    binding: Option<(SequenceId, SequenceCallback)>,
+}
+
+impl Node {
+    fn get(&self, path: &[KeyCombo]) -> Option<&Node> {
+        path.iter()
+            .try_fold(self, |node, step| node.children.get(step))
+    }
+
+    /// Removes the binding at `path`, and the nodes left without bindings.
+    fn remove(&mut self, path: &[KeyCombo]) {
+        match path.split_first() {
+            None => self.binding = None,
+            Some((step, rest)) => {
+                if let Some(child) = self.children.get_mut(step) {
+                    child.remove(rest);
+                    if child.binding.is_none() && child.children.is_empty() {
+                        self.children.remove(step);
+                    }
+                }
+            }
+        }
+    }
+}
+
+struct State {
+    next_id: u64,
+    root: Node,
+    paths: HashMap<SequenceId, Vec<KeyCombo>>,
+    timeout: Duration,
+    consume: bool,
+    on_progress: Option<ProgressCallback>,
+    /// The steps typed so far, always a path of the trie.
+    progress: Vec<KeyCombo>,
+    deadline: Option<Instant>,
+    pressed: HashSet<Key>,
+    consumed: HashSet<Key>,
+}
+
+impl Default for State {
+    fn default() -> State {
+        State {
+            next_id: 0,
+            root: Node::default(),
+            paths: HashMap::new(),
+            timeout: DEFAULT_TIMEOUT,
+            consume: false,
+            on_progress: None,
+            progress: vec![],
+            deadline: None,
+            pressed: HashSet::new(),
+            consumed: HashSet::new(),
+        }
+    }
+}
+
+impl State {
+    /// Gives up on the current progress, firing the binding it stopped on if any.
+    fn resolve(&mut self, fired: &mut Vec<SequenceCallback>) {
+        if let Some((_, callback)) = self
+            .root
+            .get(&self.progress)
+            .and_then(|n| n.binding.as_ref())
+        {
+            fired.push(callback.clone());
+        }
+        self.progress.clear();
+        self.deadline = None;
+    }
+
+    fn step(&mut self, step: KeyCombo, now: Instant, fired: &mut Vec<SequenceCallback>) -> bool {
+        loop {
+            let node = match self.root.get(&self.progress) {
+                Some(node) => node,
+                None => {
+                    // The sequence we were in got unregistered.
+                    self.progress.clear();
+                    &self.root
+                }
+            };
+            if let Some(child) = node.children.get(&step) {
+                if child.children.is_empty() {
+                    if let Some((_, callback)) = &child.binding {
+                        fired.push(callback.clone());
+                    }
+                    self.progress.clear();
+                    self.deadline = None;
+                } else {
+                    self.progress.push(step);
+                    self.deadline = Some(now + self.timeout);
+                }
+                return true;
+            }
+            if self.progress.is_empty() {
+                return false;
+            }
+            // The key doesn't continue any sequence: a shorter sequence we were
+            // waiting on wins, then the key gets a chance to start a new one.
+            self.resolve(fired);
+        }
+    }
+}
+
+/// Runs callbacks when sequences of key combinations are typed, like
+/// `Ctrl+K, Ctrl+C` or a leader key followed by `g, s` (the leader is just the
+/// first step). Steps match no matter which side the modifiers are pressed on,
+/// and keys pressed while a non modifier key is still held don't interfere, so
+/// fast typing works.
+///
+/// Each step must come within the timeout of the previous one, or the sequence
+/// is abandoned. When a sequence is the prefix of another one (`g` and `g, s`),
+/// the shorter one fires once the timeout expires or another key is pressed.
+///
+/// Like `HotkeyManager`, the manager is cheap to clone, clones share their
+/// state, and sequences can be registered and unregistered at any time.
+///
+/// ```no_run
+/// use rdev::{Key, KeyCombo, SequenceManager};
+/// use std::time::Duration;
+///
+/// let manager = SequenceManager::new();
+/// manager.set_timeout(Duration::from_millis(500));
+/// manager.on_progress(|steps| println!("Waiting for more after {:?}", steps));
+/// manager.register(
+///     vec![KeyCombo::new(Key::KeyK).ctrl(), KeyCombo::new(Key::KeyC).ctrl()],
+///     || println!("Ctrl+K, Ctrl+C"),
+/// );
+/// manager.register(
+///     vec![Key::Space.into(), Key::KeyG.into(), Key::KeyS.into()],
+///     || println!("Leader, g, s"),
+/// );
+/// // This will block.
+/// if let Err(error) = manager.listen() {
+///     println!("Error: {:?}", error)
+/// }
+/// ```
+#[derive(Clone, Default)]
+pub struct SequenceManager {
+    state: Arc<Mutex<State>>,
+}
+
+impl SequenceManager {
+    pub fn new() -> SequenceManager {
+        SequenceManager::default()
+    }
+
+    /// Longest time between two steps of a sequence, one second by default.
+    pub fn set_timeout(&self, timeout: Duration) {
+        self.lock().timeout = timeout;
+    }
+
+    /// When grabbing, swallow the keys that are part of a sequence. Keys typed
+    /// at the start of a sequence that is then abandoned are lost.
+    pub fn set_consume(&self, consume: bool) {
+        self.lock().consume = consume;
+    }
+
+    /// Called with the steps typed so far whenever they change, with an empty
+    /// slice when a sequence completes or is abandoned. Meant to show which
+    /// keys the user can type next.
+    pub fn on_progress<F>(&self, callback: F)
+    where
+        F: FnMut(&[KeyCombo]) + Send + 'static,
+    {
+        self.lock().on_progress = Some(Arc::new(Mutex::new(callback)));
+    }
+
+    /// Registering a sequence that already is replaces it. An empty sequence never fires.
+    pub fn register<S, F>(&self, sequence: S, callback: F) -> SequenceId
+    where
+        S: IntoIterator<Item = KeyCombo>,
+        F: FnMut() + Send + 'static,
+    {
+        let path: Vec<KeyCombo> = sequence.into_iter().map(|step| step.sideless()).collect();
+        let mut state = self.lock();
+        let id = SequenceId(state.next_id);
+        state.next_id += 1;
+        let node = path.iter().fold(&mut state.root, |node, step| {
+            node.children.entry(step.clone()).or_default()
+        });
+        if let Some((previous, _)) = node.binding.replace((id, Arc::new(Mutex::new(callback)))) {
+            state.paths.remove(&previous);
+        }
+        state.paths.insert(id, path);
+        id
+    }
+
+    /// Returns whether the sequence was still registered.
+    pub fn unregister(&self, id: SequenceId) -> bool {
+        let mut state = self.lock();
+        match state.paths.remove(&id) {
+            Some(path) => {
+                state.root.remove(&path);
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// The steps typed so far.
+    pub fn progress(&self) -> Vec<KeyCombo> {
+        self.lock().progress.clone()
+    }
+
+    /// Feeds an event to the manager, running the callbacks of the sequences
+    /// it completes. Returns whether the event should be swallowed, which
+    /// only matters when grabbing and consuming.
+    pub fn process(&self, event: &Event) -> bool {
+        self.process_at(event, Instant::now())
+    }
+
+    /// Fires or abandons the current sequence once its timeout expired.
+    /// `SequenceManager::listen` and `SequenceManager::grab` call it for you,
+    /// otherwise call it regularly, as events alone cannot tell when the
+    /// user stopped typing.
+    pub fn poll(&self) {
+        self.poll_at(Instant::now())
+    }
+
+    fn process_at(&self, event: &Event, now: Instant) -> bool {
+        let mut fired = vec![];
+        let mut state = self.lock();
+        let before = state.progress.clone();
+        let consume = match event.event_type {
+            EventType::KeyPress(key) => {
+                if !state.pressed.insert(key) {
+                    // Key repeat
+                    return state.consumed.contains(&key);
+                }
+                if is_modifier(key) {
+                    return false;
+                }
+                if state.deadline.is_some_and(|deadline| deadline <= now) {
+                    state.resolve(&mut fired);
+                }
+                let step = KeyCombo {
+                    modifiers: state
+                        .pressed
+                        .iter()
+                        .filter(|pressed| is_modifier(**pressed))
+                        .map(|pressed| left_side(*pressed))
+                        .collect(),
+                    key,
+                };
+                let consume = state.step(step, now, &mut fired) && state.consume;
+                if consume {
+                    state.consumed.insert(key);
+                }
+                consume
+            }
+            EventType::KeyRelease(key) => {
+                state.pressed.remove(&key);
+                state.consumed.remove(&key)
+            }
+            _ => false,
+        };
+        self.notify(state, before, fired);
+        consume
+    }
+
+    fn poll_at(&self, now: Instant) {
+        let mut fired = vec![];
+        let mut state = self.lock();
+        let before = state.progress.clone();
+        if state.deadline.is_some_and(|deadline| deadline <= now) {
+            state.resolve(&mut fired);
+        }
+        self.notify(state, before, fired);
+    }
+
+    /// Runs the callbacks once the lock is released, so they can register and
+    /// unregister sequences.
+    fn notify(
+        &self,
+        state: MutexGuard<'_, State>,
+        before: Vec<KeyCombo>,
+        fired: Vec<SequenceCallback>,
+    ) {
+        let progress = match &state.on_progress {
+            Some(on_progress) if state.progress != before => {
+                Some((on_progress.clone(), state.progress.clone()))
+            }
+            _ => None,
+        };
+        drop(state);
+        for callback in fired {
+            if let Ok(mut callback) = callback.lock() {
+                callback();
+            }
+        }
+        if let Some((on_progress, steps)) = progress {
+            if let Ok(mut on_progress) = on_progress.lock() {
+                on_progress(&steps);
+            }
+        }
+    }
+
+    /// Checks for timeouts until the returned timer or the manager is dropped.
+    fn spawn_timer(&self) -> Timer {
+        let state: Weak<Mutex<State>> = Arc::downgrade(&self.state);
+        let stop = Arc::new(AtomicBool::new(false));
+        let stopped = stop.clone();
+        thread::spawn(move || loop {
+            thread::sleep(POLL_INTERVAL);
+            if stopped.load(Ordering::SeqCst) {
+                break;
+            }
+            match state.upgrade() {
+                Some(state) => SequenceManager { state }.poll(),
+                None => break,
+            }
+        });
+        Timer { stop }
+    }
+
+    /// Listens to global events and runs the sequences, consuming is not possible here.
+    pub fn listen(self) -> Result<(), ListenError> {
+        // Clones of the manager may outlive listening, the timer must not
+        let _timer = self.spawn_timer();
+        crate::listen(move |event| {
+            self.process(&event);
+        })
+    }
+
+    /// Grabs global events and runs the sequences, see `set_consume`.
+    #[cfg(feature = "unstable_grab")]
+    pub fn grab(self) -> Result<(), GrabError> {
+        let _timer = self.spawn_timer();
+        crate::grab(move |event| {
+            if self.process(&event) {
+                None
+            } else {
+                Some(event)
+            }
+        })
+    }
+
+    fn lock(&self) -> MutexGuard<'_, State> {
+        // A panicking callback never holds this lock, the state stays consistent.
+        self.state
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner())
+    }
+}
+
+/// Stops the thread started by `SequenceManager::spawn_timer` when dropped.
+struct Timer {
+    stop: Arc<AtomicBool>,
+}
+
+impl Drop for Timer {
+    fn drop(&mut self) {
+        self.stop.store(true, Ordering::SeqCst);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::time::SystemTime;
+
+    fn event(event_type: EventType) -> Event {
+        Event {
+            time: SystemTime::now(),
+            name: None,
+            event_type,
+            device_id: None,
+        }
+    }
+
+    fn tap(manager: &SequenceManager, key: Key, now: Instant) -> bool {
+        let consumed = manager.process_at(&event(EventType::KeyPress(key)), now);
+        manager.process_at(&event(EventType::KeyRelease(key)), now);
+        consumed
+    }
+
+    fn counter<S>(manager: &SequenceManager, sequence: S) -> Arc<AtomicUsize>
+    where
+        S: IntoIterator<Item = KeyCombo>,
+    {
+        let count = Arc::new(AtomicUsize::new(0));
+        let inner = count.clone();
+        manager.register(sequence, move || {
+            inner.fetch_add(1, Ordering::SeqCst);
+        });
+        count
+    }
+
+    #[test]
+    fn test_modified_steps() {
+        let manager = SequenceManager::new();
+        let count = counter(
+            &manager,
+            vec![
+                KeyCombo::new(Key::KeyK).ctrl(),
+                KeyCombo::new(Key::KeyC).ctrl(),
+            ],
+        );
+        let now = Instant::now();
+        manager.process_at(&event(EventType::KeyPress(Key::ControlRight)), now);
+        tap(&manager, Key::KeyK, now);
+        assert_eq!(manager.progress().len(), 1);
+        tap(&manager, Key::KeyC, now);
+        assert_eq!(count.load(Ordering::SeqCst), 1);
+        assert!(manager.progress().is_empty());
+    }
+
+    #[test]
+    fn test_timeout_abandons_sequence() {
+        let manager = SequenceManager::new();
+        let count = counter(&manager, vec![Key::KeyG.into(), Key::KeyS.into()]);
+        let now = Instant::now();
+        tap(&manager, Key::KeyG, now);
+        tap(&manager, Key::KeyS, now + DEFAULT_TIMEOUT * 2);
+        assert_eq!(count.load(Ordering::SeqCst), 0);
+    }
+
+    #[test]
+    fn test_prefix_ambiguity() {
+        let manager = SequenceManager::new();
+        let short = counter(&manager, vec![Key::KeyG.into()]);
+        let long = counter(&manager, vec![Key::KeyG.into(), Key::KeyS.into()]);
+        let now = Instant::now();
+
+        tap(&manager, Key::KeyG, now);
+        tap(&manager, Key::KeyS, now);
+        assert_eq!(short.load(Ordering::SeqCst), 0);
+        assert_eq!(long.load(Ordering::SeqCst), 1);
+
+        // Another key resolves to the short sequence
+        tap(&manager, Key::KeyG, now);
+        tap(&manager, Key::KeyX, now);
+        assert_eq!(short.load(Ordering::SeqCst), 1);
+
+        // So does the timeout
+        tap(&manager, Key::KeyG, now);
+        manager.poll_at(now + DEFAULT_TIMEOUT);
+        assert_eq!(short.load(Ordering::SeqCst), 2);
+        assert_eq!(long.load(Ordering::SeqCst), 1);
+    }
+
+    #[test]
+    fn test_mismatch_starts_new_sequence() {
+        let manager = SequenceManager::new();
+        let count = counter(&manager, vec![Key::KeyG.into(), Key::KeyS.into()]);
+        let now = Instant::now();
+        tap(&manager, Key::KeyG, now);
+        tap(&manager, Key::KeyG, now);
+        tap(&manager, Key::KeyS, now);
+        assert_eq!(count.load(Ordering::SeqCst), 1);
+    }
+
+    #[test]
+    fn test_progress_callback() {
+        let manager = SequenceManager::new();
+        counter(
+            &manager,
+            vec![Key::Space.into(), Key::KeyG.into(), Key::KeyS.into()],
+        );
+        let lengths = Arc::new(Mutex::new(vec![]));
+        let inner = lengths.clone();
+        manager.on_progress(move |steps| inner.lock().unwrap().push(steps.len()));
+        let now = Instant::now();
+        tap(&manager, Key::Space, now);
+        tap(&manager, Key::KeyG, now);
+        tap(&manager, Key::KeyS, now);
+        assert_eq!(*lengths.lock().unwrap(), vec![1, 2, 0]);
+    }
+
+    #[test]
+    fn test_consume_and_unregister() {
+        let manager = SequenceManager::new();
+        manager.set_consume(true);
+        let id = manager.register(vec![Key::KeyG.into(), Key::KeyS.into()], || {});
+        let now = Instant::now();
+        assert!(tap(&manager, Key::KeyG, now));
+        assert!(manager.unregister(id));
+        assert!(!manager.unregister(id));
+        assert!(!tap(&manager, Key::KeyS, now));
+        assert!(manager.progress().is_empty());
+    }
+
+    #[test]
+    fn test_timer_stops() {
+        let manager = SequenceManager::new();
+        manager.set_timeout(Duration::from_millis(1));
+        let count = counter(&manager, vec![Key::KeyG.into()]);
+        counter(&manager, vec![Key::KeyG.into(), Key::KeyS.into()]);
+        let timer = manager.spawn_timer();
+        tap(&manager, Key::KeyG, Instant::now());
+        thread::sleep(POLL_INTERVAL * 10);
+        assert_eq!(count.load(Ordering::SeqCst), 1);
+        // Once listening is over, the manager is left alone
+        drop(timer);
+        tap(&manager, Key::KeyG, Instant::now());
+        thread::sleep(POLL_INTERVAL * 10);
+        assert_eq!(count.load(Ordering::SeqCst), 1);
+        assert_eq!(manager.progress().len(), 1);
+    }
+}
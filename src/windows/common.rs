@@ -3,6 +3,7 @@ use crate::windows::keyboard::Keyboard;
 use crate::windows::keycodes::key_from_code;
 use crate::windows::{DWORD, LONG, MOUSE_BACKWARD, MOUSE_FORWARD, WORD};
 use lazy_static::lazy_static;
+use std::any::Any;
 use std::convert::TryInto;
 use std::os::raw::c_int;
 use std::sync::Mutex;
@@ -10,10 +11,10 @@ use windows_sys::Win32::Foundation::{GetLastError, WPARAM};
 use windows_sys::Win32::Foundation::{HLOCAL, LPARAM, LRESULT};
 use windows_sys::Win32::UI::WindowsAndMessaging::HHOOK;
 use windows_sys::Win32::UI::WindowsAndMessaging::{
-    SetWindowsHookExA, KBDLLHOOKSTRUCT, MSLLHOOKSTRUCT, WHEEL_DELTA, WH_KEYBOARD_LL, WH_MOUSE_LL,
-    WM_KEYDOWN, WM_KEYUP, WM_LBUTTONDOWN, WM_LBUTTONUP, WM_MBUTTONDOWN, WM_MBUTTONUP,
-    WM_MOUSEHWHEEL, WM_MOUSEMOVE, WM_MOUSEWHEEL, WM_RBUTTONDOWN, WM_RBUTTONUP, WM_SYSKEYDOWN,
-    WM_SYSKEYUP, WM_XBUTTONDOWN, WM_XBUTTONUP,
+    PostQuitMessage, SetWindowsHookExA, UnhookWindowsHookEx, KBDLLHOOKSTRUCT, MSLLHOOKSTRUCT,
+    WHEEL_DELTA, WH_KEYBOARD_LL, WH_MOUSE_LL, WM_KEYDOWN, WM_KEYUP, WM_LBUTTONDOWN, WM_LBUTTONUP,
+    WM_MBUTTONDOWN, WM_MBUTTONUP, WM_MOUSEHWHEEL, WM_MOUSEMOVE, WM_MOUSEWHEEL, WM_RBUTTONDOWN,
+    WM_RBUTTONUP, WM_SYSKEYDOWN, WM_SYSKEYUP, WM_XBUTTONDOWN, WM_XBUTTONUP,
 };
 pub const TRUE: i32 = 1;
 pub const FALSE: i32 = 0;
@@ -24,6 +25,10 @@ fn hiword(l: u32) -> u16 {
 }
 
 pub static mut HOOK: HHOOK = 0;
+static mut KEY_HOOK: HHOOK = 0;
+static mut MOUSE_HOOK: HHOOK = 0;
+/// Set when the callback panicked, the session is over then.
+pub static mut PANIC: Option<Box<dyn Any + Send>> = None;
 lazy_static! {
     pub(crate) static ref KEYBOARD: Mutex<Keyboard> = Mutex::new(Keyboard::new().unwrap());
 }
@@ -129,6 +134,7 @@ pub unsafe fn set_key_hook(callback: RawCallback) -> Result<(), HookError> {
         return Err(HookError::Key(error));
     }
     HOOK = hook;
+    KEY_HOOK = hook;
     Ok(())
 }
 
@@ -140,5 +146,16 @@ pub unsafe fn set_mouse_hook(callback: RawCallback) -> Result<(), HookError> {
         return Err(HookError::Mouse(error));
     }
     HOOK = hook;
+    MOUSE_HOOK = hook;
     Ok(())
 }
+
+/// Unwinding out of a hook is undefined behavior, so a panic of the callback
+/// is caught and ends the session instead: the hooks are removed and the message
+/// loop told to quit, `listen` or `grab` then returns the payload.
+pub unsafe fn stop_after_panic(payload: Box<dyn Any + Send>) {
+    PANIC = Some(payload);
+    UnhookWindowsHookEx(KEY_HOOK);
+    UnhookWindowsHookEx(MOUSE_HOOK);
+    PostQuitMessage(0);
+}
@@ -1,5 +1,8 @@
 use crate::rdev::{Event, EventType, GrabError};
-use crate::windows::common::{convert, set_key_hook, set_mouse_hook, HookError, HOOK, KEYBOARD};
+use crate::windows::common::{
+    convert, set_key_hook, set_mouse_hook, stop_after_panic, HookError, HOOK, KEYBOARD, PANIC,
+};
+use std::panic::{self, AssertUnwindSafe};
 use std::ptr::null_mut;
 use std::time::SystemTime;
 use windows_sys::Win32::Foundation::HWND;
@@ -22,9 +25,18 @@ unsafe extern "system" fn raw_callback(code: i32, param: usize, lpdata: isize) -
                 event_type,
                 time: SystemTime::now(),
                 name,
+                device_id: None,
             };
             if let Some(callback) = &mut GLOBAL_CALLBACK {
-                if callback(event).is_none() {
+                let result = match panic::catch_unwind(AssertUnwindSafe(|| callback(event))) {
+                    Ok(result) => result,
+                    // The event goes through, the grab is over
+                    Err(payload) => {
+                        stop_after_panic(payload);
+                        return CallNextHookEx(HOOK, code, param, lpdata);
+                    }
+                };
+                if result.is_none() {
                     // https://stackoverflow.com/questions/42756284/blocking-windows-mouse-click-using-setwindowshookex
                     // https://android.developreference.com/article/14560004/Blocking+windows+mouse+click+using+SetWindowsHookEx()
                     // https://cboard.cprogramming.com/windows-programming/99678-setwindowshookex-wm_keyboard_ll.html
@@ -56,6 +68,9 @@ where
         set_mouse_hook(raw_callback)?;
 
         GetMessageA(null_mut(), hwnd, 0, 0);
+        if let Some(payload) = PANIC.take() {
+            return Err(GrabError::CallbackPanic(payload));
+        }
     }
     Ok(())
 }
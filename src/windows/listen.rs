@@ -1,6 +1,9 @@
 use crate::rdev::{Event, EventType, ListenError};
-use crate::windows::common::{convert, set_key_hook, set_mouse_hook, HookError, HOOK, KEYBOARD};
+use crate::windows::common::{
+    convert, set_key_hook, set_mouse_hook, stop_after_panic, HookError, HOOK, KEYBOARD, PANIC,
+};
 use std::os::raw::c_int;
+use std::panic::{self, AssertUnwindSafe};
 use std::ptr::null_mut;
 use std::time::SystemTime;
 use windows_sys::Win32::Foundation::{LPARAM, LRESULT, WPARAM};
@@ -32,9 +35,12 @@ unsafe extern "system" fn raw_callback(code: c_int, param: WPARAM, lpdata: LPARA
                 event_type,
                 time: SystemTime::now(),
                 name,
+                device_id: None,
             };
             if let Some(callback) = &mut GLOBAL_CALLBACK {
-                callback(event);
+                if let Err(payload) = panic::catch_unwind(AssertUnwindSafe(|| callback(event))) {
+                    stop_after_panic(payload);
+                }
             }
         }
     }
@@ -51,6 +57,9 @@ where
         set_mouse_hook(raw_callback)?;
 
         GetMessageA(null_mut(), 0, 0, 0);
+        if let Some(payload) = PANIC.take() {
+            return Err(ListenError::CallbackPanic(payload));
+        }
     }
     Ok(())
 }
@@ -6,11 +6,11 @@ use std::convert::TryFrom;
 use std::ffi::c_int;
 use std::mem::size_of;
 use windows_sys::Win32::UI::Input::KeyboardAndMouse::{
-    SendInput, INPUT, INPUT_0, INPUT_KEYBOARD, INPUT_MOUSE, KEYBDINPUT, KEYEVENTF_KEYUP,
-    MOUSEEVENTF_ABSOLUTE, MOUSEEVENTF_HWHEEL, MOUSEEVENTF_LEFTDOWN, MOUSEEVENTF_LEFTUP,
-    MOUSEEVENTF_MIDDLEDOWN, MOUSEEVENTF_MIDDLEUP, MOUSEEVENTF_MOVE, MOUSEEVENTF_RIGHTDOWN,
-    MOUSEEVENTF_RIGHTUP, MOUSEEVENTF_VIRTUALDESK, MOUSEEVENTF_WHEEL, MOUSEEVENTF_XDOWN,
-    MOUSEEVENTF_XUP, MOUSEINPUT,
+    GetKeyState, SendInput, INPUT, INPUT_0, INPUT_KEYBOARD, INPUT_MOUSE, KEYBDINPUT,
+    KEYEVENTF_KEYUP, MOUSEEVENTF_ABSOLUTE, MOUSEEVENTF_HWHEEL, MOUSEEVENTF_LEFTDOWN,
+    MOUSEEVENTF_LEFTUP, MOUSEEVENTF_MIDDLEDOWN, MOUSEEVENTF_MIDDLEUP, MOUSEEVENTF_MOVE,
+    MOUSEEVENTF_RIGHTDOWN, MOUSEEVENTF_RIGHTUP, MOUSEEVENTF_VIRTUALDESK, MOUSEEVENTF_WHEEL,
+    MOUSEEVENTF_XDOWN, MOUSEEVENTF_XUP, MOUSEINPUT,
 };
 use windows_sys::Win32::UI::WindowsAndMessaging::{
     GetSystemMetrics, SM_CXVIRTUALSCREEN, SM_CYVIRTUALSCREEN, WHEEL_DELTA,
@@ -18,6 +18,14 @@ use windows_sys::Win32::UI::WindowsAndMessaging::{
 /// Not defined in win32 but define here for clarity
 static KEYEVENTF_KEYDOWN: u32 = 0;
 
+/// Windows scrolls by fractions of `WHEEL_DELTA`, so smooth wheel deltas go through as is.
+fn wheel_data(delta: f64) -> Result<LONG, SimulateError> {
+    if !delta.is_finite() {
+        return Err(SimulateError);
+    }
+    Ok((delta * f64::from(WHEEL_DELTA)).round() as LONG)
+}
+
 fn sim_mouse_event(flags: DWORD, data: LONG, dx: LONG, dy: LONG) -> Result<(), SimulateError> {
     let mut union: INPUT_0 = unsafe { std::mem::zeroed() };
     let inner_union = unsafe { &mut union.mi };
@@ -123,6 +131,15 @@ pub fn simulate(event_type: &EventType) -> Result<(), SimulateError> {
             }
             Ok(())
         }
+        EventType::SmoothWheel { delta_x, delta_y } => {
+            if *delta_x != 0.0 {
+                sim_mouse_event(MOUSEEVENTF_HWHEEL, wheel_data(*delta_x)?, 0, 0)?;
+            }
+            if *delta_y != 0.0 {
+                sim_mouse_event(MOUSEEVENTF_WHEEL, wheel_data(*delta_y)?, 0, 0)?;
+            }
+            Ok(())
+        }
         EventType::MouseMove { x, y } => {
             let width = unsafe { GetSystemMetrics(SM_CXVIRTUALSCREEN) };
             let height = unsafe { GetSystemMetrics(SM_CYVIRTUALSCREEN) };
@@ -137,5 +154,25 @@ pub fn simulate(event_type: &EventType) -> Result<(), SimulateError> {
                 (*y as i32 + 1) * 65535 / height,
             )
         }
+        EventType::MouseMoveRelative { delta_x, delta_y } => {
+            sim_mouse_event(MOUSEEVENTF_MOVE, 0, *delta_x as LONG, *delta_y as LONG)
+        }
+        EventType::LockChange { lock, on } => {
+            let code = code_from_key(lock.key()).ok_or(SimulateError)?;
+            // The low bit is set while the lock is on
+            let locked = unsafe { GetKeyState(code.into()) } & 1 != 0;
+            if locked == *on {
+                return Ok(());
+            }
+            let scan = scan_from_code(code).ok_or(SimulateError)?;
+            sim_keyboard_event(KEYEVENTF_KEYDOWN, code, scan)?;
+            sim_keyboard_event(KEYEVENTF_KEYUP, code, scan)
+        }
+        EventType::Axis { .. }
+        | EventType::GamepadButtonPress(_)
+        | EventType::GamepadButtonRelease(_)
+        | EventType::GamepadAxis { .. } => Err(SimulateError),
+        // Touches are only ever listened to
+        EventType::Touch { .. } | EventType::Gesture(_) => Err(SimulateError),
     }
 }